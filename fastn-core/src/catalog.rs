@@ -0,0 +1,416 @@
+/// `.ftl` files of a package are in `i18n/<language>/`, like `i18n/hi/main.ftl`
+pub const CATALOG_FOLDER: &str = "i18n";
+/// the header of the `translate` processor with the message id, the other headers are the
+/// arguments of the message
+pub const MESSAGE_ID_HEADER: &str = "message-id";
+/// the language of a message missing from every catalog of the request
+const FALLBACK_LANGUAGE: &str = "en";
+
+static MESSAGE_ID: once_cell::sync::Lazy<regex::Regex> = once_cell::sync::Lazy::new(|| {
+    regex::Regex::new(r"(?m)^([a-zA-Z][a-zA-Z0-9_-]*)[ \t]*=").unwrap()
+});
+static MESSAGE_ID_USE: once_cell::sync::Lazy<regex::Regex> = once_cell::sync::Lazy::new(|| {
+    regex::Regex::new(r"(?m)^[ \t]*message-id[ \t]*:[ \t]*([a-zA-Z][a-zA-Z0-9_-]*)[ \t]*$").unwrap()
+});
+
+/// `Catalogs` are the Fluent message catalogs of a package. A page reads a message with the
+/// `translate` processor:
+///
+/// ```ftd
+/// -- string greeting:
+/// $processor$: pr.translate
+/// message-id: hello-user
+/// name: $user.name
+/// count: $unread
+/// ```
+///
+/// The message is looked up in the language of the `fastn-lang` cookie, then the languages of
+/// the `Accept-Language` header, then the default language of the package and `en`, see
+/// `request_languages`. `integer` and `decimal` variables are passed as numbers, so plural
+/// selectors work, everything else as a string.
+///
+/// The catalogs are read once and kept on `Config`, see `CatalogCache`.
+#[derive(Default)]
+pub struct Catalogs {
+    /// language -> path and source of its `.ftl` files
+    languages: std::collections::BTreeMap<String, Vec<(String, String)>>,
+    /// language -> bundle of its `.ftl` files, built by the first message formatted in it
+    bundles: scc::HashMap<String, std::sync::Arc<Bundle>>,
+}
+
+type Bundle = fluent::concurrent::FluentBundle<fluent::FluentResource>;
+
+impl std::fmt::Debug for Catalogs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Catalogs")
+            .field("languages", &self.languages)
+            .finish()
+    }
+}
+
+/// `CatalogCache` keeps the catalogs of the package between requests, `fastn serve --watch`
+/// clears it when a file changes
+#[derive(Debug, Clone, Default)]
+pub struct CatalogCache {
+    catalogs: std::sync::Arc<std::sync::RwLock<Option<std::sync::Arc<Catalogs>>>>,
+}
+
+impl CatalogCache {
+    pub async fn get(
+        &self,
+        config: &fastn_core::Config,
+    ) -> fastn_core::Result<std::sync::Arc<Catalogs>> {
+        let cached = self.catalogs.read().unwrap().clone();
+        if let Some(catalogs) = cached {
+            return Ok(catalogs);
+        }
+
+        let catalogs = std::sync::Arc::new(Catalogs::read(config).await?);
+        *self.catalogs.write().unwrap() = Some(std::sync::Arc::clone(&catalogs));
+        Ok(catalogs)
+    }
+
+    pub fn clear(&self) {
+        *self.catalogs.write().unwrap() = None;
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum CatalogError {
+    #[error("{path}: {message}")]
+    Syntax { path: String, message: String },
+    #[error("invalid language `{0}`, the folders in i18n/ are language ids like `hi` or `pt-BR`")]
+    InvalidLanguage(String),
+    #[error("no message `{id}` in the catalogs of {languages}")]
+    MissingMessage { id: String, languages: String },
+    #[error("message `{id}`: {message}")]
+    Format { id: String, message: String },
+}
+
+impl Catalogs {
+    /// the `.ftl` files of the package being served
+    pub async fn read(config: &fastn_core::Config) -> fastn_core::Result<Catalogs> {
+        let root = config.get_root_for_package(&config.package);
+        let folder = root.join(CATALOG_FOLDER);
+        let mut catalogs = Catalogs::default();
+        if !config.ds.exists(&folder).await {
+            return Ok(catalogs);
+        }
+
+        for path in config.ds.get_all_file_path(&folder, &[]).await {
+            if path.extension().as_deref() != Some("ftl") {
+                continue;
+            }
+            let language = match path.strip_prefix(&folder).and_then(|p| {
+                p.to_string()
+                    .split_once(['/', '\\'])
+                    .map(|v| v.0.to_string())
+            }) {
+                Some(language) => language,
+                // a file directly in i18n/ has no language
+                None => continue,
+            };
+            let source = config.ds.read_to_string(&path).await?;
+            let name = path
+                .strip_prefix(&root)
+                .map(|p| p.to_string())
+                .unwrap_or_else(|| path.to_string());
+            catalogs.add(language.as_str(), name.as_str(), source);
+        }
+        Ok(catalogs)
+    }
+
+    pub fn add(&mut self, language: &str, path: &str, source: String) {
+        let sources = self.languages.entry(language.to_string()).or_default();
+        sources.push((path.to_string(), source));
+        sources.sort();
+        self.bundles.remove(language);
+    }
+
+    pub fn languages(&self) -> Vec<String> {
+        self.languages.keys().cloned().collect()
+    }
+
+    /// ids of the messages of `language`, terms are not included
+    pub fn message_ids(&self, language: &str) -> std::collections::BTreeSet<String> {
+        self.languages
+            .get(language)
+            .into_iter()
+            .flatten()
+            .flat_map(|(_, source)| MESSAGE_ID.captures_iter(source))
+            .map(|c| c[1].to_string())
+            .collect()
+    }
+
+    /// `id` formatted by the first of `languages` with a catalog that has it
+    pub fn format(
+        &self,
+        languages: &[String],
+        id: &str,
+        args: &fluent::FluentArgs,
+    ) -> Result<String, CatalogError> {
+        for language in languages {
+            let sources = match self.languages.get(language) {
+                Some(sources) => sources,
+                None => continue,
+            };
+            let bundle = self.bundle(language, sources)?;
+            let message = match bundle.get_message(id) {
+                Some(message) => message,
+                None => continue,
+            };
+            let pattern = match message.value() {
+                Some(pattern) => pattern,
+                None => {
+                    return Err(CatalogError::Format {
+                        id: id.to_string(),
+                        message: format!("the message has no value in {language}"),
+                    })
+                }
+            };
+
+            let mut errors = vec![];
+            let text = bundle.format_pattern(pattern, Some(args), &mut errors);
+            if !errors.is_empty() {
+                return Err(CatalogError::Format {
+                    id: id.to_string(),
+                    message: format!("{errors:?}"),
+                });
+            }
+            return Ok(text.to_string());
+        }
+
+        Err(CatalogError::MissingMessage {
+            id: id.to_string(),
+            languages: languages.join(", "),
+        })
+    }
+
+    /// checks the syntax of every catalog
+    pub fn validate(&self) -> Result<(), CatalogError> {
+        for (language, sources) in self.languages.iter() {
+            self.bundle(language, sources)?;
+        }
+        Ok(())
+    }
+
+    fn bundle(
+        &self,
+        language: &str,
+        sources: &[(String, String)],
+    ) -> Result<std::sync::Arc<Bundle>, CatalogError> {
+        if let Some(bundle) = self.bundles.get(language) {
+            return Ok(std::sync::Arc::clone(bundle.get()));
+        }
+        let bundle = std::sync::Arc::new(new_bundle(language, sources)?);
+        fastn_ds::insert_or_update(
+            &self.bundles,
+            language.to_string(),
+            std::sync::Arc::clone(&bundle),
+        );
+        Ok(bundle)
+    }
+}
+
+fn new_bundle(language: &str, sources: &[(String, String)]) -> Result<Bundle, CatalogError> {
+    let mut bundle = Bundle::new_concurrent(vec![language
+        .parse()
+        .map_err(|_| CatalogError::InvalidLanguage(language.to_string()))?]);
+    // the unicode isolation marks around arguments would end up in the page
+    bundle.set_use_isolating(false);
+
+    for (path, source) in sources {
+        let resource = fluent::FluentResource::try_new(source.to_string()).map_err(|(_, e)| {
+            CatalogError::Syntax {
+                path: path.to_string(),
+                message: format!("{e:?}"),
+            }
+        })?;
+        // fails on a message defined twice
+        bundle
+            .add_resource(resource)
+            .map_err(|e| CatalogError::Syntax {
+                path: path.to_string(),
+                message: format!("{e:?}"),
+            })?;
+    }
+    Ok(bundle)
+}
+
+/// the languages to look a message up in, best first: the language of the url prefix, the
+/// `fastn-lang` cookie, the languages
+/// of the `Accept-Language` header, the language selected for the package, its default language
+/// and `en`. `hi-IN` is followed by `hi`.
+pub fn request_languages(req_config: &fastn_core::RequestConfig) -> Vec<String> {
+    use itertools::Itertools;
+
+    let mut languages = vec![];
+    languages.extend(req_config.language.clone());
+    languages.extend(
+        req_config
+            .request
+            .cookie(fastn_core::package::lang::LANGUAGE_COOKIE),
+    );
+    if let Some(header) = req_config
+        .request
+        .headers()
+        .get("accept-language")
+        .and_then(|v| v.to_str().ok())
+    {
+        for language in accept_language::parse(header) {
+            let primary = language.split_once('-').map(|(p, _)| p.to_string());
+            languages.push(language);
+            languages.extend(primary);
+        }
+    }
+    languages.extend(req_config.config.package.selected_language.clone());
+    languages.extend(
+        req_config
+            .config
+            .package
+            .lang
+            .as_ref()
+            .map(|l| l.default_lang.to_string()),
+    );
+    languages.push(FALLBACK_LANGUAGE.to_string());
+    languages.into_iter().unique().collect()
+}
+
+/// message ids used by the `translate` processors in `source`
+pub fn used_message_ids(source: &str) -> impl Iterator<Item = String> + '_ {
+    MESSAGE_ID_USE
+        .captures_iter(source)
+        .map(|c| c[1].to_string())
+}
+
+/// a number, like an `integer` variable, is a number for the plural rules of fluent, a string
+/// stays a string even if it looks like a number, `02134` is a zip code
+pub(crate) fn fluent_value(value: serde_json::Value) -> fluent::FluentValue<'static> {
+    match value {
+        serde_json::Value::Number(n) => match n.as_f64() {
+            Some(n) => fluent::FluentValue::from(n),
+            None => fluent::FluentValue::from(n.to_string()),
+        },
+        serde_json::Value::String(s) => fluent::FluentValue::from(s),
+        v => fluent::FluentValue::from(v.to_string()),
+    }
+}
+
+impl fastn_core::RequestConfig {
+    /// the catalogs of the package, see `CatalogCache`
+    pub async fn catalogs(&self) -> fastn_core::Result<std::sync::Arc<Catalogs>> {
+        self.config.catalogs.get(&self.config).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn catalogs() -> super::Catalogs {
+        let mut catalogs = super::Catalogs::default();
+        catalogs.add(
+            "en",
+            "i18n/en/main.ftl",
+            indoc::indoc! {"
+                hello-user = Hello, { $name }!
+                emails = { $count ->
+                    [one] One new email
+                   *[other] { $count } new emails
+                }
+                -brand = fastn
+                only-english = Only in English
+            "}
+            .to_string(),
+        );
+        catalogs.add(
+            "hi",
+            "i18n/hi/main.ftl",
+            "hello-user = नमस्ते, { $name }!\n".to_string(),
+        );
+        catalogs
+    }
+
+    fn args(pairs: &[(&'static str, serde_json::Value)]) -> fluent::FluentArgs<'static> {
+        let mut args = fluent::FluentArgs::new();
+        for (k, v) in pairs {
+            args.set(*k, super::fluent_value(v.clone()));
+        }
+        args
+    }
+
+    #[test]
+    fn format() {
+        let mut catalogs = catalogs();
+        let languages = |l: &[&str]| l.iter().map(|l| l.to_string()).collect::<Vec<_>>();
+        let name = args(&[("name", serde_json::json!("Asha"))]);
+
+        assert_eq!(
+            catalogs
+                .format(&languages(&["hi", "en"]), "hello-user", &name)
+                .unwrap(),
+            "नमस्ते, Asha!"
+        );
+        // falls back to the next language
+        assert_eq!(
+            catalogs
+                .format(&languages(&["hi", "en"]), "only-english", &name)
+                .unwrap(),
+            "Only in English"
+        );
+        assert_eq!(
+            catalogs
+                .format(
+                    &languages(&["en"]),
+                    "emails",
+                    &args(&[("count", serde_json::json!(1))])
+                )
+                .unwrap(),
+            "One new email"
+        );
+        // a string is not a number
+        assert_eq!(
+            catalogs
+                .format(
+                    &languages(&["en"]),
+                    "emails",
+                    &args(&[("count", serde_json::json!("02134"))])
+                )
+                .unwrap(),
+            "02134 new emails"
+        );
+        assert_eq!(
+            catalogs
+                .format(
+                    &languages(&["en"]),
+                    "emails",
+                    &args(&[("count", serde_json::json!(3))])
+                )
+                .unwrap(),
+            "3 new emails"
+        );
+        assert!(matches!(
+            catalogs.format(&languages(&["hi"]), "only-english", &name),
+            Err(super::CatalogError::MissingMessage { .. })
+        ));
+
+        // a bundle is built once per language, and again once its catalogs change
+        assert_eq!(catalogs.bundles.len(), 2);
+        catalogs.add("hi", "i18n/hi/extra.ftl", "bye = अलविदा\n".to_string());
+        assert_eq!(catalogs.bundles.len(), 1);
+    }
+
+    #[test]
+    fn message_ids() {
+        let catalogs = catalogs();
+        assert_eq!(
+            catalogs.message_ids("en").into_iter().collect::<Vec<_>>(),
+            vec!["emails", "hello-user", "only-english"]
+        );
+        assert_eq!(
+            super::used_message_ids(
+                "-- string a:\n$processor$: pr.translate\nmessage-id: emails\n"
+            )
+            .collect::<Vec<_>>(),
+            vec!["emails"]
+        );
+    }
+}
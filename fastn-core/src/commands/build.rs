@@ -30,6 +30,8 @@ pub async fn build(
             }
             None => {
                 incremental_build(config, &documents, base_url, ignore_failed, test).await?;
+                build_dynamic_urls(config, base_url, ignore_failed, test).await?;
+                build_languages(config, &documents, base_url, ignore_failed, test).await?;
             }
         }
     }
@@ -60,6 +62,8 @@ pub async fn build(
         }
     }
 
+    build_seo_files(config).await?;
+
     if !test {
         config.download_fonts().await?;
     }
@@ -507,6 +511,244 @@ async fn handle_file(
     Ok(())
 }
 
+/// Renders every `fastn.dynamic-urls` url that lists the values of its path parameters, see
+/// `fastn_core::sitemap::dynamic_urls::BuildUrl`.
+async fn build_dynamic_urls(
+    config: &fastn_core::Config,
+    base_url: &str,
+    ignore_failed: bool,
+    test: bool,
+) -> fastn_core::Result<()> {
+    let build_urls = match config.package.dynamic_urls {
+        Some(ref dynamic_urls) => dynamic_urls.build_urls(),
+        None => return Ok(()),
+    };
+
+    for build_url in build_urls {
+        let mut all_params = build_url.inline_params();
+        if let Some(id) = build_url.params_document() {
+            all_params.extend(build_params_from_document(config, id).await?);
+        }
+
+        let document = match get_ftd_document(config, build_url.document.as_str()).await? {
+            Some(document) => document,
+            None => {
+                return fastn_core::usage_error(format!(
+                    "{} is not an ftd document",
+                    build_url.document
+                ))
+            }
+        };
+
+        for params in all_params {
+            let path = build_url.path(&params)?;
+            let start = std::time::Instant::now();
+            print!("Processing {}{} ... ", config.package.name, path);
+
+            let (_, named_parameters) =
+                fastn_core::sitemap::utils::url_match(path.as_str(), &build_url.path_parameters)?;
+            let resp = {
+                let req = fastn_core::http::Request::default();
+                let mut req_config =
+                    fastn_core::RequestConfig::new(config, &req, document.id.as_str(), base_url);
+                req_config.current_document = Some(path.to_string());
+                req_config.named_parameters = named_parameters;
+                req_config.extra_data = build_url.extra_data.clone();
+
+                fastn_core::package::package_doc::process_ftd(
+                    &mut req_config,
+                    &document,
+                    base_url,
+                    false,
+                    test,
+                    format!("{}/index.html", path.trim_matches('/')).as_str(),
+                )
+                .await
+            };
+
+            match (resp, ignore_failed) {
+                (Ok(_), _) => fastn_core::utils::print_end(
+                    format!("Processed {}{}", config.package.name, path).as_str(),
+                    start,
+                ),
+                (Err(_), true) => print!("Failed "),
+                (Err(e), false) => {
+                    fastn_core::utils::print_error(
+                        format!("Failed {}{}", config.package.name, path).as_str(),
+                        start,
+                    );
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Renders every document once more in every available language of the package, under the
+/// language prefix, `docs.ftd` in `hi` is `hi/docs/index.html`. See `fastn_core::package::Lang`.
+async fn build_languages(
+    config: &fastn_core::Config,
+    documents: &std::collections::BTreeMap<String, fastn_core::File>,
+    base_url: &str,
+    ignore_failed: bool,
+    test: bool,
+) -> fastn_core::Result<()> {
+    let languages = match config.package.lang {
+        Some(ref lang) => lang.languages(),
+        None => return Ok(()),
+    };
+
+    for language in languages {
+        for document in documents.values() {
+            let doc = match document {
+                fastn_core::File::Ftd(doc) if !doc.id.eq("FASTN.ftd") => doc,
+                _ => continue,
+            };
+            let file_path = format!("{language}/{}", html_file_path(doc.id.as_str()));
+            let start = std::time::Instant::now();
+            print!("Processing {}/{} ... ", config.package.name, file_path);
+
+            let resp = {
+                let req = fastn_core::http::Request::default();
+                let mut req_config =
+                    fastn_core::RequestConfig::new(config, &req, doc.id.as_str(), base_url);
+                req_config.current_document = Some(doc.id.to_string());
+                req_config.language = Some(language.to_string());
+                req_config
+                    .config
+                    .package
+                    .auto_import_language(Some(language.to_string()), None)?;
+
+                fastn_core::package::package_doc::process_ftd(
+                    &mut req_config,
+                    doc,
+                    base_url,
+                    false,
+                    test,
+                    file_path.as_str(),
+                )
+                .await
+            };
+
+            match (resp, ignore_failed) {
+                (Ok(_), _) => fastn_core::utils::print_end(
+                    format!("Processed {}/{}", config.package.name, file_path).as_str(),
+                    start,
+                ),
+                (Err(_), true) => print!("Failed "),
+                (Err(e), false) => {
+                    fastn_core::utils::print_error(
+                        format!("Failed {}/{}", config.package.name, file_path).as_str(),
+                        start,
+                    );
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// `sitemap.xml`, `robots.txt` and `feed.xml` for the package, unless the package has its own.
+async fn build_seo_files(config: &fastn_core::Config) -> fastn_core::Result<()> {
+    for name in fastn_core::sitemap::seo::generated_files(&config.package) {
+        if config.ds.exists(&config.ds.root().join(name)).await {
+            continue;
+        }
+
+        let start = std::time::Instant::now();
+        print!("Processing {}/{} ... ", config.package.name, name);
+        if let Some((_, content)) = fastn_core::sitemap::seo::generate(config, name).await? {
+            fastn_core::utils::update(
+                &config.ds.root().join(".build").join(name),
+                content.as_bytes(),
+                &config.ds,
+            )
+            .await?;
+        }
+        fastn_core::utils::print_end(
+            format!("Processed {}/{}", config.package.name, name).as_str(),
+            start,
+        );
+    }
+
+    Ok(())
+}
+
+async fn get_ftd_document(
+    config: &fastn_core::Config,
+    id: &str,
+) -> fastn_core::Result<Option<fastn_core::Document>> {
+    let root = config.ds.root();
+    match fastn_core::get_file(
+        &config.ds,
+        config.package.name.to_string(),
+        &root.join(id.trim_start_matches('/')),
+        &root,
+    )
+    .await?
+    {
+        fastn_core::File::Ftd(document) => Ok(Some(document)),
+        _ => Ok(None),
+    }
+}
+
+/// Values of the path parameters from the `build-params` record list defined in the document
+/// `id`, one map per url. Record fields are the parameter names.
+async fn build_params_from_document(
+    config: &fastn_core::Config,
+    id: &str,
+) -> fastn_core::Result<Vec<std::collections::BTreeMap<String, String>>> {
+    let document = match get_ftd_document(config, id).await? {
+        Some(document) => document,
+        None => return fastn_core::usage_error(format!("{} is not an ftd document", id)),
+    };
+
+    let interpreted = fastn_core::commands::check::interpret_document(config, &document)
+        .await
+        .map_err(|d| {
+            fastn_core::Error::GenericError(format!("{}:{:?}: {}", d.doc_id, d.line, d.message))
+        })?;
+
+    let rows = match interpreted.json(
+        format!(
+            "{}#{}",
+            interpreted.name.trim_end_matches('/'),
+            fastn_core::sitemap::dynamic_urls::BUILD_PARAMS
+        )
+        .as_str(),
+    )? {
+        serde_json::Value::Array(rows) => rows,
+        _ => {
+            return fastn_core::usage_error(format!(
+                "{}: `{}` must be a record list",
+                id,
+                fastn_core::sitemap::dynamic_urls::BUILD_PARAMS
+            ))
+        }
+    };
+
+    rows.into_iter()
+        .map(|row| match row {
+            serde_json::Value::Object(fields) => Ok(fields
+                .into_iter()
+                .map(|(k, v)| match v {
+                    serde_json::Value::String(v) => (k, v),
+                    v => (k, v.to_string()),
+                })
+                .collect()),
+            _ => fastn_core::usage_error(format!(
+                "{}: `{}` must be a record list",
+                id,
+                fastn_core::sitemap::dynamic_urls::BUILD_PARAMS
+            )),
+        })
+        .collect()
+}
+
 fn is_cached<'a>(
     cache: Option<&'a mut cache::Cache>,
     doc: &fastn_core::Document,
@@ -596,6 +838,17 @@ fn remove_extension(id: &str) -> String {
     }
 }
 
+/// the file in `.build` a document is rendered to
+fn html_file_path(id: &str) -> String {
+    if id.eq("404.ftd") {
+        "404.html".to_string()
+    } else if id.ends_with("index.ftd") {
+        fastn_core::utils::replace_last_n(id, 1, "index.ftd", "index.html")
+    } else {
+        fastn_core::utils::replace_last_n(id, 1, ".ftd", "/index.html")
+    }
+}
+
 #[tracing::instrument(skip(document, config, cache))]
 async fn handle_file_(
     document: &fastn_core::File,
@@ -608,13 +861,7 @@ async fn handle_file_(
 ) -> fastn_core::Result<()> {
     match document {
         fastn_core::File::Ftd(doc) => {
-            let file_path = if doc.id.eq("404.ftd") {
-                "404.html".to_string()
-            } else if doc.id.ends_with("index.ftd") {
-                fastn_core::utils::replace_last_n(doc.id.as_str(), 1, "index.ftd", "index.html")
-            } else {
-                fastn_core::utils::replace_last_n(doc.id.as_str(), 1, ".ftd", "/index.html")
-            };
+            let file_path = html_file_path(doc.id.as_str());
 
             let (cache, is_cached) = is_cached(cache, doc, file_path.as_str());
             if is_cached {
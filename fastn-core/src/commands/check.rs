@@ -2,6 +2,370 @@ pub const INDEX_FILE: &str = "index.html";
 pub const BUILD_FOLDER: &str = ".build";
 pub const IGNORED_DIRECTORIES: [&str; 4] = ["-", "images", "static", "assets"];
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MessageFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+impl std::str::FromStr for MessageFormat {
+    type Err = fastn_core::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            t => fastn_core::usage_error(format!(
+                "Unknown message format `{}`. Help use `human` or `json` instead",
+                t
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// Columns are 1 based, `end` is exclusive.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A Diagnostic is one problem found in one document. With `--message-format=json` every
+/// diagnostic is printed as a single line of JSON, so CI and editors can parse it.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Diagnostic {
+    pub doc_id: String,
+    pub line: Option<usize>,
+    pub column: Option<Span>,
+    pub severity: Severity,
+    pub code: String,
+    pub message: String,
+    pub suggestion: Option<String>,
+}
+
+impl Diagnostic {
+    fn error(code: &str, doc_id: &str, line: usize, message: String) -> Diagnostic {
+        Diagnostic {
+            doc_id: doc_id.to_string(),
+            // the parser uses 0 when it does not know the line
+            line: if line == 0 { None } else { Some(line) },
+            column: None,
+            severity: Severity::Error,
+            code: code.to_string(),
+            message,
+            suggestion: None,
+        }
+    }
+
+    fn with_suggestion<T: ToString>(self, suggestion: T) -> Diagnostic {
+        Diagnostic {
+            suggestion: Some(suggestion.to_string()),
+            ..self
+        }
+    }
+
+    /// Errors only carry the line number, so the span covers the line without its indentation.
+    /// `document` is used only if the diagnostic belongs to it and not to an imported module.
+    fn with_span(self, package_name: &str, document: &fastn_core::Document) -> Diagnostic {
+        if module_name(package_name, self.doc_id.as_str())
+            != module_name(package_name, document.id.as_str())
+        {
+            return self;
+        }
+
+        let column = self
+            .line
+            .and_then(|l| document.content.lines().nth(l - 1))
+            .map(|l| Span {
+                start: l.chars().take_while(|c| c.is_whitespace()).count() + 1,
+                end: l.trim_end().chars().count() + 1,
+            });
+
+        Diagnostic {
+            doc_id: document.id.to_string(),
+            column,
+            ..self
+        }
+    }
+
+    pub fn from_interpreter_error(doc_id: &str, e: &ftd::interpreter::Error) -> Diagnostic {
+        match e {
+            ftd::interpreter::Error::P1Error(e)
+            | ftd::interpreter::Error::ASTError(ftd_ast::Error::P1(e)) => {
+                Diagnostic::from_p1_error(e)
+            }
+            ftd::interpreter::Error::ASTError(ftd_ast::Error::Parse {
+                message,
+                doc_id,
+                line_number,
+            }) => Diagnostic::error("ast::parse", doc_id, *line_number, message.to_string()),
+            ftd::interpreter::Error::OldP1Error(e) => Diagnostic::from_old_p1_error(doc_id, e),
+            ftd::interpreter::Error::InvalidKind {
+                doc_id,
+                line_number,
+                message,
+            } => Diagnostic::error(
+                "interpreter::invalid-kind",
+                doc_id,
+                *line_number,
+                message.to_string(),
+            )
+            .with_suggestion("make sure the value matches the kind it is declared with"),
+            ftd::interpreter::Error::ValueNotFound {
+                doc_id,
+                line_number,
+                message,
+            } => Diagnostic::error(
+                "interpreter::value-not-found",
+                doc_id,
+                *line_number,
+                message.to_string(),
+            )
+            .with_suggestion(
+                "check the spelling, or `-- import:` the module that defines this name",
+            ),
+            ftd::interpreter::Error::ParseError {
+                message,
+                doc_id,
+                line_number,
+            } => Diagnostic::error(
+                "interpreter::parse",
+                doc_id,
+                *line_number,
+                message.to_string(),
+            ),
+            ftd::interpreter::Error::InvalidAccessError {
+                message,
+                line_number,
+            } => Diagnostic::error(
+                "interpreter::invalid-access",
+                doc_id,
+                *line_number,
+                message.to_string(),
+            ),
+            e => Diagnostic::error("interpreter::error", doc_id, 0, e.to_string()),
+        }
+    }
+
+    pub fn from_p1_error(e: &ftd_p1::Error) -> Diagnostic {
+        match e {
+            ftd_p1::Error::SectionNotFound {
+                doc_id,
+                line_number,
+            } => Diagnostic::error(
+                "p1::section-not-found",
+                doc_id,
+                *line_number,
+                "section not found".to_string(),
+            )
+            .with_suggestion("sections start with `-- <kind> <name>:`"),
+            ftd_p1::Error::MoreThanOneCaption {
+                doc_id,
+                line_number,
+            } => Diagnostic::error(
+                "p1::more-than-one-caption",
+                doc_id,
+                *line_number,
+                "more than one caption".to_string(),
+            )
+            .with_suggestion(
+                "pass the caption either after `:` or as a `$caption$` header, not both",
+            ),
+            ftd_p1::Error::ParseError {
+                message,
+                doc_id,
+                line_number,
+            } => Diagnostic::error("p1::parse", doc_id, *line_number, message.to_string()),
+            ftd_p1::Error::MoreThanOneHeader {
+                key,
+                doc_id,
+                line_number,
+            } => Diagnostic::error(
+                "p1::more-than-one-header",
+                doc_id,
+                *line_number,
+                format!("more than one header for key `{}`", key),
+            )
+            .with_suggestion(format!("remove the duplicate `{}` header", key)),
+            ftd_p1::Error::HeaderNotFound {
+                key,
+                doc_id,
+                line_number,
+            } => Diagnostic::error(
+                "p1::header-not-found",
+                doc_id,
+                *line_number,
+                format!("header not found for key `{}`", key),
+            )
+            .with_suggestion(format!("add the `{}` header", key)),
+        }
+    }
+
+    fn from_old_p1_error(doc_id: &str, e: &ftd::ftd2021::p1::Error) -> Diagnostic {
+        match e {
+            ftd::ftd2021::p1::Error::ParseError {
+                message,
+                doc_id,
+                line_number,
+            } => Diagnostic::error("p1::parse", doc_id, *line_number, message.to_string()),
+            e => Diagnostic::error("p1::error", doc_id, 0, e.to_string()),
+        }
+    }
+
+    /// used for errors that are not tied to a single document, e.g. when `fastn build` fails
+    pub fn from_error(doc_id: &str, e: &fastn_core::Error) -> Diagnostic {
+        match e {
+            fastn_core::Error::FTDInterpreterError(e) => {
+                Diagnostic::from_interpreter_error(doc_id, e)
+            }
+            fastn_core::Error::FTDP1Error(e)
+            | fastn_core::Error::FTDAstError(ftd_ast::Error::P1(e)) => Diagnostic::from_p1_error(e),
+            fastn_core::Error::FTDAstError(ftd_ast::Error::Parse {
+                message,
+                doc_id,
+                line_number,
+            }) => Diagnostic::error("ast::parse", doc_id, *line_number, message.to_string()),
+            fastn_core::Error::FTDError(e) => Diagnostic::from_old_p1_error(doc_id, e),
+            e => Diagnostic::error("fastn::error", doc_id, 0, e.to_string()),
+        }
+    }
+
+    fn to_human(&self) -> String {
+        use colored::Colorize;
+
+        let severity = match self.severity {
+            Severity::Error => "error".red(),
+            Severity::Warning => "warning".yellow(),
+        };
+        let mut location = self.doc_id.to_string();
+        if let Some(line) = self.line {
+            location = format!("{}:{}", location, line);
+            if let Some(ref column) = self.column {
+                location = format!("{}:{}", location, column.start);
+            }
+        }
+
+        let mut human = format!(
+            "{}[{}]: {}\n  --> {}",
+            severity, self.code, self.message, location
+        );
+        if let Some(ref suggestion) = self.suggestion {
+            human = format!("{}\n  help: {}", human, suggestion);
+        }
+        human
+    }
+}
+
+/// foo/index.ftd, foo.ftd, <package-name>/foo/ -> foo
+pub(crate) fn module_name(package_name: &str, id: &str) -> String {
+    let id = id.trim_matches('/');
+    let id = match id.strip_prefix(package_name) {
+        Some(v) if v.is_empty() || v.starts_with('/') => v.trim_start_matches('/'),
+        _ => id,
+    };
+    let id = id.strip_suffix(".ftd").unwrap_or(id);
+    if id == "index" {
+        return "".to_string();
+    }
+    id.strip_suffix("/index").unwrap_or(id).to_string()
+}
+
+pub fn print_diagnostics(diagnostics: &[Diagnostic], message_format: MessageFormat) {
+    for diagnostic in diagnostics {
+        match message_format {
+            MessageFormat::Human => eprintln!("{}\n", diagnostic.to_human()),
+            MessageFormat::Json => println!(
+                "{}",
+                serde_json::to_string(diagnostic).expect("diagnostic is always valid json")
+            ),
+        }
+    }
+}
+
+/// check interprets every ftd document of the current package (or only `only_id`), without
+/// writing anything to `.build`, and returns a diagnostic for every document that fails.
+#[tracing::instrument(skip(config))]
+pub async fn check(
+    config: &fastn_core::Config,
+    only_id: Option<&str>,
+) -> fastn_core::Result<Vec<Diagnostic>> {
+    let mut diagnostics = vec![];
+
+    for file in config.get_files(&config.package).await? {
+        let document = match file {
+            fastn_core::File::Ftd(document) => document,
+            _ => continue,
+        };
+
+        if document.id.eq("FASTN.ftd")
+            || only_id.map_or(false, |id| {
+                module_name(config.package.name.as_str(), id)
+                    != module_name(config.package.name.as_str(), document.id.as_str())
+            })
+        {
+            continue;
+        }
+
+        if let Some(diagnostic) = check_document(config, &document).await {
+            diagnostics.push(diagnostic);
+        }
+    }
+
+    Ok(diagnostics)
+}
+
+async fn check_document(
+    config: &fastn_core::Config,
+    document: &fastn_core::Document,
+) -> Option<Diagnostic> {
+    interpret_document(config, document).await.err()
+}
+
+/// interpret_document runs `document` (which does not have to be saved to disk) through the
+/// interpreter, the same way `fastn serve` would, and returns the diagnostic if it fails.
+pub(crate) async fn interpret_document(
+    config: &fastn_core::Config,
+    document: &fastn_core::Document,
+) -> Result<ftd::interpreter::Document, Diagnostic> {
+    let req = fastn_core::http::Request::default();
+    let mut req_config = fastn_core::RequestConfig::new(config, &req, document.id.as_str(), "/");
+    req_config.current_document = Some(document.id.to_string());
+
+    let package_name = config.package.name.as_str();
+    let current_package = config.find_package_else_default(document.package_name.as_str(), None);
+    let doc_content =
+        current_package.get_prefixed_body(document.content.as_str(), document.id.as_str(), true);
+    let doc_content = current_package
+        .fix_imports_in_body(doc_content.as_str(), document.id.as_str())
+        .map_err(|e| {
+            Diagnostic::from_old_p1_error(document.id.as_str(), &e)
+                .with_span(package_name, document)
+        })?;
+    let line_number = doc_content.split('\n').count() - document.content.split('\n').count();
+
+    fastn_core::doc::interpret_helper(
+        document.id_with_package().as_str(),
+        doc_content.as_str(),
+        &mut req_config,
+        "/",
+        false,
+        line_number,
+    )
+    .await
+    .map_err(|e| {
+        Diagnostic::from_interpreter_error(document.id.as_str(), &e)
+            .with_span(package_name, document)
+    })
+}
+
 pub async fn post_build_check(config: &fastn_core::Config) -> fastn_core::Result<()> {
     let build_path = config.ds.root().join(BUILD_FOLDER);
     println!("Post build index assertion started ...");
@@ -68,3 +432,48 @@ async fn check_index_in_folders(
 fn is_ignored_directory(path: &camino::Utf8PathBuf) -> bool {
     IGNORED_DIRECTORIES.iter().any(|dir| path.ends_with(dir))
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn module_name() {
+        assert_eq!(super::module_name("foo.com", "index.ftd"), "");
+        assert_eq!(super::module_name("foo.com", "foo.com/"), "");
+        assert_eq!(super::module_name("foo.com", "blog/index.ftd"), "blog");
+        assert_eq!(super::module_name("foo.com", "foo.com/blog/"), "blog");
+        assert_eq!(
+            super::module_name("foo.com", "foo.com/blog/post/"),
+            "blog/post"
+        );
+        assert_eq!(
+            super::module_name("foo.com", "bar.com/blog/"),
+            "bar.com/blog"
+        );
+        assert_eq!(super::module_name("foo.com", "reindex.ftd"), "reindex");
+    }
+
+    #[test]
+    fn diagnostic_span() {
+        let document = fastn_core::Document {
+            package_name: "foo.com".to_string(),
+            id: "blog.ftd".to_string(),
+            content: "-- ftd.text: hello\n\n  -- ftd.txt: world  \n".to_string(),
+            parent_path: fastn_ds::Path::new("."),
+        };
+
+        let diagnostic = super::Diagnostic::from_interpreter_error(
+            "blog.ftd",
+            &ftd::interpreter::Error::ValueNotFound {
+                doc_id: "foo.com/blog/".to_string(),
+                line_number: 3,
+                message: "ftd.txt".to_string(),
+            },
+        )
+        .with_span("foo.com", &document);
+
+        assert_eq!(diagnostic.doc_id, "blog.ftd");
+        assert_eq!(diagnostic.line, Some(3));
+        assert_eq!(diagnostic.column, Some(super::Span { start: 3, end: 20 }));
+        assert_eq!(diagnostic.code, "interpreter::value-not-found");
+    }
+}
@@ -0,0 +1,573 @@
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DepsFormat {
+    #[default]
+    Human,
+    Json,
+    Dot,
+}
+
+impl std::str::FromStr for DepsFormat {
+    type Err = fastn_core::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(DepsFormat::Human),
+            "json" => Ok(DepsFormat::Json),
+            "dot" => Ok(DepsFormat::Dot),
+            t => fastn_core::usage_error(format!(
+                "Unknown format `{}`. Help use `human`, `json` or `dot` instead",
+                t
+            )),
+        }
+    }
+}
+
+/// A package and the packages it depends on
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct PackageNode {
+    pub name: String,
+    /// the version the depending package asks for in its `FASTN.ftd`
+    pub version: Option<String>,
+    /// the checksum of the `manifest.json` `fastn update` downloaded
+    pub checksum: Option<String>,
+    /// empty for a package already on the path from the root, a dependency cycle
+    pub dependencies: Vec<PackageNode>,
+}
+
+/// A document of the package that imports the module `fastn deps --why` asks about
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Importer {
+    /// like `index.ftd`
+    pub document: String,
+    /// the modules from the one `document` imports to the one asked about
+    pub chain: Vec<String>,
+}
+
+/// `fastn deps` prints the dependency tree of the package, `fastn deps --why <module>` the
+/// documents of the package that import `module`, a document id, a module or a package
+pub async fn deps(
+    config: &fastn_core::Config,
+    why: Option<&str>,
+    format: DepsFormat,
+) -> fastn_core::Result<()> {
+    match why {
+        Some(module) => {
+            let importers = importers(config, module).await?;
+            match format {
+                DepsFormat::Json => println!("{}", serde_json::to_string_pretty(&importers)?),
+                DepsFormat::Dot => print!("{}", importers_to_dot(&importers)),
+                DepsFormat::Human if importers.is_empty() => {
+                    println!("No document imports {module}")
+                }
+                DepsFormat::Human => {
+                    for importer in importers.iter() {
+                        println!(
+                            "{} imports {}",
+                            importer.document,
+                            importer.chain.join(" -> ")
+                        );
+                    }
+                }
+            }
+        }
+        None => {
+            let tree = package_tree(config).await?;
+            match format {
+                DepsFormat::Json => println!("{}", serde_json::to_string_pretty(&tree)?),
+                DepsFormat::Dot => print!("{}", tree_to_dot(&tree)),
+                DepsFormat::Human => {
+                    println!("{}", tree.name);
+                    print_tree(&tree.dependencies, "");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// the dependencies of the package, and theirs from `.packages`, with the checksums of the
+/// manifests in `.fastn/config.json`
+pub async fn package_tree(config: &fastn_core::Config) -> fastn_core::Result<PackageNode> {
+    let manifests = match fastn_core::ConfigTemp::read(&config.ds).await {
+        Ok(config_temp) => config_temp.all_packages,
+        // `fastn update` has not run yet
+        Err(fastn_core::config_temp::Error::NotFound(_)) => Default::default(),
+        Err(e) => return Err(e.into()),
+    };
+
+    // package name -> name and version of its dependencies
+    let dependencies: std::collections::BTreeMap<String, Vec<(String, Option<String>)>> =
+        packages(config)
+            .await?
+            .into_iter()
+            .map(|(name, package)| {
+                (
+                    name,
+                    package
+                        .dependencies
+                        .iter()
+                        .map(|d| (d.package.name.to_string(), d.version.clone()))
+                        .collect(),
+                )
+            })
+            .collect();
+
+    Ok(to_node(
+        config.package.name.as_str(),
+        None,
+        &dependencies,
+        &manifests,
+        &mut vec![],
+    ))
+}
+
+/// the package, and the dependencies in `.packages`, and theirs, by name
+async fn packages(
+    config: &fastn_core::Config,
+) -> fastn_core::Result<std::collections::BTreeMap<String, fastn_core::Package>> {
+    let mut packages = std::collections::BTreeMap::new();
+    let mut stack = vec![config.package.clone()];
+    while let Some(package) = stack.pop() {
+        if packages.contains_key(&package.name) {
+            continue;
+        }
+        for dependency in package.dependencies.iter() {
+            let name = dependency.package.name.as_str();
+            let fastn_path = config.packages_root.join(name).join("FASTN.ftd");
+            if packages.contains_key(name) || !config.ds.exists(&fastn_path).await {
+                continue;
+            }
+            let mut dependency = fastn_core::Package::new(name);
+            dependency.resolve(&fastn_path, &config.ds).await?;
+            stack.push(dependency);
+        }
+        packages.insert(package.name.to_string(), package);
+    }
+    Ok(packages)
+}
+
+fn to_node(
+    name: &str,
+    version: Option<String>,
+    dependencies: &std::collections::BTreeMap<String, Vec<(String, Option<String>)>>,
+    manifests: &std::collections::BTreeMap<String, fastn_core::Manifest>,
+    path: &mut Vec<String>,
+) -> PackageNode {
+    let mut node = PackageNode {
+        name: name.to_string(),
+        version,
+        checksum: manifests.get(name).map(|m| m.checksum.to_string()),
+        dependencies: vec![],
+    };
+    if path.iter().any(|p| p == name) {
+        return node;
+    }
+
+    path.push(name.to_string());
+    node.dependencies = dependencies
+        .get(name)
+        .into_iter()
+        .flatten()
+        .map(|(name, version)| to_node(name, version.clone(), dependencies, manifests, path))
+        .collect();
+    path.pop();
+    node
+}
+
+fn print_tree(nodes: &[PackageNode], prefix: &str) {
+    use colored::Colorize;
+
+    for (idx, node) in nodes.iter().enumerate() {
+        let last = idx + 1 == nodes.len();
+        let mut line = format!(
+            "{prefix}{}{}",
+            if last { "└── " } else { "├── " },
+            node.name
+        );
+        if let Some(ref version) = node.version {
+            line.push_str(format!(" {}", version.green()).as_str());
+        }
+        if let Some(ref checksum) = node.checksum {
+            let short = checksum.get(..12).unwrap_or(checksum.as_str());
+            line.push_str(format!(" {}", short.bright_black()).as_str());
+        }
+        println!("{line}");
+        print_tree(
+            &node.dependencies,
+            format!("{prefix}{}", if last { "    " } else { "│   " }).as_str(),
+        );
+    }
+}
+
+fn tree_to_dot(tree: &PackageNode) -> String {
+    fn edges(node: &PackageNode, lines: &mut std::collections::BTreeSet<String>) {
+        for dependency in node.dependencies.iter() {
+            lines.insert(match dependency.version {
+                Some(ref version) => format!(
+                    "  {:?} -> {:?} [label={:?}];",
+                    node.name, dependency.name, version
+                ),
+                None => format!("  {:?} -> {:?};", node.name, dependency.name),
+            });
+            edges(dependency, lines);
+        }
+    }
+
+    let mut lines = std::collections::BTreeSet::new();
+    edges(tree, &mut lines);
+    to_dot(format!("  {:?};", tree.name), lines)
+}
+
+fn importers_to_dot(importers: &[Importer]) -> String {
+    let mut lines = std::collections::BTreeSet::new();
+    for importer in importers {
+        let mut from = importer.document.as_str();
+        for module in importer.chain.iter() {
+            lines.insert(format!("  {:?} -> {:?};", from, module));
+            from = module.as_str();
+        }
+    }
+    to_dot(String::new(), lines)
+}
+
+fn to_dot(root: String, lines: std::collections::BTreeSet<String>) -> String {
+    let mut dot = "digraph deps {\n".to_string();
+    if !root.is_empty() {
+        dot.push_str(root.as_str());
+        dot.push('\n');
+    }
+    for line in lines {
+        dot.push_str(line.as_str());
+        dot.push('\n');
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+/// the documents of the package that import `module`, directly or through the modules of the
+/// dependencies in `.packages`, with aliases of dependencies and the auto imports of `FASTN.ftd`
+/// resolved
+pub async fn importers(
+    config: &fastn_core::Config,
+    module: &str,
+) -> fastn_core::Result<Vec<Importer>> {
+    let packages = packages(config).await?;
+
+    let mut imports = std::collections::BTreeMap::new();
+    for file in config.get_files(&config.package).await? {
+        if let fastn_core::File::Ftd(document) = file {
+            imports.insert(
+                document.id.to_string(),
+                package_document_imports(
+                    &config.package,
+                    document.content.as_str(),
+                    document.id.as_str(),
+                )?,
+            );
+        }
+    }
+
+    // module of a dependency -> modules it imports, for the modules the package reaches
+    let mut dependency_imports = std::collections::BTreeMap::new();
+    let mut queue: Vec<String> = imports.values().flatten().cloned().collect();
+    while let Some(name) = queue.pop() {
+        let name = normalize_module(name.as_str());
+        if dependency_imports.contains_key(&name) || is_in_package(&config.package.name, &name) {
+            continue;
+        }
+        // `fastn`, `fastn/processors` and the modules of missing packages import nothing
+        let package = match packages
+            .values()
+            .filter(|p| is_in_package(&p.name, &name))
+            .max_by_key(|p| p.name.len())
+        {
+            Some(package) => package,
+            None => continue,
+        };
+        let modules = match read_module(config, name.as_str()).await? {
+            Some(content) => package_document_imports(package, content.as_str(), name.as_str())?,
+            None => vec![],
+        };
+        queue.extend(modules.iter().cloned());
+        dependency_imports.insert(name, modules);
+    }
+
+    Ok(why(
+        config.package.name.as_str(),
+        &imports,
+        &dependency_imports,
+        module,
+    ))
+}
+
+fn is_in_package(package_name: &str, module: &str) -> bool {
+    module == package_name || module.starts_with(format!("{package_name}/").as_str())
+}
+
+/// the content of the module `example.com/blog` of a dependency, `.packages/example.com/blog.ftd`
+/// or `.packages/example.com/blog/index.ftd`
+async fn read_module(
+    config: &fastn_core::Config,
+    module: &str,
+) -> fastn_core::Result<Option<String>> {
+    for path in [format!("{module}.ftd"), format!("{module}/index.ftd")] {
+        let path = config.packages_root.join(path);
+        if config.ds.exists(&path).await {
+            return Ok(Some(config.ds.read_to_string(&path).await?));
+        }
+    }
+    Ok(None)
+}
+
+/// the modules a document of `package` imports, its auto imports included
+fn package_document_imports(
+    package: &fastn_core::Package,
+    content: &str,
+    id: &str,
+) -> fastn_core::Result<Vec<String>> {
+    let body = package.fix_imports_in_body(content, id)?;
+    let mut modules = document_imports(body.as_str(), id)?;
+    modules.extend(package.auto_import.iter().map(|a| a.path.to_string()));
+    Ok(modules)
+}
+
+/// the modules of the `-- import:` sections of `body`, without the aliases and the `exposing`
+/// and `export` headers
+fn document_imports(body: &str, id: &str) -> fastn_core::Result<Vec<String>> {
+    let mut modules = vec![];
+    for section in ftd_p1::parse(body, id)? {
+        if ftd_ast::Import::is_import(&section) {
+            modules.push(ftd_ast::Import::from_p1(&section, id)?.module);
+        }
+    }
+    Ok(modules)
+}
+
+/// the module of document `id` of `package_name`, `blog/index.ftd` of `example.com` is
+/// `example.com/blog`
+fn document_module(package_name: &str, id: &str) -> String {
+    normalize_module(format!("{package_name}/{}", id.trim_end_matches(".ftd")).as_str())
+}
+
+fn normalize_module(module: &str) -> String {
+    let module = module.trim().trim_end_matches('/');
+    module.strip_suffix("/index").unwrap_or(module).to_string()
+}
+
+/// `imports` is document id -> modules it imports, `dependency_imports` is the same for the
+/// modules of the dependencies. The chains are the shortest ones.
+fn why(
+    package_name: &str,
+    imports: &std::collections::BTreeMap<String, Vec<String>>,
+    dependency_imports: &std::collections::BTreeMap<String, Vec<String>>,
+    module: &str,
+) -> Vec<Importer> {
+    let module = if imports.contains_key(module) || imports.contains_key(&format!("{module}.ftd")) {
+        // a document id of the package
+        document_module(package_name, module)
+    } else {
+        normalize_module(module)
+    };
+    let matches = |m: &str| m == module || m.starts_with(format!("{module}/").as_str());
+
+    // module -> the document of the package it is, and the modules it imports
+    let mut modules: std::collections::BTreeMap<String, (Option<&String>, Vec<String>)> =
+        dependency_imports
+            .iter()
+            .map(|(name, imported)| {
+                (
+                    normalize_module(name),
+                    (None, imported.iter().map(|m| normalize_module(m)).collect()),
+                )
+            })
+            .collect();
+    modules.extend(imports.iter().map(|(id, imported)| {
+        (
+            document_module(package_name, id),
+            (
+                Some(id),
+                imported.iter().map(|m| normalize_module(m)).collect(),
+            ),
+        )
+    }));
+
+    // module -> the chain from it to `module`
+    let mut chains: std::collections::BTreeMap<String, Vec<String>> =
+        std::collections::BTreeMap::new();
+    let mut queue = std::collections::VecDeque::new();
+    for (_, imported) in modules.values() {
+        for m in imported.iter().filter(|m| matches(m)) {
+            if !chains.contains_key(m) {
+                chains.insert(m.to_string(), vec![m.to_string()]);
+                queue.push_back(m.to_string());
+            }
+        }
+    }
+    while let Some(target) = queue.pop_front() {
+        for (name, (_, imported)) in modules.iter() {
+            if chains.contains_key(name) || !imported.contains(&target) {
+                continue;
+            }
+            let mut chain = vec![name.to_string()];
+            chain.extend(chains[&target].iter().cloned());
+            chains.insert(name.to_string(), chain);
+            queue.push_back(name.to_string());
+        }
+    }
+
+    let mut importers = vec![];
+    for (name, (id, imported)) in modules.iter() {
+        let id = match id {
+            Some(id) if !matches(name) => id,
+            _ => continue,
+        };
+        let chain = imported
+            .iter()
+            .filter_map(|m| chains.get(m))
+            .min_by_key(|c| c.len());
+        if let Some(chain) = chain {
+            importers.push(Importer {
+                document: id.to_string(),
+                chain: chain.clone(),
+            });
+        }
+    }
+    importers.sort_by(|a, b| a.document.cmp(&b.document));
+    importers
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn why() {
+        let imports: std::collections::BTreeMap<String, Vec<String>> = [
+            (
+                "index.ftd",
+                super::document_imports(
+                    indoc::indoc! {"
+                    -- import: example.com/components/card as c
+                    exposing: card
+
+                    -- import: fastn-community.github.io/doc-site
+                    export: page
+
+                    -- c.card:
+                "},
+                    "index.ftd",
+                )
+                .unwrap(),
+            ),
+            (
+                "components/card.ftd",
+                vec!["example.com/components/icons/".to_string()],
+            ),
+            ("components/icons.ftd", vec![]),
+            ("about.ftd", vec!["example.com/index".to_string()]),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+        assert_eq!(
+            imports["index.ftd"],
+            vec![
+                "example.com/components/card".to_string(),
+                "fastn-community.github.io/doc-site".to_string(),
+            ]
+        );
+        let dependency_imports: std::collections::BTreeMap<String, Vec<String>> = [(
+            "fastn-community.github.io/doc-site".to_string(),
+            vec!["fastn-community.github.io/typography/".to_string()],
+        )]
+        .into_iter()
+        .collect();
+        let why = |module| super::why("example.com", &imports, &dependency_imports, module);
+
+        let importers = why("components/icons");
+        assert_eq!(
+            importers,
+            vec![
+                super::Importer {
+                    document: "about.ftd".to_string(),
+                    chain: vec![
+                        "example.com".to_string(),
+                        "example.com/components/card".to_string(),
+                        "example.com/components/icons".to_string(),
+                    ],
+                },
+                super::Importer {
+                    document: "components/card.ftd".to_string(),
+                    chain: vec!["example.com/components/icons".to_string()],
+                },
+                super::Importer {
+                    document: "index.ftd".to_string(),
+                    chain: vec![
+                        "example.com/components/card".to_string(),
+                        "example.com/components/icons".to_string(),
+                    ],
+                },
+            ]
+        );
+
+        // a package, `about.ftd` imports it through `index.ftd`
+        let importers = why("fastn-community.github.io");
+        assert_eq!(
+            importers
+                .iter()
+                .map(|i| (i.document.as_str(), i.chain.len()))
+                .collect::<Vec<_>>(),
+            vec![("about.ftd", 2), ("index.ftd", 1)]
+        );
+        assert!(why("about").is_empty());
+
+        // a module of another package, imported through a module of a dependency
+        assert_eq!(
+            why("fastn-community.github.io/typography"),
+            vec![
+                super::Importer {
+                    document: "about.ftd".to_string(),
+                    chain: vec![
+                        "example.com".to_string(),
+                        "fastn-community.github.io/doc-site".to_string(),
+                        "fastn-community.github.io/typography".to_string(),
+                    ],
+                },
+                super::Importer {
+                    document: "index.ftd".to_string(),
+                    chain: vec![
+                        "fastn-community.github.io/doc-site".to_string(),
+                        "fastn-community.github.io/typography".to_string(),
+                    ],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn tree_to_dot() {
+        let node = |name: &str, version: Option<&str>, dependencies| super::PackageNode {
+            name: name.to_string(),
+            version: version.map(ToString::to_string),
+            checksum: None,
+            dependencies,
+        };
+        let tree = node(
+            "example.com",
+            None,
+            vec![node(
+                "a.com",
+                Some("1.0"),
+                vec![node("b.com", None, vec![])],
+            )],
+        );
+        assert_eq!(
+            super::tree_to_dot(&tree),
+            indoc::indoc! {r#"
+                digraph deps {
+                  "example.com";
+                  "a.com" -> "b.com";
+                  "example.com" -> "a.com" [label="1.0"];
+                }
+            "#}
+        );
+    }
+}
@@ -0,0 +1,117 @@
+/// The messages one language of `i18n/` is missing, and the ones no document uses
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct LanguageReport {
+    pub language: String,
+    /// used by a `translate` processor or defined for the default language, but not defined
+    pub missing: Vec<String>,
+    /// defined, but not used by any `translate` processor
+    pub unused: Vec<String>,
+}
+
+/// `fastn i18n` checks the message catalogs of the package, see `fastn_core::catalog::Catalogs`
+pub async fn i18n(
+    config: &fastn_core::Config,
+    message_format: fastn_core::commands::check::MessageFormat,
+) -> fastn_core::Result<Vec<LanguageReport>> {
+    use colored::Colorize;
+
+    let catalogs = fastn_core::catalog::Catalogs::read(config).await?;
+    catalogs.validate()?;
+
+    let mut used = std::collections::BTreeSet::new();
+    for file in config.get_files(&config.package).await? {
+        if let fastn_core::File::Ftd(document) = file {
+            used.extend(fastn_core::catalog::used_message_ids(
+                document.content.as_str(),
+            ));
+        }
+    }
+
+    let default_language = config
+        .package
+        .lang
+        .as_ref()
+        .map(|l| l.default_lang.to_string());
+    let reports = report(&catalogs, &used, default_language.as_deref());
+
+    match message_format {
+        fastn_core::commands::check::MessageFormat::Json => {
+            println!("{}", serde_json::to_string_pretty(&reports)?)
+        }
+        fastn_core::commands::check::MessageFormat::Human => {
+            if reports.is_empty() {
+                println!(
+                    "No message catalogs in {}/",
+                    fastn_core::catalog::CATALOG_FOLDER
+                );
+            }
+            for r in reports.iter() {
+                println!(
+                    "{}: {} missing, {} unused",
+                    r.language.magenta(),
+                    r.missing.len(),
+                    r.unused.len()
+                );
+                for id in r.missing.iter() {
+                    println!("  {} {id}", "missing".red());
+                }
+                for id in r.unused.iter() {
+                    println!("  {} {id}", "unused".yellow());
+                }
+            }
+        }
+    }
+
+    Ok(reports)
+}
+
+fn report(
+    catalogs: &fastn_core::catalog::Catalogs,
+    used: &std::collections::BTreeSet<String>,
+    default_language: Option<&str>,
+) -> Vec<LanguageReport> {
+    let mut expected = used.clone();
+    if let Some(default_language) = default_language {
+        expected.extend(catalogs.message_ids(default_language));
+    }
+
+    catalogs
+        .languages()
+        .into_iter()
+        .map(|language| {
+            let defined = catalogs.message_ids(language.as_str());
+            LanguageReport {
+                missing: expected.difference(&defined).cloned().collect(),
+                unused: defined.difference(used).cloned().collect(),
+                language,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn report() {
+        let mut catalogs = fastn_core::catalog::Catalogs::default();
+        catalogs.add("en", "i18n/en/main.ftl", "a = A\nb = B\n".to_string());
+        catalogs.add("hi", "i18n/hi/main.ftl", "a = अ\nc = स\n".to_string());
+        let used = ["a".to_string(), "d".to_string()].into_iter().collect();
+
+        assert_eq!(
+            super::report(&catalogs, &used, Some("en")),
+            vec![
+                super::LanguageReport {
+                    language: "en".to_string(),
+                    missing: vec!["d".to_string()],
+                    unused: vec!["b".to_string()],
+                },
+                super::LanguageReport {
+                    language: "hi".to_string(),
+                    missing: vec!["b".to_string(), "d".to_string()],
+                    unused: vec!["c".to_string()],
+                },
+            ]
+        );
+    }
+}
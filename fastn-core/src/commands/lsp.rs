@@ -0,0 +1,708 @@
+// A minimal language server for `.ftd` files, speaking LSP (JSON-RPC) over stdio.
+//
+// Supported requests: diagnostics (on open, change and save), go to definition, hover and
+// completion. Documents are always synced in full.
+
+const COMPLETION_KIND_CLASS: u8 = 7;
+const COMPLETION_KIND_PROPERTY: u8 = 10;
+
+pub async fn lsp(config: &fastn_core::Config) -> fastn_core::Result<()> {
+    let mut reader = tokio::io::BufReader::new(tokio::io::stdin());
+    let mut writer = tokio::io::stdout();
+    let mut server = Server {
+        config,
+        documents: Default::default(),
+        shutdown: false,
+    };
+
+    while let Some(message) = read_message(&mut reader).await? {
+        let method = match message.get("method").and_then(|v| v.as_str()) {
+            Some(method) => method.to_string(),
+            // responses to requests we never send
+            None => continue,
+        };
+        let params = message
+            .get("params")
+            .cloned()
+            .unwrap_or(serde_json::Value::Null);
+
+        if method == "exit" {
+            break;
+        }
+
+        let result = server.handle(method.as_str(), &params, &mut writer).await?;
+
+        if let Some(id) = message.get("id") {
+            let response = match result {
+                Some(result) => serde_json::json!({"jsonrpc": "2.0", "id": id, "result": result}),
+                None => serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": {"code": -32601, "message": format!("method not found: {}", method)},
+                }),
+            };
+            write_message(&mut writer, &response).await?;
+        }
+    }
+
+    if !server.shutdown {
+        tracing::info!(msg = "lsp: exit without shutdown");
+    }
+
+    Ok(())
+}
+
+struct OpenDocument {
+    text: String,
+    /// the last document that interpreted without errors, used for hover and completion while
+    /// the document is being edited
+    interpreted: Option<ftd::interpreter::Document>,
+}
+
+struct Server<'a> {
+    config: &'a fastn_core::Config,
+    /// uri -> document
+    documents: std::collections::HashMap<String, OpenDocument>,
+    shutdown: bool,
+}
+
+impl Server<'_> {
+    /// returns `None` for methods we do not know about
+    async fn handle(
+        &mut self,
+        method: &str,
+        params: &serde_json::Value,
+        writer: &mut tokio::io::Stdout,
+    ) -> fastn_core::Result<Option<serde_json::Value>> {
+        let uri = params
+            .pointer("/textDocument/uri")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
+
+        Ok(Some(match method {
+            "initialize" => serde_json::json!({
+                "capabilities": {
+                    // full document sync
+                    "textDocumentSync": {"openClose": true, "change": 1, "save": true},
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "completionProvider": {"triggerCharacters": ["-", " ", "."]},
+                },
+                "serverInfo": {"name": "fastn", "version": env!("CARGO_PKG_VERSION")},
+            }),
+            "shutdown" => {
+                self.shutdown = true;
+                serde_json::Value::Null
+            }
+            "textDocument/didOpen" => {
+                let text = params
+                    .pointer("/textDocument/text")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default();
+                self.update(uri.as_str(), text.to_string(), writer).await?;
+                serde_json::Value::Null
+            }
+            "textDocument/didChange" => {
+                // we only support full sync, so the last change is the whole document
+                if let Some(text) = params
+                    .get("contentChanges")
+                    .and_then(|v| v.as_array())
+                    .and_then(|v| v.last())
+                    .and_then(|v| v.get("text"))
+                    .and_then(|v| v.as_str())
+                {
+                    self.update(uri.as_str(), text.to_string(), writer).await?;
+                }
+                serde_json::Value::Null
+            }
+            "textDocument/didSave" => {
+                if let Some(text) = self.documents.get(uri.as_str()).map(|d| d.text.clone()) {
+                    self.update(uri.as_str(), text, writer).await?;
+                }
+                serde_json::Value::Null
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri.as_str());
+                serde_json::Value::Null
+            }
+            "textDocument/definition" => self
+                .definition(uri.as_str(), position(params))
+                .await
+                .unwrap_or(serde_json::Value::Null),
+            "textDocument/hover" => self
+                .hover(uri.as_str(), position(params))
+                .unwrap_or(serde_json::Value::Null),
+            "textDocument/completion" => {
+                serde_json::Value::Array(self.completion(uri.as_str(), position(params)))
+            }
+            // notifications that need no work
+            "initialized" | "$/cancelRequest" | "$/setTrace" => serde_json::Value::Null,
+            _ => return Ok(None),
+        }))
+    }
+
+    fn document_id(&self, uri: &str) -> Option<String> {
+        let path = url::Url::parse(uri).ok()?.to_file_path().ok()?;
+        let root = self.config.ds.root().to_string();
+        path.strip_prefix(root.as_str())
+            .ok()
+            .map(|v| v.to_string_lossy().replace('\\', "/"))
+    }
+
+    fn document(&self, uri: &str, text: &str) -> Option<fastn_core::Document> {
+        Some(fastn_core::Document {
+            package_name: self.config.package.name.to_string(),
+            id: self.document_id(uri)?,
+            content: text.to_string(),
+            parent_path: self.config.ds.root(),
+        })
+    }
+
+    /// interprets the new text of `uri` and publishes its diagnostics
+    async fn update(
+        &mut self,
+        uri: &str,
+        text: String,
+        writer: &mut tokio::io::Stdout,
+    ) -> fastn_core::Result<()> {
+        let mut diagnostics = vec![];
+        let mut interpreted = self.documents.remove(uri).and_then(|d| d.interpreted);
+
+        // files of dependencies in .packages are not checked, we only use them for navigation
+        if let Some(document) = self
+            .document(uri, text.as_str())
+            .filter(|d| !d.id.starts_with(".packages/"))
+        {
+            match fastn_core::commands::check::interpret_document(self.config, &document).await {
+                Ok(d) => interpreted = Some(d),
+                Err(d) => diagnostics.push(to_lsp_diagnostic(&d, document.id.as_str())),
+            }
+        }
+
+        self.documents
+            .insert(uri.to_string(), OpenDocument { text, interpreted });
+
+        write_message(
+            writer,
+            &serde_json::json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": {"uri": uri, "diagnostics": diagnostics},
+            }),
+        )
+        .await
+    }
+
+    /// returns (module, name) the word under the cursor refers to, e.g. `lib.button` ->
+    /// (`<lib-module>`, `button`)
+    fn resolve(&self, uri: &str, position: (usize, usize)) -> Option<(String, String)> {
+        let document = self.documents.get(uri)?;
+        let word = word_at(document.text.as_str(), position)?;
+
+        let (doc_name, aliases) = match document.interpreted {
+            Some(ref d) => (d.name.trim_end_matches('/').to_string(), d.aliases.clone()),
+            None => (
+                self.document(uri, document.text.as_str())?
+                    .id_with_package()
+                    .trim_end_matches('/')
+                    .to_string(),
+                ftd::interpreter::default::default_aliases(),
+            ),
+        };
+
+        Some(resolve_word(word.as_str(), doc_name.as_str(), &aliases))
+    }
+
+    async fn definition(&self, uri: &str, position: (usize, usize)) -> Option<serde_json::Value> {
+        let (module, name) = self.resolve(uri, position)?;
+        let current = self.documents.get(uri)?;
+
+        // builtins do not live in any file
+        if module == "ftd" || module == "inherited" {
+            return None;
+        }
+
+        let current_module = self
+            .document(uri, current.text.as_str())?
+            .id_with_package()
+            .trim_end_matches('/')
+            .to_string();
+
+        let (target_uri, line) = if current_module == module {
+            (
+                uri.to_string(),
+                definition_line(current.text.as_str(), module.as_str(), name.as_str())?,
+            )
+        } else {
+            let mut found = None;
+            for path in self.module_paths(module.as_str()) {
+                if let Ok(content) = self.config.ds.read_to_string(&path).await {
+                    found = Some((path, content));
+                    break;
+                }
+            }
+            let (path, content) = found?;
+            (
+                url::Url::from_file_path(path.to_string()).ok()?.to_string(),
+                definition_line(content.as_str(), module.as_str(), name.as_str())?,
+            )
+        };
+
+        let position = serde_json::json!({"line": line.saturating_sub(1), "character": 0});
+        Some(serde_json::json!({
+            "uri": target_uri,
+            "range": {"start": position, "end": position},
+        }))
+    }
+
+    /// files that can contain `module`, modules of the current package are in the package root
+    /// and the rest are in `.packages`
+    fn module_paths(&self, module: &str) -> Vec<fastn_ds::Path> {
+        let package_name = self.config.package.name.as_str();
+        let base = match module.strip_prefix(package_name) {
+            Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+                self.config.ds.root().join(rest.trim_matches('/'))
+            }
+            _ => self.config.packages_root.join(module.trim_matches('/')),
+        };
+
+        let base = base.to_string();
+        vec![
+            fastn_ds::Path::new(format!("{}.ftd", base.trim_end_matches('/'))),
+            fastn_ds::Path::new(format!("{}/index.ftd", base.trim_end_matches('/'))),
+        ]
+    }
+
+    fn thing(&self, uri: &str, key: &str) -> Option<ftd::interpreter::Thing> {
+        self.documents
+            .get(uri)
+            .and_then(|d| d.interpreted.as_ref())
+            .and_then(|d| d.data.get(key))
+            .or_else(|| ftd::interpreter::default::get_default_bag().get(key))
+            .cloned()
+    }
+
+    fn hover(&self, uri: &str, position: (usize, usize)) -> Option<serde_json::Value> {
+        let (module, name) = self.resolve(uri, position)?;
+        let thing = self.thing(uri, format!("{}#{}", module, name).as_str())?;
+
+        Some(serde_json::json!({
+            "contents": {
+                "kind": "markdown",
+                "value": format!("```ftd\n{}\n```", describe(&thing)),
+            }
+        }))
+    }
+
+    fn completion(&self, uri: &str, (line, character): (usize, usize)) -> Vec<serde_json::Value> {
+        let document = match self.documents.get(uri) {
+            Some(d) => d,
+            None => return vec![],
+        };
+        let lines = document.text.lines().collect::<Vec<&str>>();
+        let prefix = match lines.get(line) {
+            Some(l) => l.chars().take(character).collect::<String>(),
+            None => return vec![],
+        };
+
+        if prefix.contains(':') {
+            return vec![];
+        }
+
+        if prefix.trim_start().starts_with("--") {
+            return self.complete_components(document);
+        }
+
+        // we are on a header line, complete the arguments of the enclosing component
+        let section = match enclosing_section(&lines[..line]) {
+            Some(section) => section,
+            None => return vec![],
+        };
+        let (module, name) = match self.resolve_name(uri, section.as_str()) {
+            Some(v) => v,
+            None => return vec![],
+        };
+        let arguments = match self.thing(uri, format!("{}#{}", module, name).as_str()) {
+            Some(ftd::interpreter::Thing::Component(c)) => c.arguments,
+            Some(ftd::interpreter::Thing::WebComponent(c)) => c.arguments,
+            _ => return vec![],
+        };
+
+        arguments
+            .iter()
+            .map(|argument| {
+                serde_json::json!({
+                    "label": argument.name,
+                    "kind": COMPLETION_KIND_PROPERTY,
+                    "detail": kind_data_to_string(&argument.kind),
+                    "insertText": format!("{}: ", argument.name),
+                })
+            })
+            .collect()
+    }
+
+    fn resolve_name(&self, uri: &str, name: &str) -> Option<(String, String)> {
+        let document = self.documents.get(uri)?;
+        let interpreted = document.interpreted.as_ref()?;
+        Some(resolve_word(
+            name,
+            interpreted.name.trim_end_matches('/'),
+            &interpreted.aliases,
+        ))
+    }
+
+    /// components defined in this document, in imported modules and the `ftd` builtins
+    fn complete_components(&self, document: &OpenDocument) -> Vec<serde_json::Value> {
+        let (doc_name, aliases, data) = match document.interpreted {
+            Some(ref d) => (
+                d.name.trim_end_matches('/').to_string(),
+                d.aliases.clone(),
+                Some(&d.data),
+            ),
+            None => (
+                "".to_string(),
+                ftd::interpreter::default::default_aliases(),
+                None,
+            ),
+        };
+
+        let mut labels = std::collections::BTreeSet::new();
+        for (key, thing) in data
+            .into_iter()
+            .flatten()
+            .chain(ftd::interpreter::default::get_default_bag().iter())
+        {
+            if !matches!(
+                thing,
+                ftd::interpreter::Thing::Component(_) | ftd::interpreter::Thing::WebComponent(_)
+            ) {
+                continue;
+            }
+            let (module, name) = match key.split_once('#') {
+                Some(v) => v,
+                None => continue,
+            };
+            if module == doc_name {
+                labels.insert(name.to_string());
+            } else if let Some((alias, _)) = aliases.iter().find(|(_, m)| m.as_str() == module) {
+                labels.insert(format!("{}.{}", alias, name));
+            }
+        }
+
+        labels
+            .into_iter()
+            .map(|label| serde_json::json!({"label": label, "kind": COMPLETION_KIND_CLASS}))
+            .collect()
+    }
+}
+
+/// (line, character), both 0 based
+fn position(params: &serde_json::Value) -> (usize, usize) {
+    let get = |p: &str| {
+        params
+            .pointer(p)
+            .and_then(|v| v.as_u64())
+            .unwrap_or_default() as usize
+    };
+    (get("/position/line"), get("/position/character"))
+}
+
+fn to_lsp_diagnostic(
+    d: &fastn_core::commands::check::Diagnostic,
+    doc_id: &str,
+) -> serde_json::Value {
+    let mut message = d.message.to_string();
+    // errors in an imported module are shown at the top of the current document
+    let (line, start, end) = if d.doc_id == doc_id {
+        let line = d.line.unwrap_or(1).saturating_sub(1);
+        match d.column {
+            Some(ref c) => (line, c.start - 1, c.end - 1),
+            None => (line, 0, 0),
+        }
+    } else {
+        message = format!("{}: {}", d.doc_id, message);
+        (0, 0, 0)
+    };
+    if let Some(ref suggestion) = d.suggestion {
+        message = format!("{}\nhelp: {}", message, suggestion);
+    }
+
+    serde_json::json!({
+        "range": {
+            "start": {"line": line, "character": start},
+            "end": {"line": line, "character": end},
+        },
+        "severity": match d.severity {
+            fastn_core::commands::check::Severity::Error => 1,
+            fastn_core::commands::check::Severity::Warning => 2,
+        },
+        "code": d.code,
+        "source": "fastn",
+        "message": message,
+    })
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '$' | '#')
+}
+
+fn word_at(text: &str, (line, character): (usize, usize)) -> Option<String> {
+    let line = text.lines().nth(line)?.chars().collect::<Vec<char>>();
+    let character = character.min(line.len());
+
+    let start = line[..character]
+        .iter()
+        .rposition(|c| !is_name_char(*c))
+        .map_or(0, |v| v + 1);
+    let end = line[character..]
+        .iter()
+        .position(|c| !is_name_char(*c))
+        .map_or(line.len(), |v| v + character);
+
+    let word = line[start..end]
+        .iter()
+        .collect::<String>()
+        .trim_matches('.')
+        .to_string();
+
+    if word.is_empty() {
+        None
+    } else {
+        Some(word)
+    }
+}
+
+/// `lib.button` -> (`<module lib is an alias of>`, `button`), `$foo.bar` -> (`doc_name`, `foo`)
+fn resolve_word(word: &str, doc_name: &str, aliases: &ftd::Map<String>) -> (String, String) {
+    let word = word.trim_start_matches('$').trim_start_matches('*');
+
+    if let Some((module, name)) = word.split_once('#') {
+        return (module.to_string(), first_part(name));
+    }
+
+    match word.split_once('.') {
+        Some((alias, rest)) if aliases.contains_key(alias) => {
+            (aliases[alias].to_string(), first_part(rest))
+        }
+        _ => (doc_name.to_string(), first_part(word)),
+    }
+}
+
+fn first_part(name: &str) -> String {
+    name.split('.').next().unwrap_or(name).to_string()
+}
+
+/// line (1 based) of the section that defines `name` in `content`
+fn definition_line(content: &str, module: &str, name: &str) -> Option<usize> {
+    let is_definition = |kind: &Option<String>, section_name: &str| {
+        kind.is_some() && section_name.split('(').next().map(str::trim) == Some(name)
+    };
+
+    if let Ok(sections) = ftd_p1::parse(content, module) {
+        return sections
+            .iter()
+            .find(|s| is_definition(&s.kind, s.name.as_str()))
+            .map(|s| s.line_number);
+    }
+
+    // the document does not parse while it is being edited, fall back to looking at the lines
+    content
+        .lines()
+        .position(|l| {
+            let l = match l.trim_start().strip_prefix("-- ") {
+                Some(l) => l,
+                None => return false,
+            };
+            match l.split(':').next().and_then(|v| v.rsplit_once(' ')) {
+                Some((kind, section_name)) => is_definition(&Some(kind.to_string()), section_name),
+                None => false,
+            }
+        })
+        .map(|v| v + 1)
+}
+
+/// the name of the component whose header we are typing, `None` for definitions
+fn enclosing_section(lines: &[&str]) -> Option<String> {
+    let line = lines.iter().rev().find_map(|l| {
+        l.trim_start()
+            .strip_prefix("-- ")
+            .filter(|l| !l.starts_with("end:"))
+    })?;
+
+    let name = line.split(':').next()?.trim();
+    if name.contains(' ') {
+        // `-- component foo:` or `-- string bar:`
+        return None;
+    }
+    Some(name.to_string())
+}
+
+fn kind_to_string(kind: &ftd::interpreter::Kind) -> String {
+    match kind {
+        ftd::interpreter::Kind::List { kind } => format!("{} list", kind_to_string(kind)),
+        ftd::interpreter::Kind::Optional { kind } => format!("optional {}", kind_to_string(kind)),
+        ftd::interpreter::Kind::Constant { kind } => format!("constant {}", kind_to_string(kind)),
+        ftd::interpreter::Kind::UI { .. } => "ftd.ui".to_string(),
+        kind => kind.get_name(),
+    }
+}
+
+fn kind_data_to_string(kind: &ftd::interpreter::KindData) -> String {
+    let kind_str = kind_to_string(&kind.kind);
+    match (kind.caption, kind.body) {
+        (true, true) => format!("caption or body {}", kind_str),
+        (true, false) => format!("caption {}", kind_str),
+        (false, true) => format!("body {}", kind_str),
+        (false, false) => kind_str,
+    }
+}
+
+/// ftd like signature of `thing`, shown on hover
+fn describe(thing: &ftd::interpreter::Thing) -> String {
+    let fields = |fields: &[ftd::interpreter::Field]| {
+        fields
+            .iter()
+            .map(|f| format!("{} {}:", kind_data_to_string(&f.kind), f.name))
+            .collect::<Vec<String>>()
+            .join("\n")
+    };
+
+    match thing {
+        ftd::interpreter::Thing::Variable(v) => {
+            format!("-- {} {}:", kind_data_to_string(&v.kind), v.name)
+        }
+        ftd::interpreter::Thing::Component(c) => {
+            format!("-- component {}:\n{}", c.name, fields(&c.arguments))
+        }
+        ftd::interpreter::Thing::WebComponent(c) => {
+            format!("-- web-component {}:\n{}", c.name, fields(&c.arguments))
+        }
+        ftd::interpreter::Thing::Record(r) => {
+            format!("-- record {}:\n{}", r.name, fields(&r.fields))
+        }
+        ftd::interpreter::Thing::OrType(o) => format!("-- or-type {}:", o.name),
+        ftd::interpreter::Thing::OrTypeWithVariant { or_type, variant } => {
+            format!("-- or-type {}:\n{}", or_type, variant.name())
+        }
+        ftd::interpreter::Thing::Function(f) => format!(
+            "-- {} {}({})",
+            kind_data_to_string(&f.return_kind),
+            f.name,
+            f.arguments
+                .iter()
+                .map(|a| format!("{} {}", kind_data_to_string(&a.kind), a.name))
+                .collect::<Vec<String>>()
+                .join(", ")
+        ),
+        ftd::interpreter::Thing::Export { from, to, .. } => format!("{} -> {}", from, to),
+    }
+}
+
+async fn read_message<R>(reader: &mut R) -> fastn_core::Result<Option<serde_json::Value>>
+where
+    R: tokio::io::AsyncBufRead + Unpin,
+{
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 {
+            // stdin closed
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((key, value)) = header.split_once(':') {
+            if key.eq_ignore_ascii_case("content-length") {
+                content_length = Some(value.trim().parse::<usize>()?);
+            }
+        }
+    }
+
+    let content_length = match content_length {
+        Some(v) => v,
+        None => return fastn_core::generic_error("lsp: missing Content-Length header".to_string()),
+    };
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body).await?;
+
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+async fn write_message(
+    writer: &mut tokio::io::Stdout,
+    message: &serde_json::Value,
+) -> fastn_core::Result<()> {
+    use tokio::io::AsyncWriteExt;
+
+    let body = serde_json::to_string(message)?;
+    writer
+        .write_all(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).as_bytes())
+        .await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn word_at() {
+        let text = "-- lib.button: Hello\n$on-click$: $toggle($a.b)";
+        assert_eq!(super::word_at(text, (0, 5)), Some("lib.button".to_string()));
+        assert_eq!(
+            super::word_at(text, (0, 13)),
+            Some("lib.button".to_string())
+        );
+        assert_eq!(super::word_at(text, (0, 14)), None);
+        assert_eq!(super::word_at(text, (1, 22)), Some("$a.b".to_string()));
+    }
+
+    #[test]
+    fn resolve_word() {
+        let mut aliases = ftd::interpreter::default::default_aliases();
+        aliases.insert("lib".to_string(), "foo.com/lib".to_string());
+
+        assert_eq!(
+            super::resolve_word("lib.button", "foo.com/index", &aliases),
+            ("foo.com/lib".to_string(), "button".to_string())
+        );
+        assert_eq!(
+            super::resolve_word("ftd.text", "foo.com/index", &aliases),
+            ("ftd".to_string(), "text".to_string())
+        );
+        assert_eq!(
+            super::resolve_word("$person.name", "foo.com/index", &aliases),
+            ("foo.com/index".to_string(), "person".to_string())
+        );
+    }
+
+    #[test]
+    fn definition_line() {
+        let content = "-- import: foo.com/lib\n\n-- lib.button: Hello\n\n-- component card:\ncaption title:\n\n-- ftd.text: $card.title\n\n-- end: card\n";
+        assert_eq!(
+            super::definition_line(content, "foo.com/index", "card"),
+            Some(5)
+        );
+        assert_eq!(
+            super::definition_line(content, "foo.com/index", "button"),
+            None
+        );
+    }
+
+    #[test]
+    fn enclosing_section() {
+        let lines = vec!["-- ftd.column:", "", "-- ftd.text: hello", "color: red"];
+        assert_eq!(
+            super::enclosing_section(&lines),
+            Some("ftd.text".to_string())
+        );
+        assert_eq!(
+            super::enclosing_section(&["-- component card:", "caption title:"]),
+            None
+        );
+    }
+}
@@ -0,0 +1,526 @@
+// Schema migrations for the database the `sql`, `pg` and `sqlite` processors query.
+//
+// Migrations are `.sql` files in the `migrations` folder of the package, their name starts
+// with a number that decides the order they are applied in: `0001-create-users.sql`. A
+// migration can be reverted if it has a `0001-create-users.down.sql` next to it. The database
+// comes from `FASTN_DB_URL` (`sqlite:///fastn.sqlite`, the default, or `postgres://..`),
+// applied migrations are recorded in the `fastn_migration` table of that database.
+
+pub const MIGRATIONS_FOLDER: &str = "migrations";
+const DOWN_EXTENSION: &str = ".down.sql";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrateAction {
+    Status,
+    Apply,
+    /// reverts every migration applied after the given one, `0` reverts all of them
+    RollbackTo(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Migration {
+    /// file name without `.sql`
+    pub id: String,
+    pub number: u64,
+    pub up: String,
+    pub down: Option<String>,
+    pub checksum: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppliedMigration {
+    pub id: String,
+    pub checksum: String,
+    pub applied_on: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    Applied,
+    Pending,
+    /// the file changed after it was applied
+    Modified,
+    /// applied, but the file is no longer in `migrations`
+    Missing,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Status::Applied => write!(f, "applied"),
+            Status::Pending => write!(f, "pending"),
+            Status::Modified => write!(f, "modified"),
+            Status::Missing => write!(f, "missing"),
+        }
+    }
+}
+
+pub async fn migrate(config: &fastn_core::Config, action: MigrateAction) -> fastn_core::Result<()> {
+    let migrations = read_migrations(config).await?;
+    let database = Database::from_config(config).await?;
+    let applied = database.applied().await?;
+    let statuses = statuses(&migrations, &applied);
+
+    match action {
+        MigrateAction::Status => {
+            if statuses.is_empty() {
+                println!("No migrations found in {MIGRATIONS_FOLDER}/");
+            }
+            for (id, status) in statuses {
+                println!("{status:<8} {id}");
+            }
+            Ok(())
+        }
+        MigrateAction::Apply => {
+            if let Some((id, _)) = statuses.iter().find(|(_, s)| *s == Status::Modified) {
+                return fastn_core::usage_error(format!(
+                    "migration {id} was modified after it was applied, revert it with \
+                    `fastn migrate rollback-to` before changing it"
+                ));
+            }
+
+            let pending = pending(&migrations, &statuses);
+            if pending.is_empty() {
+                println!("No pending migrations");
+            }
+            for migration in pending {
+                let start = std::time::Instant::now();
+                print!("Applying {} ... ", migration.id);
+                database.apply(migration).await?;
+                fastn_core::utils::print_end(format!("Applied {}", migration.id).as_str(), start);
+            }
+            Ok(())
+        }
+        MigrateAction::RollbackTo(target) => {
+            for migration in to_revert(&migrations, &applied, target.as_str())? {
+                let start = std::time::Instant::now();
+                print!("Reverting {} ... ", migration.id);
+                database.revert(migration).await?;
+                fastn_core::utils::print_end(format!("Reverted {}", migration.id).as_str(), start);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// `fastn serve` does not start with pending migrations, unless
+/// `FASTN_ALLOW_PENDING_MIGRATIONS` is set, then it only warns
+pub async fn check_pending(config: &fastn_core::Config) -> fastn_core::Result<()> {
+    let migrations = read_migrations(config).await?;
+    if migrations.is_empty() {
+        return Ok(());
+    }
+
+    let applied = Database::from_config(config).await?.applied().await?;
+    let statuses = statuses(&migrations, &applied);
+    let pending = pending(&migrations, &statuses);
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let message = format!(
+        "{} pending migrations ({}), run `fastn migrate apply`",
+        pending.len(),
+        pending
+            .iter()
+            .map(|m| m.id.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    if config
+        .ds
+        .env_bool("FASTN_ALLOW_PENDING_MIGRATIONS", false)
+        .await?
+    {
+        fastn_core::warning!("{message}");
+        return Ok(());
+    }
+
+    fastn_core::usage_error(message)
+}
+
+pub async fn read_migrations(config: &fastn_core::Config) -> fastn_core::Result<Vec<Migration>> {
+    let folder = config.ds.root().join(MIGRATIONS_FOLDER);
+    if !config.ds.exists(&folder).await {
+        return Ok(vec![]);
+    }
+
+    let mut files = std::collections::BTreeMap::new();
+    let mut entries = config.ds.read_dir(&folder).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.ends_with(".sql") && entry.file_type().await?.is_file() {
+            let content = config
+                .ds
+                .read_to_string(&folder.join(name.as_str()))
+                .await?;
+            files.insert(name, content);
+        }
+    }
+
+    parse_migrations(files)
+}
+
+/// `files` are the names and content of the `.sql` files in `migrations`
+fn parse_migrations(
+    files: std::collections::BTreeMap<String, String>,
+) -> fastn_core::Result<Vec<Migration>> {
+    let mut migrations: Vec<Migration> = vec![];
+    for (name, up) in files.iter() {
+        if name.ends_with(DOWN_EXTENSION) {
+            let up_name = format!("{}.sql", name.trim_end_matches(DOWN_EXTENSION));
+            if !files.contains_key(&up_name) {
+                return fastn_core::usage_error(format!(
+                    "{MIGRATIONS_FOLDER}/{name} has no {up_name}"
+                ));
+            }
+            continue;
+        }
+
+        let id = name.trim_end_matches(".sql").to_string();
+        let number = match id
+            .split(|c: char| !c.is_ascii_digit())
+            .next()
+            .and_then(|v| v.parse::<u64>().ok())
+        {
+            Some(v) => v,
+            None => {
+                return fastn_core::usage_error(format!(
+                    "{MIGRATIONS_FOLDER}/{name}: migration file names have to start with a \
+                    number, e.g. 0001-create-users.sql"
+                ))
+            }
+        };
+
+        if let Some(other) = migrations.iter().find(|m| m.number == number) {
+            return fastn_core::usage_error(format!(
+                "{MIGRATIONS_FOLDER}/{name} and {}.sql have the same number {number}",
+                other.id
+            ));
+        }
+
+        migrations.push(Migration {
+            checksum: fastn_core::utils::generate_hash(up),
+            down: files.get(format!("{id}{DOWN_EXTENSION}").as_str()).cloned(),
+            up: up.to_string(),
+            number,
+            id,
+        });
+    }
+
+    migrations.sort_by_key(|m| m.number);
+    Ok(migrations)
+}
+
+fn statuses(migrations: &[Migration], applied: &[AppliedMigration]) -> Vec<(String, Status)> {
+    let mut statuses = migrations
+        .iter()
+        .map(|m| {
+            let status = match applied.iter().find(|a| a.id == m.id) {
+                Some(a) if a.checksum == m.checksum => Status::Applied,
+                Some(_) => Status::Modified,
+                None => Status::Pending,
+            };
+            (m.id.to_string(), status)
+        })
+        .collect::<Vec<_>>();
+
+    statuses.extend(
+        applied
+            .iter()
+            .filter(|a| !migrations.iter().any(|m| m.id == a.id))
+            .map(|a| (a.id.to_string(), Status::Missing)),
+    );
+
+    statuses
+}
+
+fn pending<'a>(migrations: &'a [Migration], statuses: &[(String, Status)]) -> Vec<&'a Migration> {
+    migrations
+        .iter()
+        .filter(|m| statuses.contains(&(m.id.to_string(), Status::Pending)))
+        .collect()
+}
+
+/// the applied migrations after `target` (an id or a number), last applied first
+fn to_revert<'a>(
+    migrations: &'a [Migration],
+    applied: &[AppliedMigration],
+    target: &str,
+) -> fastn_core::Result<Vec<&'a Migration>> {
+    let target = match migrations
+        .iter()
+        .find(|m| m.id == target || target.parse::<u64>() == Ok(m.number))
+    {
+        Some(m) => m.number,
+        // roll back everything
+        None if target == "0" => 0,
+        None => {
+            return fastn_core::usage_error(format!(
+                "no migration {target} in {MIGRATIONS_FOLDER}/"
+            ))
+        }
+    };
+
+    if let Some(a) = applied
+        .iter()
+        .find(|a| !migrations.iter().any(|m| m.id == a.id))
+    {
+        return fastn_core::usage_error(format!(
+            "migration {} is applied but not in {MIGRATIONS_FOLDER}/, can not roll back",
+            a.id
+        ));
+    }
+
+    let mut revert = migrations
+        .iter()
+        .filter(|m| m.number > target && applied.iter().any(|a| a.id == m.id))
+        .collect::<Vec<_>>();
+    revert.reverse();
+
+    if let Some(m) = revert.iter().find(|m| m.down.is_none()) {
+        return fastn_core::usage_error(format!(
+            "migration {} can not be reverted, {MIGRATIONS_FOLDER}/{}{DOWN_EXTENSION} does not \
+            exist",
+            m.id, m.id
+        ));
+    }
+
+    Ok(revert)
+}
+
+enum Database {
+    Sqlite(fastn_ds::sqlite::Pool),
+    Postgres(deadpool_postgres::Pool),
+}
+
+const CREATE_MIGRATION_TABLE: &str = "CREATE TABLE IF NOT EXISTS fastn_migration (
+    id TEXT PRIMARY KEY,
+    checksum TEXT NOT NULL,
+    applied_on TEXT NOT NULL
+)";
+
+impl Database {
+    async fn from_config(config: &fastn_core::Config) -> fastn_core::Result<Database> {
+        Ok(match config.ds.default_db_pool(false).await? {
+            fastn_ds::db::Pool::Sqlite(pool) => Database::Sqlite(pool),
+            fastn_ds::db::Pool::Postgres(pool) => Database::Postgres(pool),
+        })
+    }
+
+    async fn applied(&self) -> fastn_core::Result<Vec<AppliedMigration>> {
+        match self {
+            Database::Sqlite(pool) => sqlite_applied(pool).map_err(sqlite_error),
+            Database::Postgres(pool) => {
+                let client = pool.get().await?;
+                client
+                    .batch_execute(CREATE_MIGRATION_TABLE)
+                    .await
+                    .map_err(pg_error)?;
+                Ok(client
+                    .query(
+                        "SELECT id, checksum, applied_on FROM fastn_migration ORDER BY id",
+                        &[],
+                    )
+                    .await
+                    .map_err(pg_error)?
+                    .into_iter()
+                    .map(|r| AppliedMigration {
+                        id: r.get(0),
+                        checksum: r.get(1),
+                        applied_on: r.get(2),
+                    })
+                    .collect())
+            }
+        }
+    }
+
+    /// runs the migration and records it, in one transaction
+    async fn apply(&self, migration: &Migration) -> fastn_core::Result<()> {
+        let applied_on = chrono::Utc::now().to_rfc3339();
+        match self {
+            Database::Sqlite(pool) => {
+                let mut conn = sqlite_open(pool).map_err(sqlite_error)?;
+                let tx = conn.transaction().map_err(sqlite_error)?;
+                tx.execute_batch(migration.up.as_str())
+                    .map_err(sqlite_error)?;
+                tx.execute(
+                    "INSERT INTO fastn_migration (id, checksum, applied_on) VALUES (?1, ?2, ?3)",
+                    (&migration.id, &migration.checksum, &applied_on),
+                )
+                .map_err(sqlite_error)?;
+                tx.commit().map_err(sqlite_error)
+            }
+            Database::Postgres(pool) => {
+                let mut client = pool.get().await?;
+                let client: &mut tokio_postgres::Client = &mut client;
+                let tx = client.transaction().await.map_err(pg_error)?;
+                tx.batch_execute(migration.up.as_str())
+                    .await
+                    .map_err(pg_error)?;
+                tx.execute(
+                    "INSERT INTO fastn_migration (id, checksum, applied_on) VALUES ($1, $2, $3)",
+                    &[&migration.id, &migration.checksum, &applied_on],
+                )
+                .await
+                .map_err(pg_error)?;
+                tx.commit().await.map_err(pg_error)
+            }
+        }
+    }
+
+    /// runs the down migration and forgets it was applied, in one transaction
+    async fn revert(&self, migration: &Migration) -> fastn_core::Result<()> {
+        let down = migration.down.as_deref().unwrap_or_default();
+        match self {
+            Database::Sqlite(pool) => {
+                let mut conn = sqlite_open(pool).map_err(sqlite_error)?;
+                let tx = conn.transaction().map_err(sqlite_error)?;
+                tx.execute_batch(down).map_err(sqlite_error)?;
+                tx.execute(
+                    "DELETE FROM fastn_migration WHERE id = ?1",
+                    (&migration.id,),
+                )
+                .map_err(sqlite_error)?;
+                tx.commit().map_err(sqlite_error)
+            }
+            Database::Postgres(pool) => {
+                let mut client = pool.get().await?;
+                let client: &mut tokio_postgres::Client = &mut client;
+                let tx = client.transaction().await.map_err(pg_error)?;
+                tx.batch_execute(down).await.map_err(pg_error)?;
+                tx.execute(
+                    "DELETE FROM fastn_migration WHERE id = $1",
+                    &[&migration.id],
+                )
+                .await
+                .map_err(pg_error)?;
+                tx.commit().await.map_err(pg_error)
+            }
+        }
+    }
+}
+
+fn sqlite_open(pool: &fastn_ds::sqlite::Pool) -> rusqlite::Result<fastn_ds::sqlite::Connection> {
+    let conn = pool.get()?;
+    conn.execute_batch(CREATE_MIGRATION_TABLE)?;
+    Ok(conn)
+}
+
+fn sqlite_applied(pool: &fastn_ds::sqlite::Pool) -> rusqlite::Result<Vec<AppliedMigration>> {
+    let conn = sqlite_open(pool)?;
+    let mut stmt =
+        conn.prepare("SELECT id, checksum, applied_on FROM fastn_migration ORDER BY id")?;
+    let rows = stmt.query_map([], |r| {
+        Ok(AppliedMigration {
+            id: r.get(0)?,
+            checksum: r.get(1)?,
+            applied_on: r.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn sqlite_error(e: rusqlite::Error) -> fastn_core::Error {
+    fastn_core::Error::DatabaseError {
+        message: e.to_string(),
+    }
+}
+
+fn pg_error(e: tokio_postgres::Error) -> fastn_core::Error {
+    fastn_core::Error::DatabaseError {
+        message: e.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn files(names: &[&str]) -> std::collections::BTreeMap<String, String> {
+        names
+            .iter()
+            .map(|n| (n.to_string(), format!("-- {n}")))
+            .collect()
+    }
+
+    fn applied(migrations: &[super::Migration], ids: &[&str]) -> Vec<super::AppliedMigration> {
+        migrations
+            .iter()
+            .filter(|m| ids.contains(&m.id.as_str()))
+            .map(|m| super::AppliedMigration {
+                id: m.id.to_string(),
+                checksum: m.checksum.to_string(),
+                applied_on: "2024-01-01T00:00:00+00:00".to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_migrations() {
+        let migrations = super::parse_migrations(files(&[
+            "10-add-email.sql",
+            "2-create-users.sql",
+            "2-create-users.down.sql",
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            migrations
+                .iter()
+                .map(|m| (m.id.as_str(), m.number, m.down.is_some()))
+                .collect::<Vec<_>>(),
+            vec![("2-create-users", 2, true), ("10-add-email", 10, false)]
+        );
+
+        assert!(super::parse_migrations(files(&["create-users.sql"])).is_err());
+        assert!(super::parse_migrations(files(&["1-a.sql", "01-b.sql"])).is_err());
+        assert!(super::parse_migrations(files(&["1-a.down.sql"])).is_err());
+    }
+
+    #[test]
+    fn statuses() {
+        let migrations =
+            super::parse_migrations(files(&["1-a.sql", "2-b.sql", "3-c.sql"])).unwrap();
+        let mut applied = applied(&migrations, &["1-a", "2-b"]);
+        applied[1].checksum = "old".to_string();
+        applied.push(super::AppliedMigration {
+            id: "0-gone".to_string(),
+            checksum: "x".to_string(),
+            applied_on: "2024-01-01T00:00:00+00:00".to_string(),
+        });
+
+        assert_eq!(
+            super::statuses(&migrations, &applied),
+            vec![
+                ("1-a".to_string(), super::Status::Applied),
+                ("2-b".to_string(), super::Status::Modified),
+                ("3-c".to_string(), super::Status::Pending),
+                ("0-gone".to_string(), super::Status::Missing),
+            ]
+        );
+    }
+
+    #[test]
+    fn to_revert() {
+        let migrations = super::parse_migrations(files(&[
+            "1-a.sql",
+            "2-b.sql",
+            "2-b.down.sql",
+            "3-c.sql",
+            "3-c.down.sql",
+        ]))
+        .unwrap();
+        let applied = applied(&migrations, &["1-a", "2-b", "3-c"]);
+
+        assert_eq!(
+            super::to_revert(&migrations, &applied, "1")
+                .unwrap()
+                .iter()
+                .map(|m| m.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["3-c", "2-b"]
+        );
+        assert!(super::to_revert(&migrations, &applied, "2-b").is_ok());
+        // 1-a has no down migration
+        assert!(super::to_revert(&migrations, &applied, "0").is_err());
+    }
+}
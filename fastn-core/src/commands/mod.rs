@@ -1,7 +1,12 @@
 pub mod build;
 pub mod check;
+pub mod deps;
 pub mod fmt;
+pub mod i18n;
+pub mod lsp;
+pub mod migrate;
 pub mod query;
 pub mod serve;
 pub mod test;
 pub mod translation_status;
+pub mod wasmc;
@@ -1,9 +1,19 @@
+/// The stages of `fastn query`, `p1` and `ast` are parsed without executing processors, the
+/// others are interpreted with the processors executed against the mock request of `--url`,
+/// `--cookie` and `--header`, see `mock_request`.
+pub const STAGES: &[&str] = &["p1", "ast", "interpreter", "js-ast", "js"];
+
 pub async fn query(
     config: &fastn_core::Config,
     stage: &str,
     path: Option<&str>,
     with_null: bool,
+    request: &fastn_core::http::Request,
 ) -> fastn_core::Result<()> {
+    if !STAGES.contains(&stage) {
+        return unknown_stage(stage);
+    }
+
     let documents = std::collections::BTreeMap::from_iter(
         config
             .get_files(&config.package)
@@ -19,7 +29,7 @@ pub async fn query(
             },
         )?;
 
-        let value = get_ftd_json(file, stage)?;
+        let value = get_interpreted_json(config, file, stage, request).await?;
         println!(
             "{}",
             if with_null {
@@ -34,7 +44,7 @@ pub async fn query(
     let mut values: serde_json::Map<String, serde_json::Value> = serde_json::Map::new();
     for file in documents.values() {
         if file.is_ftd() {
-            let value = get_ftd_json(file, stage)?;
+            let value = get_interpreted_json(config, file, stage, request).await?;
             values.insert(file.get_id().to_string(), value);
         }
     }
@@ -68,8 +78,100 @@ pub(crate) fn get_ftd_json(
     match stage {
         "p1" => get_p1_json(document),
         "ast" => get_ast_json(document),
-        _ => unimplemented!(),
+        _ => unknown_stage(stage),
+    }
+}
+
+/// `get_ftd_json`, and the `interpreter`, `js-ast` and `js` stages, which need a request to
+/// execute the processors of the document against
+async fn get_interpreted_json(
+    config: &fastn_core::Config,
+    file: &fastn_core::File,
+    stage: &str,
+    request: &fastn_core::http::Request,
+) -> fastn_core::Result<serde_json::Value> {
+    let document = match (stage, file) {
+        ("interpreter" | "js-ast" | "js", fastn_core::File::Ftd(document)) => document,
+        _ => return get_ftd_json(file, stage),
+    };
+
+    let mut req_config = fastn_core::RequestConfig::new(config, request, document.id.as_str(), "/");
+    req_config.current_document = Some(document.id.to_string());
+    req_config.config.package.auto_import_language(
+        request.cookie(fastn_core::package::lang::LANGUAGE_COOKIE),
+        None,
+    )?;
+    let interpreted =
+        fastn_core::package::package_doc::interpret_ftd_2023(&mut req_config, document, "/", false)
+            .await?;
+
+    if stage == "interpreter" {
+        return Ok(serde_json::to_value(interpreted)?);
+    }
+    let js_ast_data = ftd::js::document_into_js_ast(interpreted);
+    if stage == "js-ast" {
+        return Ok(serde_json::to_value(js_ast_data.asts)?);
+    }
+    Ok(serde_json::Value::String(fastn_js::to_js(
+        js_ast_data.asts.as_slice(),
+        config.package.name.as_str(),
+    )))
+}
+
+fn unknown_stage<T>(stage: &str) -> fastn_core::Result<T> {
+    fastn_core::usage_error(format!(
+        "Unknown stage `{stage}`. Help use one of: {}",
+        STAGES.join(", ")
+    ))
+}
+
+/// the request the processors of the `interpreter`, `js-ast` and `js` stages see: `url` is the
+/// path with the query string, like `/blog/?page=2`, `cookies` are `name=value` and `headers`
+/// are `name: value`
+pub fn mock_request(
+    url: &str,
+    cookies: &[String],
+    headers: &[String],
+) -> fastn_core::Result<fastn_core::http::Request> {
+    if url.parse::<actix_web::http::Uri>().is_err() {
+        return fastn_core::usage_error(format!("Invalid url `{url}`"));
     }
+    let mut request = actix_web::test::TestRequest::with_uri(url);
+
+    for header in headers {
+        let (name, value) = match header.split_once(':') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => {
+                return fastn_core::usage_error(format!(
+                    "Invalid header `{header}`, expected `name: value`"
+                ))
+            }
+        };
+        if actix_web::http::header::HeaderName::from_bytes(name.as_bytes()).is_err()
+            || actix_web::http::header::HeaderValue::from_str(value).is_err()
+        {
+            return fastn_core::usage_error(format!("Invalid header `{header}`"));
+        }
+        request = request.append_header((name, value));
+    }
+
+    if !cookies.is_empty() {
+        if let Some(cookie) = cookies.iter().find(|c| !c.contains('=')) {
+            return fastn_core::usage_error(format!(
+                "Invalid cookie `{cookie}`, expected `name=value`"
+            ));
+        }
+        let cookie = cookies.join("; ");
+        if actix_web::http::header::HeaderValue::from_str(cookie.as_str()).is_err() {
+            return fastn_core::usage_error(format!("Invalid cookies `{cookie}`"));
+        }
+        request = request.append_header((actix_web::http::header::COOKIE, cookie));
+    }
+
+    Ok(fastn_core::http::Request::from_actix(
+        request.to_http_request(),
+        actix_web::web::Bytes::new(),
+    ))
 }
 
 fn get_p1_json(document: &fastn_core::Document) -> fastn_core::Result<serde_json::Value> {
@@ -91,3 +193,30 @@ fn get_ast_json(document: &fastn_core::Document) -> fastn_core::Result<serde_jso
 
     Ok(value)
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn mock_request() {
+        let request = super::mock_request(
+            "/blog/?page=2",
+            &["fastn-lang=hi".to_string(), "sid=1".to_string()],
+            &["x-tenant: acme".to_string()],
+        )
+        .unwrap();
+        assert_eq!(request.path(), "/blog/");
+        assert_eq!(request.query_string(), "page=2");
+        assert_eq!(request.cookie("fastn-lang"), Some("hi".to_string()));
+        assert_eq!(request.cookie("sid"), Some("1".to_string()));
+        assert_eq!(
+            request
+                .headers()
+                .get("x-tenant")
+                .and_then(|v| v.to_str().ok()),
+            Some("acme")
+        );
+
+        assert!(super::mock_request("/", &["sid".to_string()], &[]).is_err());
+        assert!(super::mock_request("/", &[], &["x-tenant".to_string()]).is_err());
+    }
+}
@@ -19,11 +19,14 @@ async fn serve_file(
     path: &camino::Utf8Path,
     only_js: bool,
 ) -> fastn_core::http::Response {
-    if let Err(e) = config
-        .config
-        .package
-        .auto_import_language(config.request.cookie("fastn-lang"), None)
-    {
+    if let Err(e) = config.config.package.auto_import_language(
+        config.language.clone().or_else(|| {
+            config
+                .request
+                .cookie(fastn_core::package::lang::LANGUAGE_COOKIE)
+        }),
+        None,
+    ) {
         return if config.config.test_command_running {
             fastn_core::http::not_found_without_warning(format!(
                 "fastn-Error: path: {}, {:?}",
@@ -97,7 +100,12 @@ async fn serve_file(
     )
     .await
     {
-        Ok(r) => r.into(),
+        Ok(r) => {
+            if let Some(ref live_reload) = config.config.live_reload {
+                live_reload.record(path.as_str(), &config.dependencies_during_render);
+            }
+            r.into()
+        }
         Err(e) => {
             tracing::error!(
                 msg = "fastn-Error",
@@ -138,6 +146,20 @@ pub async fn serve(
     req: fastn_core::http::Request,
     only_js: bool,
 ) -> fastn_core::Result<fastn_core::http::Response> {
+    if let Some(ref live_reload) = config.live_reload {
+        if req.path() == fastn_core::live_reload::LIVE_RELOAD_PATH {
+            return Ok(live_reload.events(&req));
+        }
+    }
+
+    if let Some(purge_response) = fastn_core::processor_cache::purge_route(config, &req).await {
+        return purge_response;
+    }
+
+    if let Some(action_response) = handle_sql_action(config, &req).await {
+        return action_response;
+    }
+
     if let Some(endpoint_response) = handle_endpoints(config, &req).await {
         return endpoint_response;
     }
@@ -150,19 +172,85 @@ pub async fn serve(
         return default_response;
     }
 
-    let path: camino::Utf8PathBuf = req.path().replacen('/', "", 1).parse()?;
+    let (language, negotiated, url_path) = request_language(config.package.lang.as_ref(), &req);
+    let path: camino::Utf8PathBuf = url_path.replacen('/', "", 1).parse()?;
 
     if let Some(r) = handle_redirect(config, &path) {
         return Ok(r);
     }
 
-    if fastn_core::utils::is_static_path(req.path()) {
-        return handle_static_route(req.path(), config.package.name.as_str(), &config.ds).await;
+    // `/hi/robots.txt` and `/hi/assets/logo.png` are the same files as without the prefix
+    if let Some(r) = handle_seo_route(config, url_path.as_str()).await {
+        return r;
     }
 
-    let req_config = fastn_core::RequestConfig::new(config, &req, "", "/");
+    if fastn_core::utils::is_static_path(url_path.as_str()) {
+        return handle_static_route(url_path.as_str(), config.package.name.as_str(), &config.ds)
+            .await;
+    }
 
-    serve_helper(req_config, only_js, path).await
+    let mut req_config = fastn_core::RequestConfig::new(config, &req, "", "/");
+    req_config.language = language;
+
+    let mut resp = serve_helper(req_config, only_js, path).await?;
+    if negotiated {
+        // the same url is in a different language for a different `Accept-Language`
+        resp.headers_mut().append(
+            actix_web::http::header::VARY,
+            actix_web::http::header::HeaderValue::from_static("Accept-Language, Cookie"),
+        );
+    }
+    Ok(resp)
+}
+
+/// the language of the request and the url path without the language prefix, see
+/// `fastn_core::package::Lang`. The bool is true if the language was negotiated, as the url has
+/// no language prefix.
+fn request_language(
+    lang: Option<&fastn_core::package::Lang>,
+    req: &fastn_core::http::Request,
+) -> (Option<String>, bool, String) {
+    let lang = match lang {
+        Some(lang) => lang,
+        None => return (None, false, req.path().to_string()),
+    };
+
+    if let Some((language, rest)) = lang.split_prefix(req.path()) {
+        return (Some(language), false, rest);
+    }
+
+    let language = lang.negotiate(
+        req.cookie(fastn_core::package::lang::LANGUAGE_COOKIE)
+            .as_deref(),
+        req.headers()
+            .get("accept-language")
+            .and_then(|v| v.to_str().ok()),
+    );
+    (Some(language), true, req.path().to_string())
+}
+
+/// `/sitemap.xml`, `/robots.txt` and `/feed.xml`, if the package does not have these files
+async fn handle_seo_route(
+    config: &fastn_core::Config,
+    path: &str,
+) -> Option<fastn_core::Result<fastn_core::http::Response>> {
+    let name = path.strip_prefix('/')?;
+    if !fastn_core::sitemap::seo::generated_files(&config.package)
+        .iter()
+        .any(|f| *f == name)
+        || config.ds.exists(&config.ds.root().join(name)).await
+    {
+        return None;
+    }
+
+    match config.seo.get(config, name).await {
+        Ok(Some((content_type, content))) => Some(Ok(fastn_core::http::ok_with_content_type(
+            content.into_bytes(),
+            content_type,
+        ))),
+        Ok(None) => None,
+        Err(e) => Some(Err(e)),
+    }
 }
 
 #[tracing::instrument(skip_all)]
@@ -171,7 +259,7 @@ pub async fn serve_helper(
     only_js: bool,
     path: camino::Utf8PathBuf,
 ) -> fastn_core::Result<fastn_core::http::Response> {
-    let mut resp = if req_config.request.path() == "/" {
+    let mut resp = if path.as_str().is_empty() {
         serve_file(&mut req_config, &path.join("/"), only_js).await
     } else {
         // url is present in config or not
@@ -336,6 +424,14 @@ async fn handle_static_route(
             return favicon(ds).await;
         }
 
+        // `.fastn`, `.packages` internals, `.env` and the like, only `.well-known` is public
+        if path
+            .split('/')
+            .any(|s| s.starts_with('.') && s != ".well-known")
+        {
+            return Err(fastn_ds::ReadError::NotFound(path.to_string()));
+        }
+
         // the path can start with slash or -/. If later, it is a static file from our dependencies, so
         // we have to look for them inside .packages.
         let path = match path.strip_prefix("/-/") {
@@ -407,12 +503,32 @@ async fn handle_static_route(
         ds: &fastn_ds::DocumentStore,
         path: &str,
     ) -> Result<fastn_core::http::Response, fastn_ds::ReadError> {
-        ds.read_content(&fastn_ds::Path::new(path)).await.map(|r| {
+        ds.read_content(&ds.root().join(path)).await.map(|r| {
             fastn_core::http::ok_with_content_type(r, guess_mime_type(path.to_string().as_str()))
         })
     }
 }
 
+/// form submissions to the urls mapped to a `fastn.sql-action`
+async fn handle_sql_action(
+    config: &fastn_core::Config,
+    req: &fastn_core::http::Request,
+) -> Option<fastn_core::Result<fastn_core::http::Response>> {
+    let name = config
+        .package
+        .sql_action_urls
+        .iter()
+        .find(|(url, _)| url.trim_end_matches('/') == req.path().trim_end_matches('/'))
+        .map(|(_, name)| name)?;
+    let action = config
+        .package
+        .sql_actions
+        .iter()
+        .find(|a| &a.name == name)?;
+
+    Some(action.handle(config, req).await)
+}
+
 async fn handle_endpoints(
     config: &fastn_core::Config,
     req: &fastn_core::http::Request,
@@ -437,7 +553,7 @@ async fn handle_endpoints(
     );
 
     if url.starts_with("wasm+proxy://") {
-        return match config.ds.handle_wasm(url, req).await {
+        return match config.ds.handle_wasm(url, req, &endpoint.options).await {
             Ok(r) => Some(Ok(fastn_ds::wasm::to_response(r))),
             Err(e) => return Some(Err(e.into())),
         };
@@ -517,6 +633,8 @@ pub async fn listen(
     use colored::Colorize;
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
+    fastn_core::commands::migrate::check_pending(&config).await?;
+
     let tcp_listener = match fastn_core::http::get_available_port(port, bind_address) {
         Some(listener) => listener,
         None => {
@@ -537,6 +655,12 @@ You can try without providing port, it will automatically pick unused port."#,
         }
     };
 
+    if config.live_reload.is_some() {
+        tokio::spawn(fastn_core::live_reload::watch(std::sync::Arc::clone(
+            &config,
+        )));
+    }
+
     let app = move || {
         actix_web::App::new()
             .app_data(actix_web::web::Data::new(std::sync::Arc::clone(&config)))
@@ -564,3 +688,103 @@ You can try without providing port, it will automatically pick unused port."#,
         .await?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    #[tokio::test]
+    async fn language_prefix_of_static_files() {
+        let lang = fastn_core::package::Lang {
+            default_lang: "en".to_string(),
+            available_languages: [("en", "en.ftd"), ("hi", "hi.ftd")]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        };
+        let request = |path: &str| {
+            fastn_core::http::Request::from_actix(
+                actix_web::test::TestRequest::get()
+                    .uri(path)
+                    .to_http_request(),
+                actix_web::web::Bytes::new(),
+            )
+        };
+
+        let (language, negotiated, url_path) =
+            super::request_language(Some(&lang), &request("/hi/assets/logo.png"));
+        assert_eq!(language.as_deref(), Some("hi"));
+        assert!(!negotiated);
+        assert_eq!(url_path, "/assets/logo.png");
+        assert!(fastn_core::utils::is_static_path(url_path.as_str()));
+
+        let (_, _, url_path) = super::request_language(Some(&lang), &request("/hi/robots.txt"));
+        assert_eq!(url_path, "/robots.txt");
+
+        let (language, negotiated, url_path) =
+            super::request_language(Some(&lang), &request("/assets/logo.png"));
+        assert_eq!(language.as_deref(), Some("en"));
+        assert!(negotiated);
+        assert_eq!(url_path, "/assets/logo.png");
+
+        let (language, _, url_path) =
+            super::request_language(None, &request("/hi/assets/logo.png"));
+        assert_eq!(language, None);
+        assert_eq!(url_path, "/hi/assets/logo.png");
+
+        // the file is served under the language prefix
+        let root = std::env::temp_dir().join(format!("fastn-serve-{:016x}", rand::random::<u64>()));
+        std::fs::create_dir_all(root.join("assets")).unwrap();
+        std::fs::write(root.join("assets/logo.png"), "png").unwrap();
+        let ds = fastn_ds::DocumentStore::new(
+            camino::Utf8PathBuf::from_path_buf(root.clone()).unwrap(),
+            actix_web::web::Data::new(scc::HashMap::new()),
+        );
+        let (_, _, url_path) =
+            super::request_language(Some(&lang), &request("/hi/assets/logo.png"));
+        assert_eq!(
+            super::handle_static_route(url_path.as_str(), "example.com", &ds)
+                .await
+                .unwrap()
+                .status(),
+            200
+        );
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn static_route_does_not_serve_dot_directories() {
+        let root = std::env::temp_dir().join(format!("fastn-serve-{:016x}", rand::random::<u64>()));
+        for file in [
+            "a.json",
+            ".fastn/tejar/key.json",
+            ".tejar/key.json",
+            ".env.json",
+            ".well-known/security.txt",
+        ] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "{}").unwrap();
+        }
+        let ds = fastn_ds::DocumentStore::new(
+            camino::Utf8PathBuf::from_path_buf(root.clone()).unwrap(),
+            actix_web::web::Data::new(scc::HashMap::new()),
+        );
+        let status = |path: &'static str| {
+            let ds = &ds;
+            async move {
+                super::handle_static_route(path, "example.com", ds)
+                    .await
+                    .unwrap()
+                    .status()
+            }
+        };
+
+        assert_eq!(status("/a.json").await, 200);
+        assert_eq!(status("/.well-known/security.txt").await, 200);
+        assert_eq!(status("/.fastn/tejar/key.json").await, 404);
+        assert_eq!(status("/.tejar/key.json").await, 404);
+        assert_eq!(status("/.env.json").await, 404);
+        assert_eq!(status("/a/../.tejar/key.json").await, 404);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -2,6 +2,10 @@ pub(crate) const TEST_FOLDER: &str = "_tests";
 pub(crate) const FIXTURE_FOLDER: &str = "fixtures";
 pub(crate) const TEST_FILE_EXTENSION: &str = ".test.ftd";
 pub(crate) const FIXTURE_FILE_EXTENSION: &str = ".test.ftd";
+/// recorded outgoing requests, `_tests/http-fixtures/<test file>.json`
+pub(crate) const HTTP_FIXTURE_FOLDER: &str = "http-fixtures";
+/// `fastn.snapshot` outputs, `_tests/snapshots/<test file>/<id>.html`
+pub(crate) const SNAPSHOT_FOLDER: &str = "snapshots";
 
 // mandatory test parameters
 pub(crate) const TEST_TITLE_HEADER: &str = "title";
@@ -18,6 +22,9 @@ pub(crate) const TEST_CONTENT_HEADER: &str = "test";
 pub(crate) const HTTP_REDIRECT_HEADER: &str = "http-redirect";
 pub(crate) const HTTP_STATUS_HEADER: &str = "http-status";
 pub(crate) const HTTP_LOCATION_HEADER: &str = "http-location";
+pub(crate) const SNAPSHOT_FORMAT_HEADER: &str = "format";
+pub(crate) const SQL_FILE_HEADER: &str = "file";
+pub(crate) const SQL_QUERY_HEADER: &str = "query";
 
 macro_rules! log_variable {
     // When verbose is true, debug variables
@@ -41,6 +48,10 @@ macro_rules! log_message {
 pub struct TestParameters {
     pub script: bool,
     pub verbose: bool,
+    /// only the tests whose title or id contains this are run
+    pub filter: Option<String>,
+    /// `fastn.snapshot` writes the snapshots instead of comparing with them
+    pub update_snapshots: bool,
     pub instruction_number: i64,
     pub test_results: ftd::Map<String>,
     pub test_data: ftd::Map<String>,
@@ -51,6 +62,8 @@ impl TestParameters {
         TestParameters {
             script,
             verbose,
+            filter: None,
+            update_snapshots: false,
             instruction_number: 0,
             test_results: Default::default(),
             test_data: Default::default(),
@@ -58,34 +71,341 @@ impl TestParameters {
     }
 }
 
-pub async fn test(
-    config: &fastn_core::Config,
-    only_id: Option<&str>,
-    _base_url: &str,
-    headless: bool,
-    script: bool,
-    verbose: bool,
-) -> fastn_core::Result<()> {
+impl TestParameters {
+    pub fn with_filter(mut self, filter: Option<String>) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_update_snapshots(mut self, update_snapshots: bool) -> Self {
+        self.update_snapshots = update_snapshots;
+        self
+    }
+
+    fn matches(&self, title: &str, id: Option<&str>) -> bool {
+        match self.filter {
+            Some(ref filter) => {
+                title.contains(filter.as_str()) || id.is_some_and(|id| id.contains(filter.as_str()))
+            }
+            None => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TestOptions {
+    /// test files to run, all if empty. A file matches if its id contains the value, or if it
+    /// matches the value as a glob, where `*` matches any characters including `/`
+    pub files: Vec<String>,
+    /// only the tests whose title or id contains this are run, fixtures always run
+    pub filter: Option<String>,
+    pub headless: bool,
+    pub script: bool,
+    pub verbose: bool,
+    /// test files run concurrently, every file has its own cookies
+    pub jobs: usize,
+    pub http_fixtures: fastn_ds::http_fixtures::Mode,
+    pub update_snapshots: bool,
+    pub junit_report: Option<String>,
+    pub json_report: Option<String>,
+}
+
+impl TestOptions {
+    fn matches_file(&self, id: &str) -> bool {
+        if self.files.is_empty() {
+            return true;
+        }
+
+        let short_id = id.trim_start_matches(TEST_FOLDER).trim_start_matches('/');
+        self.files.iter().any(|file| {
+            let file = file.trim_start_matches("./");
+            if file.contains(['*', '?']) {
+                glob_match(file, id) || glob_match(file, short_id)
+            } else {
+                id.contains(file)
+            }
+        })
+    }
+}
+
+/// `*` matches any characters, including `/`, and `?` any one character
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // the last `*` and the position in `text` it is matched up to
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TestStatus {
+    Passed,
+    Failed,
+    /// the test could not run, the file failed to parse or the request failed
+    Error,
+    /// filtered out, or an earlier test of the file failed
+    Skipped,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct TestResult {
+    pub file: String,
+    pub title: String,
+    pub id: Option<String>,
+    pub status: TestStatus,
+    pub duration_ms: u64,
+    pub message: Option<String>,
+}
+
+impl TestResult {
+    fn new(file: &str, title: String, id: Option<String>, status: TestStatus) -> TestResult {
+        TestResult {
+            file: file.to_string(),
+            title,
+            id,
+            status,
+            duration_ms: 0,
+            message: None,
+        }
+    }
+}
+
+pub async fn test(config: &fastn_core::Config, options: TestOptions) -> fastn_core::Result<()> {
     use colored::Colorize;
+    use futures::StreamExt;
 
-    if !headless {
+    if !options.headless {
         return fastn_core::usage_error(
             "Currently headless mode is only supported, use: --headless flag".to_string(),
         );
     }
-    let ftd_documents = config.get_test_files().await?;
+    let ftd_documents: Vec<_> = config
+        .get_test_files()
+        .await?
+        .into_iter()
+        .filter(|d| options.matches_file(d.id.as_str()))
+        .collect();
+
+    // recorded requests are not tied to a test file, and the transaction isolating a postgres
+    // database is in the pool shared by all test files, so these run one at a time
+    let postgres = matches!(
+        config.ds.default_db_url().await?,
+        fastn_ds::db::DbUrl::Postgres(_)
+    );
+    let jobs = match options.http_fixtures {
+        fastn_ds::http_fixtures::Mode::Live if !postgres => options.jobs.max(1),
+        _ => 1,
+    };
+
+    let results: Vec<TestResult> = futures::stream::iter(ftd_documents)
+        .map(|document| run_test_file(document, config, &options))
+        .buffered(jobs)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<fastn_core::Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    if let Some(ref path) = options.junit_report {
+        config
+            .ds
+            .write_content(
+                &fastn_ds::Path::new(path),
+                junit_report(&results).as_bytes(),
+            )
+            .await?;
+    }
+    if let Some(ref path) = options.json_report {
+        config
+            .ds
+            .write_content(
+                &fastn_ds::Path::new(path),
+                &serde_json::to_vec_pretty(&serde_json::json!({ "tests": results }))?,
+            )
+            .await?;
+    }
+
+    let count = |status: TestStatus| results.iter().filter(|r| r.status == status).count();
+    let (failed, errors) = (count(TestStatus::Failed), count(TestStatus::Error));
+    println!(
+        "{} passed, {} failed, {} errors, {} skipped",
+        count(TestStatus::Passed).to_string().green(),
+        failed.to_string().red(),
+        errors.to_string().red(),
+        count(TestStatus::Skipped)
+    );
+
+    if failed + errors > 0 {
+        return fastn_core::assert_error(format!("{} tests failed", failed + errors));
+    }
+    Ok(())
+}
+
+async fn run_test_file(
+    document: fastn_core::Document,
+    config: &fastn_core::Config,
+    options: &TestOptions,
+) -> fastn_core::Result<Vec<TestResult>> {
+    use colored::Colorize;
+
+    let mut test_parameters =
+        TestParameters::new(options.script, options.verbose).with_filter(options.filter.clone());
+    let id = document.id.to_string();
+    println!("Running test file: {}", id.magenta());
+
+    // nothing a test file writes to the database is seen by the other test files
+    let test_database = fastn_ds::db::TestDatabase::start(&config.ds).await?;
+    let mut config = config.clone();
+    config.ds = config.ds.clone().with_db_url(test_database.url());
+    // cached processor results may come from the database of another test file
+    config.processor_cache = Default::default();
+
+    let fixture_path = config.get_http_fixture_path(id.as_str());
+    start_http_fixtures(&config, options.http_fixtures, &fixture_path).await?;
+    let result = read_ftd_test_file(document, &config, &mut test_parameters).await;
+    let finished = finish_http_fixtures(&config, options.http_fixtures, &fixture_path).await;
+    test_database.finish(&config.ds).await?;
+    finished?;
+
+    Ok(match result {
+        Ok(results) => results,
+        Err(e) => {
+            println!("{}", format!("Test file failed: {e}").red());
+            let mut result = TestResult::new(&id, id.clone(), None, TestStatus::Error);
+            result.message = Some(e.to_string());
+            vec![result]
+        }
+    })
+}
+
+/// one `<testsuite>` per test file
+fn junit_report(results: &[TestResult]) -> String {
+    use itertools::Itertools;
 
-    for document in ftd_documents {
-        if let Some(id) = only_id {
-            if !document.id.contains(id) {
-                continue;
+    let count = |results: &[&TestResult], status: TestStatus| {
+        results.iter().filter(|r| r.status == status).count()
+    };
+    let seconds = |ms: u64| format!("{:.3}", ms as f64 / 1000.0);
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let all: Vec<&TestResult> = results.iter().collect();
+    xml.push_str(&format!(
+        "<testsuites tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{}\">\n",
+        all.len(),
+        count(&all, TestStatus::Failed),
+        count(&all, TestStatus::Error),
+        count(&all, TestStatus::Skipped),
+        seconds(results.iter().map(|r| r.duration_ms).sum()),
+    ));
+
+    for (file, cases) in &results.iter().group_by(|r| r.file.as_str()) {
+        let cases: Vec<&TestResult> = cases.collect();
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{}\">\n",
+            xml_escape(file),
+            cases.len(),
+            count(&cases, TestStatus::Failed),
+            count(&cases, TestStatus::Error),
+            count(&cases, TestStatus::Skipped),
+            seconds(cases.iter().map(|r| r.duration_ms).sum()),
+        ));
+        for case in cases {
+            xml.push_str(&format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{}\"",
+                xml_escape(case.title.as_str()),
+                xml_escape(file),
+                seconds(case.duration_ms),
+            ));
+            let message = xml_escape(case.message.as_deref().unwrap_or_default());
+            match case.status {
+                TestStatus::Passed => xml.push_str("/>\n"),
+                TestStatus::Failed => xml.push_str(&format!(
+                    ">\n      <failure message=\"{message}\"/>\n    </testcase>\n"
+                )),
+                TestStatus::Error => xml.push_str(&format!(
+                    ">\n      <error message=\"{message}\"/>\n    </testcase>\n"
+                )),
+                TestStatus::Skipped => xml.push_str(">\n      <skipped/>\n    </testcase>\n"),
             }
         }
-        let mut test_parameters = TestParameters::new(script, verbose);
-        println!("Running test file: {}", document.id.magenta());
-        read_ftd_test_file(document, config, &mut test_parameters).await?;
+        xml.push_str("  </testsuite>\n");
     }
 
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\n', "&#10;")
+}
+
+async fn start_http_fixtures(
+    config: &fastn_core::Config,
+    mode: fastn_ds::http_fixtures::Mode,
+    path: &fastn_ds::Path,
+) -> fastn_core::Result<()> {
+    let fixtures = match mode {
+        fastn_ds::http_fixtures::Mode::Replay if config.ds.exists(path).await => {
+            serde_json::from_slice(&config.ds.read_content(path).await?)?
+        }
+        _ => vec![],
+    };
+    config.ds.http_fixtures.start(mode, fixtures);
+    Ok(())
+}
+
+/// saves the recorded requests, or fails if a request had no recorded response
+async fn finish_http_fixtures(
+    config: &fastn_core::Config,
+    mode: fastn_ds::http_fixtures::Mode,
+    path: &fastn_ds::Path,
+) -> fastn_core::Result<()> {
+    let (fixtures, unmatched) = config.ds.http_fixtures.finish();
+    match mode {
+        fastn_ds::http_fixtures::Mode::Record => {
+            config
+                .ds
+                .write_content(path, &serde_json::to_vec_pretty(&fixtures)?)
+                .await?;
+            println!("Recorded {} requests in {path}", fixtures.len());
+        }
+        fastn_ds::http_fixtures::Mode::Replay if !unmatched.is_empty() => {
+            return fastn_core::assert_error(format!(
+                "requests without a recorded response in {path}: {}",
+                unmatched
+                    .iter()
+                    .map(|u| format!("{} {}", u.method, u.url))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        _ => {}
+    }
     Ok(())
 }
 
@@ -161,7 +481,11 @@ impl fastn_core::Config {
         let path = self
             .get_root_for_package(&self.package)
             .join(fastn_core::commands::test::TEST_FOLDER);
-        let ignored_directories = ["fixtures".to_string()];
+        let ignored_directories = [
+            "fixtures".to_string(),
+            fastn_core::commands::test::HTTP_FIXTURE_FOLDER.to_string(),
+            fastn_core::commands::test::SNAPSHOT_FOLDER.to_string(),
+        ];
         Ok(self.ds.get_all_file_path(&path, &ignored_directories).await)
     }
 
@@ -169,6 +493,37 @@ impl fastn_core::Config {
         self.get_root_for_package(&self.package)
             .join(fastn_core::commands::test::TEST_FOLDER)
     }
+
+    /// `_tests/snapshots/foo/<name>.<extension>` for a snapshot of the test document
+    /// `<package>/_tests/foo.test`
+    pub(crate) fn get_snapshot_path(
+        &self,
+        doc_name: &str,
+        name: &str,
+        extension: &str,
+    ) -> fastn_ds::Path {
+        let test_name = doc_name
+            .trim_end_matches('/')
+            .rsplit_once('/')
+            .map_or(doc_name, |(_, file_name)| file_name)
+            .trim_end_matches(".test");
+        self.get_test_directory_path()
+            .join(fastn_core::commands::test::SNAPSHOT_FOLDER)
+            .join(test_name)
+            .join(format!("{name}.{extension}"))
+    }
+
+    /// `_tests/http-fixtures/foo.json` for the test file `_tests/foo.test.ftd`
+    pub(crate) fn get_http_fixture_path(&self, test_id: &str) -> fastn_ds::Path {
+        let name = test_id
+            .trim_start_matches('/')
+            .trim_start_matches(fastn_core::commands::test::TEST_FOLDER)
+            .trim_start_matches('/')
+            .trim_end_matches(fastn_core::commands::test::TEST_FILE_EXTENSION);
+        self.get_test_directory_path()
+            .join(fastn_core::commands::test::HTTP_FIXTURE_FOLDER)
+            .join(format!("{name}.json"))
+    }
 }
 
 #[async_recursion::async_recursion(? Send)]
@@ -196,14 +551,18 @@ async fn read_only_instructions(
         &main_ftd_doc.aliases,
         &main_ftd_doc.data,
     );
-    get_all_instructions(&main_ftd_doc.tree, &doc, config).await
+    Ok(get_all_instructions(&main_ftd_doc.tree, &doc, config)
+        .await?
+        .0)
 }
 
 async fn read_ftd_test_file(
     ftd_document: fastn_core::Document,
     config: &fastn_core::Config,
     test_parameters: &mut TestParameters,
-) -> fastn_core::Result<()> {
+) -> fastn_core::Result<Vec<TestResult>> {
+    use colored::Colorize;
+
     let req = fastn_core::http::Request::default();
     let mut saved_cookies: std::collections::HashMap<String, String> =
         std::collections::HashMap::new();
@@ -225,33 +584,101 @@ async fn read_ftd_test_file(
     bag.extend(ftd::interpreter::default::default_test_bag());
 
     let doc = ftd::interpreter::TDoc::new(&main_ftd_doc.name, &main_ftd_doc.aliases, &bag);
-    let all_instructions = get_all_instructions(&main_ftd_doc.tree, &doc, config).await?;
+    let (all_instructions, fixture_count) =
+        get_all_instructions(&main_ftd_doc.tree, &doc, config).await?;
+    let titles: Vec<(String, Option<String>)> = all_instructions
+        .iter()
+        .map(|instruction| instruction_title(instruction, &doc))
+        .collect();
+
+    // a file without a test matching the filter does not run its fixtures either
+    if test_parameters.filter.is_some()
+        && !titles[fixture_count..]
+            .iter()
+            .any(|(title, id)| test_parameters.matches(title, id.as_deref()))
+    {
+        return Ok(vec![]);
+    }
+
+    let mut results = vec![];
+    let mut failed = false;
     let mut instruction_number = 1;
-    for instruction in all_instructions.iter() {
+    for (index, (instruction, (title, id))) in all_instructions.iter().zip(titles).enumerate() {
+        let mut result = TestResult::new(ftd_document.id.as_str(), title, id, TestStatus::Skipped);
+        // seeding the database runs like the fixtures do
+        if failed
+            || (index >= fixture_count
+                && instruction.name != "fastn#sql"
+                && !test_parameters.matches(result.title.as_str(), result.id.as_deref()))
+        {
+            results.push(result);
+            continue;
+        }
+
         test_parameters.instruction_number = instruction_number;
-        if !execute_instruction(
+        let start = std::time::Instant::now();
+        let status = execute_instruction(
             instruction,
             &doc,
             config,
             &mut saved_cookies,
             test_parameters,
         )
-        .await?
-        {
-            break;
-        }
+        .await;
+        result.duration_ms = start.elapsed().as_millis() as u64;
+        result.status = match status {
+            Ok(true) => TestStatus::Passed,
+            Ok(false) => {
+                result.message = Some("assertion failed".to_string());
+                TestStatus::Failed
+            }
+            Err(e) => {
+                println!("{}", format!("Test Failed: {e}").red());
+                result.message = Some(e.to_string());
+                TestStatus::Error
+            }
+        };
+        // the tests of a file depend on each other, so the rest of the file is skipped
+        failed = result.status != TestStatus::Passed;
+        results.push(result);
         instruction_number += 1;
     }
-    Ok(())
+    Ok(results)
+}
+
+/// the title and the id of a `fastn.get`, `fastn.post`, `fastn.redirect`, `fastn.snapshot` or
+/// `fastn.sql` instruction
+fn instruction_title(
+    instruction: &ftd::interpreter::Component,
+    doc: &ftd::interpreter::TDoc<'_>,
+) -> (String, Option<String>) {
+    let property_values = match instruction.get_interpreter_property_value_of_all_arguments(doc) {
+        Ok(v) => v,
+        Err(_) => return (instruction.name.to_string(), None),
+    };
+    let title = get_optional_value_string(TEST_TITLE_HEADER, &property_values, doc)
+        .ok()
+        .flatten()
+        .or_else(|| {
+            get_optional_value_string(HTTP_REDIRECT_HEADER, &property_values, doc)
+                .ok()
+                .flatten()
+        })
+        .unwrap_or_else(|| instruction.name.to_string());
+    let id = get_optional_value_string(TEST_ID_HEADER, &property_values, doc)
+        .ok()
+        .flatten();
+    (title, id)
 }
 
 // This will give all overall set of instructions for a test file
 // including instructions from fixture and other test instructions
+// and how many of them are fixture instructions, they come first
 async fn get_all_instructions(
     instructions: &[ftd::interpreter::Component],
     doc: &ftd::interpreter::TDoc<'_>,
     config: &fastn_core::Config,
-) -> fastn_core::Result<Vec<ftd::interpreter::Component>> {
+) -> fastn_core::Result<(Vec<ftd::interpreter::Component>, usize)> {
     let mut fixture_and_test_instructions = vec![];
     let mut rest_instructions = vec![];
     let mut included_fixtures: std::collections::HashSet<String> = std::collections::HashSet::new();
@@ -274,7 +701,7 @@ async fn get_all_instructions(
                         .await?,
                 );
             }
-            "fastn#get" | "fastn#post" | "fastn#redirect" => {
+            "fastn#get" | "fastn#post" | "fastn#redirect" | "fastn#snapshot" | "fastn#sql" => {
                 if !found_test_component {
                     return fastn_core::usage_error(format!(
                         "fastn.test doesn't exist for this test, doc: {} \
@@ -294,10 +721,11 @@ async fn get_all_instructions(
     }
     // instructions from fastn.test (fixture and fastn.test children instructions)
     let mut all_instructions = fixture_and_test_instructions;
+    let fixture_count = all_instructions.len();
     // Rest instructions if fastn.test not used at all
     all_instructions.extend(rest_instructions);
 
-    Ok(all_instructions)
+    Ok((all_instructions, fixture_count))
 }
 
 async fn execute_instruction(
@@ -318,6 +746,11 @@ async fn execute_instruction(
             execute_redirect_instruction(instruction, doc, config, saved_cookies, test_parameters)
                 .await
         }
+        "fastn#snapshot" => {
+            execute_snapshot_instruction(instruction, doc, config, saved_cookies, test_parameters)
+                .await
+        }
+        "fastn#sql" => execute_sql_instruction(instruction, doc, config, test_parameters).await,
         t => fastn_core::usage_error(format!(
             "Unknown instruction {}, line number: {}",
             t, instruction.line_number
@@ -1090,3 +1523,329 @@ async fn execute_redirect_instruction(
     )
     .await
 }
+
+/// ```ftd
+/// -- fastn.sql: people
+/// file: people.sql
+///
+/// INSERT INTO people (name) VALUES ('Alice');
+/// ```
+///
+/// seeds the database of the test file with `_tests/fixtures/<file>`, then with the body. Both
+/// are optional and may have many statements.
+async fn execute_sql_instruction(
+    instruction: &ftd::interpreter::Component,
+    doc: &ftd::interpreter::TDoc<'_>,
+    config: &fastn_core::Config,
+    test_parameters: &TestParameters,
+) -> fastn_core::Result<bool> {
+    use colored::Colorize;
+
+    let property_values = instruction.get_interpreter_property_value_of_all_arguments(doc)?;
+    let title = get_optional_value_string(TEST_TITLE_HEADER, &property_values, doc)?;
+    let file = get_optional_value_string(SQL_FILE_HEADER, &property_values, doc)?;
+    let query = get_optional_value_string(SQL_QUERY_HEADER, &property_values, doc)?;
+    if file.is_none() && query.is_none() {
+        return fastn_core::usage_error(format!(
+            "fastn.sql needs a file or a query, doc: {} line_number: {}",
+            doc.name, instruction.line_number
+        ));
+    }
+
+    println!(
+        "SQL: {}",
+        title
+            .as_deref()
+            .or(file.as_deref())
+            .unwrap_or("query")
+            .yellow()
+    );
+    log_message!(test_parameters.verbose, "Test type: SQL");
+
+    let pool = config.ds.default_db_pool(false).await?;
+    if let Some(file) = file {
+        let path = config
+            .get_test_directory_path()
+            .join(FIXTURE_FOLDER)
+            .join(file.as_str());
+        log_variable!(test_parameters.verbose, &path);
+        let sql = config.ds.read_to_string(&path).await?;
+        pool.execute_batch(sql.as_str()).await?;
+    }
+    if let Some(query) = query {
+        log_variable!(test_parameters.verbose, &query);
+        pool.execute_batch(query.as_str()).await?;
+    }
+
+    println!("{}", "Seeded".green());
+    Ok(true)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SnapshotFormat {
+    /// the server rendered page
+    Html,
+    /// the js generated for the document
+    Js,
+}
+
+/// ```ftd
+/// -- fastn.snapshot: Home page
+/// url: /
+/// format: html
+/// ```
+///
+/// renders `url` and compares it with `_tests/snapshots/<test file>/<id or title>.html`. A
+/// missing snapshot fails the test, `fastn test --update-snapshots` writes the snapshots.
+async fn execute_snapshot_instruction(
+    instruction: &ftd::interpreter::Component,
+    doc: &ftd::interpreter::TDoc<'_>,
+    config: &fastn_core::Config,
+    saved_cookies: &mut std::collections::HashMap<String, String>,
+    test_parameters: &mut TestParameters,
+) -> fastn_core::Result<bool> {
+    use actix_web::body::MessageBody;
+    use colored::Colorize;
+
+    let property_values = instruction.get_interpreter_property_value_of_all_arguments(doc)?;
+
+    let url = get_value_ok(TEST_URL_HEADER, &property_values, instruction.line_number)?
+        .to_json_string(doc, false)?
+        .unwrap();
+    let title = get_value_ok(TEST_TITLE_HEADER, &property_values, instruction.line_number)?
+        .to_json_string(doc, false)?
+        .unwrap();
+    let format = match get_optional_value_string(SNAPSHOT_FORMAT_HEADER, &property_values, doc)?
+        .as_deref()
+    {
+        None | Some("html") => SnapshotFormat::Html,
+        Some("js") => SnapshotFormat::Js,
+        Some(t) => {
+            return fastn_core::usage_error(format!(
+                "Unknown snapshot format `{}`, use `html` or `js`, doc: {} line_number: {}",
+                t, doc.name, instruction.line_number
+            ))
+        }
+    };
+    let name = get_optional_value_string(TEST_ID_HEADER, &property_values, doc)?
+        .unwrap_or_else(|| slug::slugify(title.as_str()));
+
+    println!("Test: {}", title.yellow());
+    log_message!(test_parameters.verbose, "Test type: SNAPSHOT");
+
+    let mut request = fastn_core::http::Request::default();
+    match url.split_once('?') {
+        Some((path, query_string)) => {
+            request.path = path.to_string();
+            request.set_query_string(query_string);
+        }
+        None => request.path = url.to_string(),
+    }
+    request.set_method("get");
+    request.set_cookies(saved_cookies);
+
+    log_message!(test_parameters.verbose, "Request details");
+    log_variable!(test_parameters.verbose, &request);
+
+    let response =
+        fastn_core::commands::serve::serve(config, request, format == SnapshotFormat::Js).await?;
+    update_cookies(saved_cookies, &response);
+
+    let body = match response.into_body().try_into_bytes() {
+        Ok(body) => body,
+        Err(_) => {
+            return fastn_core::assert_error(format!("{url}: could not read the response body"))
+        }
+    };
+    let output = normalize_snapshot(String::from_utf8_lossy(&body).as_ref(), format);
+
+    let path = config.get_snapshot_path(
+        doc.name,
+        name.as_str(),
+        match format {
+            SnapshotFormat::Html => "html",
+            SnapshotFormat::Js => "js",
+        },
+    );
+    if test_parameters.update_snapshots {
+        config.ds.write_content(&path, output.as_bytes()).await?;
+        println!("{}", format!("Snapshot written: {path}").green());
+        return Ok(true);
+    }
+
+    if !config.ds.exists(&path).await {
+        println!("{}", format!("Snapshot {path} does not exist").red());
+        println!("{}", "Test Failed".red());
+        println!("run `fastn test --update-snapshots` to write it");
+        return Ok(false);
+    }
+
+    let expected = config.ds.read_to_string(&path).await?;
+    if let Some((line, expected, found)) = first_difference(expected.as_str(), output.as_str()) {
+        println!(
+            "{}",
+            format!("Snapshot {path} differs at line {line}:\n- {expected}\n+ {found}").red()
+        );
+        println!("{}", "Test Failed".red());
+        println!("run `fastn test --update-snapshots` if the change is expected");
+        return Ok(false);
+    }
+
+    println!("{}", "Test Passed".green());
+    Ok(true)
+}
+
+static HASHED_ASSET_REGEX: once_cell::sync::Lazy<regex::Regex> =
+    once_cell::sync::Lazy::new(|| regex::Regex::new(r"-[0-9A-F]{64}\.").unwrap());
+
+/// the hashes in asset names like `default-<hash>.js` change with every fastn release, and the
+/// js snapshot leaves out the fastn js that every page includes
+fn normalize_snapshot(output: &str, format: SnapshotFormat) -> String {
+    let output = match format {
+        SnapshotFormat::Html => output,
+        SnapshotFormat::Js => output
+            .strip_prefix(fastn_js::all_js_without_test_and_ftd_langugage_js().as_str())
+            .unwrap_or(output),
+    };
+    HASHED_ASSET_REGEX
+        .replace_all(output.trim(), "-HASH.")
+        .to_string()
+        + "\n"
+}
+
+/// the first line, 1 based, where `found` differs from `expected`
+fn first_difference<'a>(expected: &'a str, found: &'a str) -> Option<(usize, &'a str, &'a str)> {
+    let mut expected_lines = expected.lines();
+    let mut found_lines = found.lines();
+    let mut line = 0;
+    loop {
+        line += 1;
+        match (expected_lines.next(), found_lines.next()) {
+            (None, None) => return None,
+            (e, f) if e == f => continue,
+            (e, f) => return Some((line, e.unwrap_or_default(), f.unwrap_or_default())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn glob_match() {
+        assert!(super::glob_match("*.test.ftd", "_tests/auth.test.ftd"));
+        assert!(super::glob_match(
+            "_tests/auth-*",
+            "_tests/auth-login.test.ftd"
+        ));
+        assert!(super::glob_match(
+            "_tests/**/login?.test.ftd",
+            "_tests/a/b/login1.test.ftd"
+        ));
+        assert!(!super::glob_match(
+            "_tests/auth-*",
+            "_tests/signup.test.ftd"
+        ));
+        assert!(!super::glob_match("login?.test.ftd", "login.test.ftd"));
+    }
+
+    #[test]
+    fn matches_file() {
+        let options = super::TestOptions {
+            files: vec!["auth-*".to_string(), "signup".to_string()],
+            ..Default::default()
+        };
+        assert!(options.matches_file("_tests/auth-login.test.ftd"));
+        assert!(options.matches_file("_tests/01-signup.test.ftd"));
+        assert!(!options.matches_file("_tests/profile.test.ftd"));
+        assert!(super::TestOptions::default().matches_file("_tests/profile.test.ftd"));
+    }
+
+    #[test]
+    fn normalize_snapshot() {
+        let hash = "A".repeat(64);
+        assert_eq!(
+            super::normalize_snapshot(
+                format!("<script src=\"default-{hash}.js\"></script>\n").as_str(),
+                super::SnapshotFormat::Html
+            ),
+            "<script src=\"default-HASH.js\"></script>\n"
+        );
+        assert_eq!(super::first_difference("a\nb\n", "a\nb"), None);
+        assert_eq!(
+            super::first_difference("a\nb", "a\nc\nd"),
+            Some((2, "b", "c"))
+        );
+        assert_eq!(super::first_difference("a", "a\nd"), Some((2, "", "d")));
+    }
+
+    #[tokio::test]
+    async fn test_database() {
+        let root = std::env::temp_dir().join(format!("fastn-test-database-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let ds = fastn_ds::DocumentStore::new(
+            camino::Utf8PathBuf::from_path_buf(root.clone()).unwrap(),
+            Default::default(),
+        )
+        .with_db_url(fastn_ds::db::DbUrl::Sqlite("fastn.sqlite".to_string()));
+        let count = |ds: fastn_ds::DocumentStore| async move {
+            ds.default_db_pool(false)
+                .await
+                .unwrap()
+                .sqlite()
+                .unwrap()
+                .get()
+                .unwrap()
+                .query_row("SELECT COUNT(*) FROM t", [], |r| r.get::<_, i64>(0))
+                .unwrap()
+        };
+
+        ds.default_db_pool(false)
+            .await
+            .unwrap()
+            .execute_batch("CREATE TABLE t (v INTEGER); INSERT INTO t VALUES (1);")
+            .await
+            .unwrap();
+
+        let test_database = fastn_ds::db::TestDatabase::start(&ds).await.unwrap();
+        let test_ds = ds.clone().with_db_url(test_database.url());
+        test_ds
+            .default_db_pool(false)
+            .await
+            .unwrap()
+            .execute_batch("INSERT INTO t VALUES (2);")
+            .await
+            .unwrap();
+        assert_eq!(count(test_ds.clone()).await, 2);
+        assert_eq!(count(ds.clone()).await, 1);
+
+        let fastn_ds::db::DbUrl::Sqlite(copy) = test_database.url() else {
+            panic!("expected a sqlite database");
+        };
+        test_database.finish(&test_ds).await.unwrap();
+        assert!(!std::path::Path::new(copy.as_str()).exists());
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn junit_report() {
+        let mut failed = super::TestResult::new(
+            "_tests/a.test.ftd",
+            "login <admin>".to_string(),
+            None,
+            super::TestStatus::Failed,
+        );
+        failed.message = Some("assertion failed".to_string());
+        let report = super::junit_report(&[
+            super::TestResult::new(
+                "_tests/a.test.ftd",
+                "home".to_string(),
+                None,
+                super::TestStatus::Passed,
+            ),
+            failed,
+        ]);
+        assert!(report.contains(r#"<testsuite name="_tests/a.test.ftd" tests="2" failures="1""#));
+        assert!(report.contains(r#"<testcase name="login &lt;admin&gt;""#));
+        assert!(report.contains(r#"<failure message="assertion failed"/>"#));
+    }
+}
@@ -1,9 +1,14 @@
-pub async fn translation_status(config: &fastn_core::Config) -> fastn_core::Result<()> {
+/// `fastn translation-status` prints the status of every file of a translation package, or the
+/// summary of every translation of an original package
+pub async fn translation_status(
+    config: &fastn_core::Config,
+    message_format: fastn_core::commands::check::MessageFormat,
+) -> fastn_core::Result<()> {
     // it can be original package or translation
     if config.is_translation_package() {
-        translation_package_status(config).await?;
+        translation_package_status(config, message_format).await?;
     } else if !config.package.translations.is_empty() {
-        original_package_status(config).await?;
+        original_package_status(config, message_format).await?;
     } else {
         return Err(fastn_core::Error::UsageError {
             message:
@@ -14,22 +19,92 @@ pub async fn translation_status(config: &fastn_core::Config) -> fastn_core::Resu
     Ok(())
 }
 
-async fn translation_package_status(config: &fastn_core::Config) -> fastn_core::Result<()> {
+async fn translation_package_status(
+    config: &fastn_core::Config,
+    message_format: fastn_core::commands::check::MessageFormat,
+) -> fastn_core::Result<()> {
     let original_snapshots =
         fastn_core::snapshot::get_latest_snapshots(&config.ds, &config.original_path()?).await?;
     let translation_status =
         get_translation_status(config, &original_snapshots, &config.ds.root()).await?;
-    print_translation_status(&translation_status);
+
+    match message_format {
+        fastn_core::commands::check::MessageFormat::Json => {
+            let report = TranslationReport {
+                package: config.package.name.to_string(),
+                translation_of: config
+                    .package
+                    .translation_of
+                    .as_ref()
+                    .as_ref()
+                    .map(|p| p.name.to_string()),
+                summary: summary(&translation_status),
+                files: translation_status,
+            };
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        fastn_core::commands::check::MessageFormat::Human => {
+            print_translation_status(&translation_status)
+        }
+    }
+    Ok(())
+}
+
+async fn original_package_status(
+    config: &fastn_core::Config,
+    message_format: fastn_core::commands::check::MessageFormat,
+) -> fastn_core::Result<()> {
+    match message_format {
+        fastn_core::commands::check::MessageFormat::Json => {
+            let summaries: std::collections::BTreeMap<_, _> = config
+                .package
+                .translations
+                .iter()
+                .filter_map(|t| {
+                    t.translation_status_summary
+                        .as_ref()
+                        .map(|s| (t.name.to_string(), s))
+                })
+                .collect();
+            println!("{}", serde_json::to_string_pretty(&summaries)?);
+        }
+        fastn_core::commands::check::MessageFormat::Human => {
+            for translation in config.package.translations.iter() {
+                if let Some(ref status) = translation.translation_status_summary {
+                    println!("Status for `{}` package:", translation.name);
+                    println!("{status}");
+                }
+            }
+        }
+    }
     Ok(())
 }
 
-async fn original_package_status(config: &fastn_core::Config) -> fastn_core::Result<()> {
-    for translation in config.package.translations.iter() {
-        if let Some(ref status) = translation.translation_status_summary {
-            println!("Status for `{}` package:", translation.name);
-            println!("{status}");
+/// `fastn mark-upto-date <file>` records that the translation of `file` is up to date with the
+/// latest snapshot of `file` in the original package
+pub async fn mark_upto_date(config: &fastn_core::Config, file: &str) -> fastn_core::Result<()> {
+    if !config.is_translation_package() {
+        return fastn_core::usage_error(
+            "`mark-upto-date` works only in a package with `translation-of`".to_string(),
+        );
+    }
+
+    let original_snapshots =
+        fastn_core::snapshot::get_latest_snapshots(&config.ds, &config.original_path()?).await?;
+    let timestamp = match original_snapshots.get(file) {
+        Some(timestamp) => *timestamp,
+        None => {
+            return fastn_core::usage_error(format!(
+                "`{file}` is not in the snapshots of the original package"
+            ))
         }
+    };
+    if !config.ds.exists(&config.ds.root().join(file)).await {
+        return fastn_core::usage_error(format!("`{file}` is not translated yet"));
     }
+
+    fastn_core::tracker::mark_upto_date(config, &config.ds.root(), file, timestamp).await?;
+    println!("{file} is now marked upto date");
     Ok(())
 }
 
@@ -37,16 +112,23 @@ pub(crate) async fn get_translation_status(
     config: &fastn_core::Config,
     snapshots: &std::collections::BTreeMap<String, u128>,
     path: &fastn_ds::Path,
-) -> fastn_core::Result<std::collections::BTreeMap<String, TranslationStatus>> {
-    let mut translation_status = std::collections::BTreeMap::new();
+) -> fastn_core::Result<Vec<FileStatus>> {
+    let mut translation_status = vec![];
     for (file, timestamp) in snapshots {
+        let mut file_status = FileStatus {
+            file: file.clone(),
+            status: TranslationStatus::NeverMarked,
+            original_latest: *timestamp,
+            last_marked_on: None,
+        };
         if !config.ds.exists(&path.join(file)).await {
-            translation_status.insert(file.clone(), TranslationStatus::Missing);
+            file_status.status = TranslationStatus::Missing;
+            translation_status.push(file_status);
             continue;
         }
         let track_path = fastn_core::utils::track_path(file.as_str(), path);
         if !config.ds.exists(&track_path).await {
-            translation_status.insert(file.clone(), TranslationStatus::NeverMarked);
+            translation_status.push(file_status);
             continue;
         }
         let tracks = fastn_core::tracker::get_tracks(config, path, &track_path).await?;
@@ -55,30 +137,65 @@ pub(crate) async fn get_translation_status(
             ..
         }) = tracks.get(file)
         {
-            if last_merged_version < timestamp {
-                translation_status.insert(file.clone(), TranslationStatus::Outdated);
-                continue;
-            }
-            translation_status.insert(file.clone(), TranslationStatus::UptoDate);
-        } else {
-            translation_status.insert(file.clone(), TranslationStatus::NeverMarked);
+            file_status.last_marked_on = Some(*last_merged_version);
+            file_status.status = if last_merged_version < timestamp {
+                TranslationStatus::Outdated
+            } else {
+                TranslationStatus::UptoDate
+            };
         }
+        translation_status.push(file_status);
     }
     Ok(translation_status)
 }
 
-fn print_translation_status(
-    translation_status: &std::collections::BTreeMap<String, TranslationStatus>,
-) {
-    for (file, status) in translation_status {
-        println!("{}: {}", status.as_str(), file);
+fn print_translation_status(translation_status: &[FileStatus]) {
+    for file_status in translation_status {
+        println!("{}: {}", file_status.status.as_str(), file_status.file);
     }
 }
 
-pub(crate) enum TranslationStatus {
+fn summary(
+    translation_status: &[FileStatus],
+) -> std::collections::BTreeMap<TranslationStatus, usize> {
+    let mut summary = std::collections::BTreeMap::new();
+    for file_status in translation_status {
+        *summary.entry(file_status.status).or_default() += 1;
+    }
+    summary
+}
+
+/// the `--message-format json` output of a translation package
+#[derive(serde::Serialize, Debug)]
+pub struct TranslationReport {
+    pub package: String,
+    #[serde(rename = "translation-of")]
+    pub translation_of: Option<String>,
+    pub summary: std::collections::BTreeMap<TranslationStatus, usize>,
+    pub files: Vec<FileStatus>,
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct FileStatus {
+    pub file: String,
+    pub status: TranslationStatus,
+    /// the timestamp of the latest snapshot of the file in the original package
+    #[serde(rename = "original-latest")]
+    pub original_latest: u128,
+    /// the snapshot of the original the translation was last marked up to date with
+    #[serde(rename = "last-marked-on")]
+    pub last_marked_on: Option<u128>,
+}
+
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TranslationStatus {
+    #[serde(rename = "missing")]
     Missing,
+    #[serde(rename = "never-marked")]
     NeverMarked,
+    #[serde(rename = "out-of-date")]
     Outdated,
+    #[serde(rename = "up-to-date")]
     UptoDate,
 }
 
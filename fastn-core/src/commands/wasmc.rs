@@ -0,0 +1,41 @@
+/// wasmc compiles `files` into the wasm cache of the document store. With `all` every `.wasm`
+/// used by a `wasm+proxy://` endpoint in `fastn.url-mappings` is compiled.
+pub async fn wasmc(
+    config: &fastn_core::Config,
+    files: &[String],
+    all: bool,
+) -> fastn_core::Result<()> {
+    let mut files = files.to_vec();
+    if all {
+        files.extend(endpoint_wasm_files(config));
+    }
+    files.sort();
+    files.dedup();
+
+    if files.is_empty() {
+        return fastn_core::usage_error(
+            "pass the .wasm files to compile, or --all to compile every wasm endpoint".to_string(),
+        );
+    }
+
+    for file in files {
+        let start = std::time::Instant::now();
+        print!("Compiling {file} ... ");
+        config.ds.wasmc(file.as_str()).await?;
+        fastn_core::utils::print_end(format!("Compiled {file}").as_str(), start);
+    }
+
+    Ok(())
+}
+
+/// `wasm+proxy://api.wasm/*` is served by `api.wasm`
+fn endpoint_wasm_files(config: &fastn_core::Config) -> Vec<String> {
+    config
+        .package
+        .endpoints
+        .iter()
+        .filter_map(|e| e.endpoint.strip_prefix("wasm+proxy://"))
+        .filter_map(|e| e.split_once(".wasm"))
+        .map(|(file, _)| format!("{file}.wasm"))
+        .collect()
+}
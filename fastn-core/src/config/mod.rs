@@ -40,6 +40,13 @@ pub struct Config {
     pub ftd_external_css: Vec<String>,
     pub ftd_inline_css: Vec<String>,
     pub test_command_running: bool,
+    pub live_reload: Option<fastn_core::live_reload::LiveReload>,
+    /// results of the processors with a `cache-ttl`, shared by all requests
+    pub processor_cache: fastn_core::processor_cache::ProcessorCache,
+    /// the message catalogs of the package, read by the first `translate` processor
+    pub catalogs: fastn_core::catalog::CatalogCache,
+    /// `sitemap.xml`, `robots.txt` and `feed.xml` generated by `fastn serve`
+    pub seo: fastn_core::sitemap::seo::SeoCache,
 }
 
 #[derive(Debug, Clone)]
@@ -60,6 +67,9 @@ pub struct RequestConfig {
     pub module_package_map: std::collections::BTreeMap<String, String>,
     /// each string is the value of Set-Cookie header
     pub processor_set_cookies: Vec<String>,
+    /// the language of the url prefix, like `hi` of `/hi/docs/`, or negotiated from the
+    /// `Accept-Language` header, see `fastn_core::package::Lang::negotiate`
+    pub language: Option<String>,
 }
 
 impl RequestConfig {
@@ -68,7 +78,9 @@ impl RequestConfig {
     }
 
     pub fn current_language(&self) -> Option<String> {
-        self.config.package.selected_language.clone()
+        self.language
+            .clone()
+            .or_else(|| self.config.package.selected_language.clone())
     }
 
     pub fn new(
@@ -91,6 +103,7 @@ impl RequestConfig {
             base_url: base_url.to_string(),
             module_package_map: Default::default(),
             processor_set_cookies: Default::default(),
+            language: None,
         }
     }
 
@@ -1039,6 +1052,12 @@ impl Config {
         config
     }
 
+    pub fn set_live_reload(self, watch: bool) -> Self {
+        let mut config = self;
+        config.live_reload = watch.then(fastn_core::live_reload::LiveReload::new);
+        config
+    }
+
     /// `read()` is the way to read a Config.
     #[tracing::instrument(name = "Config::read", skip_all)]
     pub async fn read(
@@ -1062,6 +1081,10 @@ impl Config {
             ftd_external_css: Default::default(),
             ftd_inline_css: Default::default(),
             test_command_running: false,
+            live_reload: None,
+            processor_cache: Default::default(),
+            catalogs: Default::default(),
+            seo: Default::default(),
             ds,
         };
         // Update global_ids map from the current package files
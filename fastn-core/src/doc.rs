@@ -164,6 +164,8 @@ pub async fn resolve_import_2022(
                 "current-language".to_string(),
                 "current-url".to_string(),
                 "translation-info".to_string(),
+                "translate".to_string(),
+                "language-switcher".to_string(),
             ],
             0,
         )
@@ -239,6 +241,8 @@ pub async fn resolve_import_2022(
                 "current-language".to_string(),
                 "current-url".to_string(),
                 "translation-info".to_string(),
+                "translate".to_string(),
+                "language-switcher".to_string(),
             ],
             ignore_line_numbers,
         )
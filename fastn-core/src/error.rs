@@ -123,12 +123,21 @@ pub enum Error {
     #[error("ds::CreatePoolError: {}", _0)]
     CreatePool(#[from] fastn_ds::CreatePoolError),
 
+    #[error("ds::DbError: {}", _0)]
+    DSDbError(#[from] fastn_ds::db::DbError),
+
     #[error("pool error: {0}")]
     PoolError(#[from] deadpool::managed::PoolError<tokio_postgres::Error>),
 
     #[error("ds::HttpError: {}", _0)]
     DSHttpError(#[from] fastn_ds::HttpError),
 
+    #[error("CatalogError: {}", _0)]
+    CatalogError(#[from] fastn_core::catalog::CatalogError),
+
+    #[error("ds::WasmReadError: {}", _0)]
+    DSWasmReadError(#[from] fastn_ds::WasmReadError),
+
     #[error("AssertError: {message}")]
     AssertError { message: String },
 
@@ -379,32 +379,17 @@ pub async fn http_post_with_cookie(
     headers: &std::collections::HashMap<String, String>,
     body: &str,
 ) -> fastn_core::Result<(fastn_core::Result<bytes::Bytes>, Vec<String>)> {
-    pub use fastn_ds::RequestType;
     tracing::info!(url = url);
 
-    let cookies = req_config.request.cookies().clone();
-    let mut req_headers = reqwest::header::HeaderMap::new();
-    for (key, value) in headers.iter() {
-        req_headers.insert(
-            reqwest::header::HeaderName::from_bytes(key.as_bytes()).unwrap(),
-            reqwest::header::HeaderValue::from_str(value.as_str()).unwrap(),
-        );
-    }
-
-    let mut http_request = fastn_core::http::Request::default();
-    http_request.set_method("post");
-    http_request.set_cookies(&cookies);
-    http_request.set_headers(headers);
-    http_request.set_ip(req_config.request.ip.clone());
-    http_request.set_body(actix_web::web::Bytes::copy_from_slice(body.as_bytes()));
-
-    let http_url = url::Url::parse(url).map_err(|e| fastn_core::Error::DSHttpError(e.into()))?;
-    let res = req_config
-        .config
-        .ds
-        .http(http_url, &http_request, &Default::default())
-        .await
-        .map_err(fastn_core::Error::DSHttpError)?;
+    let res = http_request(
+        &req_config.config.ds,
+        &req_config.request,
+        "post",
+        url,
+        headers,
+        body,
+    )
+    .await?;
 
     let mut resp_cookies = vec![];
     res.headers().iter().for_each(|(k, v)| {
@@ -433,6 +418,29 @@ pub async fn http_post_with_cookie(
     Ok((Ok(res.body().clone()), resp_cookies))
 }
 
+/// sends a `method` request to `url` with the cookies and the ip of `req`. The response is
+/// returned whatever its status, only failing to send the request is an error.
+pub async fn http_request(
+    ds: &fastn_ds::DocumentStore,
+    req: &fastn_core::http::Request,
+    method: &str,
+    url: &str,
+    headers: &std::collections::HashMap<String, String>,
+    body: &str,
+) -> fastn_core::Result<fastn_ds::HttpResponse> {
+    let mut http_request = fastn_core::http::Request::default();
+    http_request.set_method(method);
+    http_request.set_cookies(req.cookies());
+    http_request.set_headers(headers);
+    http_request.set_ip(req.ip.clone());
+    http_request.set_body(actix_web::web::Bytes::copy_from_slice(body.as_bytes()));
+
+    let http_url = url::Url::parse(url).map_err(|e| fastn_core::Error::DSHttpError(e.into()))?;
+    ds.http(http_url, &http_request, &Default::default())
+        .await
+        .map_err(fastn_core::Error::DSHttpError)
+}
+
 pub async fn http_get(ds: &fastn_ds::DocumentStore, url: &str) -> fastn_core::Result<bytes::Bytes> {
     tracing::debug!("http_get {}", &url);
 
@@ -6,6 +6,7 @@ extern crate self as fastn_core;
 #[macro_use]
 pub mod utils;
 mod auto_import;
+pub mod catalog;
 pub mod commands;
 mod config;
 pub mod doc;
@@ -18,6 +19,8 @@ pub mod http;
 mod ds;
 mod error;
 pub mod library;
+pub mod live_reload;
+pub mod processor_cache;
 pub mod sitemap;
 mod snapshot;
 mod tracker;
@@ -30,7 +33,15 @@ mod library2022;
 
 pub(crate) use auto_import::AutoImport;
 pub use commands::{
-    build::build, check::post_build_check, fmt::fmt, query::query, serve::listen, test::test,
+    build::build,
+    check::{check, post_build_check},
+    fmt::fmt,
+    lsp::lsp,
+    migrate::migrate,
+    query::query,
+    serve::listen,
+    test::test,
+    wasmc::wasmc,
 };
 pub use config::{config_temp, Config, ConfigTemp, FTDEdition, RequestConfig};
 pub use doc::resolve_foreign_variable2;
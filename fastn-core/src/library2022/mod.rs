@@ -252,6 +252,10 @@ impl Library2022 {
             "http" => processor::http::process(value, kind, doc, self).await,
             "translation-info" => processor::lang_details::process(value, kind, doc, self).await,
             "current-language" => processor::lang::process(value, kind, doc, self).await,
+            "language-switcher" => {
+                processor::lang_details::switcher_process(value, kind, doc, self).await
+            }
+            "translate" => processor::translate::process(value, kind, doc, self).await,
             "toc" => processor::toc::process(value, kind, doc),
             "get-data" => processor::get_data::process(value, kind, doc, self),
             "sitemap" => processor::sitemap::process(value, kind, doc, self),
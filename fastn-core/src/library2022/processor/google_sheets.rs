@@ -572,7 +572,7 @@ pub(crate) fn parse_query(
 }
 
 pub(crate) async fn process(
-    ds: &fastn_ds::DocumentStore,
+    config: &fastn_core::Config,
     value: ftd_ast::VariableValue,
     kind: ftd::interpreter::Kind,
     doc: &ftd::interpreter::TDoc<'_>,
@@ -585,7 +585,17 @@ pub(crate) async fn process(
     let request_url =
         fastn_core::google_sheets::prepare_query_url(&db_config.db_url, query.as_str(), sheet);
 
-    let response = match fastn_core::http::http_get_str(ds, &request_url).await {
+    let policy =
+        fastn_core::processor_cache::Policy::from_headers(&headers, doc.name, value.line_number())?;
+    let key =
+        fastn_core::processor_cache::ProcessorCache::key("google-sheets", &[request_url.as_str()]);
+    let response = match config
+        .processor_cache
+        .get_or_fetch(key, policy, || {
+            fastn_core::http::http_get_str(&config.ds, &request_url)
+        })
+        .await
+    {
         Ok(v) => v,
         Err(e) => {
             return ftd::interpreter::utils::e2(
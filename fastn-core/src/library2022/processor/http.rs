@@ -1,3 +1,44 @@
+/// headers of the `http` processor that configure the request, the other headers are sent in
+/// the body of `POST`, `PUT` and `PATCH` requests without a `body` header, and in the query
+/// string otherwise
+const REQUEST_HEADERS: [&str; 9] = [
+    "url",
+    "method",
+    "body",
+    "content-type",
+    "timeout",
+    "retries",
+    "retry-delay",
+    "retry-non-idempotent",
+    "response",
+];
+const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+const DEFAULT_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// ```ftd
+/// -- pr.http-response res:
+/// $processor$: pr.http
+/// url: https://example.com/api/people/
+/// method: put
+/// content-type: form
+/// timeout: 5s
+/// retries: 2
+/// response: full
+/// name: $name
+/// ```
+///
+/// `content-type` is `json` (the default), `form`, `text` or any mime type. A `body` header is
+/// sent as is, or as json if it is a `$variable`.
+///
+/// `retries` retries a request that failed to send, timed out or got a 5xx or 429 response,
+/// after `retry-delay`, doubled on every retry. The upstream may have handled a request that
+/// failed, so `POST` and `PATCH` requests are only retried with `retry-non-idempotent: true`.
+///
+/// Only `GET` requests can be cached with `cache-ttl`, see `fastn_core::ProcessorCache`.
+///
+/// By default the variable is read from the json response and any other response fails the
+/// render. With `response: full` the variable is a `pr.http-response`, or any record with its
+/// fields and a `data` field for the json response, even if the request fails.
 pub async fn process(
     value: ftd_ast::VariableValue,
     kind: ftd::interpreter::Kind,
@@ -10,14 +51,20 @@ pub async fn process(
         (ftd_ast::HeaderValues::new(vec![]), value.line_number())
     };
 
+    let policy =
+        fastn_core::processor_cache::Policy::from_headers(&headers, doc.name, line_number)?;
+
     let method = headers
         .get_optional_string_by_key("method", doc.name, line_number)?
         .unwrap_or_else(|| "GET".to_string())
         .to_lowercase();
 
-    if method.as_str().ne("get") && method.as_str().ne("post") {
+    if !METHODS.contains(&method.as_str()) {
         return ftd::interpreter::utils::e2(
-            format!("only GET and POST methods are allowed, found: {}", method),
+            format!(
+                "only GET, POST, PUT, PATCH and DELETE methods are allowed, found: {}",
+                method
+            ),
             doc.name,
             line_number,
         );
@@ -66,11 +113,97 @@ pub async fn process(
             },
         )?;
 
-    let mut body = vec![];
+    let content_type =
+        match headers.get_optional_string_by_key("content-type", doc.name, line_number)? {
+            Some(v) => match ContentType::parse(v.as_str()) {
+                Some(c) => c,
+                None => {
+                    return ftd::interpreter::utils::e2(
+                        format!(
+                            "content-type: expected json, form, text or a mime type, found {v}"
+                        ),
+                        doc.name,
+                        line_number,
+                    )
+                }
+            },
+            None => ContentType::Json,
+        };
+
+    let body = match headers.get_optional_string_by_key("body", doc.name, line_number)? {
+        Some(v) if v.starts_with('$') => Some(Body::Json(
+            resolve_json(doc, v.as_str(), line_number)?.unwrap_or_default(),
+        )),
+        Some(v) => Some(Body::Text(v)),
+        None => None,
+    };
+    let fields_in_body = body.is_none() && ["post", "put", "patch"].contains(&method.as_str());
+
+    let timeout =
+        fastn_core::processor_cache::duration_header(&headers, "timeout", doc.name, line_number)?;
+    let retry_delay = fastn_core::processor_cache::duration_header(
+        &headers,
+        "retry-delay",
+        doc.name,
+        line_number,
+    )?
+    .unwrap_or(DEFAULT_RETRY_DELAY);
+    let retries = match headers.get_optional_string_by_key("retries", doc.name, line_number)? {
+        Some(v) => match v.trim().parse::<u32>() {
+            Ok(v) => v,
+            Err(_) => {
+                return ftd::interpreter::utils::e2(
+                    format!("retries: expected a number, found {v}"),
+                    doc.name,
+                    line_number,
+                )
+            }
+        },
+        None => 0,
+    };
+    let retry_non_idempotent = match headers
+        .get_optional_string_by_key("retry-non-idempotent", doc.name, line_number)?
+        .as_deref()
+    {
+        None | Some("false") => false,
+        Some("true") => true,
+        Some(v) => {
+            return ftd::interpreter::utils::e2(
+                format!("retry-non-idempotent: expected true or false, found {v}"),
+                doc.name,
+                line_number,
+            )
+        }
+    };
+    if let Err(e) = check_method(
+        method.as_str(),
+        policy.is_some(),
+        retries,
+        retry_non_idempotent,
+    ) {
+        return ftd::interpreter::utils::e2(e, doc.name, line_number);
+    }
+
+    let full_response = match headers
+        .get_optional_string_by_key("response", doc.name, line_number)?
+        .as_deref()
+    {
+        None | Some("body") => false,
+        Some("full") => true,
+        Some(v) => {
+            return ftd::interpreter::utils::e2(
+                format!("response: expected body or full, found {v}"),
+                doc.name,
+                line_number,
+            )
+        }
+    };
+
+    let mut fields = vec![];
     for header in headers.0 {
         if header.key.as_str() == ftd::PROCESSOR_MARKER
-            || header.key.as_str() == "url"
-            || header.key.as_str() == "method"
+            || REQUEST_HEADERS.contains(&header.key.as_str())
+            || fastn_core::processor_cache::CACHE_HEADERS.contains(&header.key.as_str())
         {
             continue;
         }
@@ -79,119 +212,455 @@ pub async fn process(
 
         // 1 id: $query.id
         // After resolve headers: id:1234(value of $query.id)
-        if value.starts_with('$') {
-            if let Some(value) = doc
-                .get_value(header.line_number, value.as_str())?
-                .to_json_string(doc, true)?
-            {
-                if let Some(key) = fastn_core::http::get_header_key(header.key.as_str()) {
-                    conf.insert(key.to_string(), value.trim_matches('"').to_string());
-                    continue;
-                }
-                if method.as_str().eq("post") {
-                    body.push(format!("\"{}\": {}", header.key, value));
-                    continue;
-                }
-                url.query_pairs_mut()
-                    .append_pair(header.key.as_str(), value.trim_matches('"'));
+        let value = if value.starts_with('$') {
+            match resolve_json(doc, value.as_str(), header.line_number)? {
+                Some(v) => v,
+                None => continue,
             }
         } else {
-            if let Some(key) = fastn_core::http::get_header_key(header.key.as_str()) {
-                conf.insert(key.to_string(), value);
-                continue;
-            }
-            if method.as_str().eq("post") {
-                body.push(format!(
-                    "\"{}\": \"{}\"",
-                    header.key,
-                    fastn_core::utils::escape_string(value.as_str())
-                ));
-                continue;
-            }
-            url.query_pairs_mut()
-                .append_pair(header.key.as_str(), value.as_str());
+            serde_json::Value::String(value)
+        };
+
+        if let Some(key) = fastn_core::http::get_header_key(header.key.as_str()) {
+            conf.insert(key.to_string(), as_text(&value));
+            continue;
         }
+        if fields_in_body {
+            fields.push((header.key, value));
+            continue;
+        }
+        url.query_pairs_mut()
+            .append_pair(header.key.as_str(), as_text(&value).as_str());
     }
 
-    if !req_config.config.test_command_running {
-        println!("calling `http` processor with url: {}", &url);
+    let body = match body {
+        Some(body) => content_type.encode(body),
+        None if fields_in_body => match content_type.encode_fields(fields) {
+            Some(body) => body,
+            None => {
+                return ftd::interpreter::utils::e2(
+                    format!(
+                        "content-type: {} needs a `body` header",
+                        content_type.mime()
+                    ),
+                    doc.name,
+                    line_number,
+                )
+            }
+        },
+        None => String::new(),
+    };
+    if !body.is_empty() && !conf.keys().any(|k| k.eq_ignore_ascii_case("content-type")) {
+        conf.insert("content-type".to_string(), content_type.mime().to_string());
     }
 
-    let resp = if url.scheme() == "wasm+proxy" {
-        match req_config
-            .config
-            .ds
-            .handle_wasm(url.to_string(), &req_config.request)
-            .await
-        {
-            Ok(r) => {
-                let mut resp_cookies = vec![];
-                r.headers.into_iter().for_each(|(k, v)| {
-                    if k.as_str().eq("set-cookie") {
-                        if let Ok(v) = String::from_utf8(v) {
-                            resp_cookies.push(v.to_string());
-                        }
-                    }
-                });
-                Ok((Ok(r.body.into()), resp_cookies))
-            }
-            e => todo!("error: {e:?}"),
-        }
-    } else if method.as_str().eq("post") {
-        fastn_core::http::http_post_with_cookie(
-            req_config,
-            url.as_str(),
-            &conf,
-            format!("{{{}}}", body.join(",")).as_str(),
-        )
-        .await
-        .map_err(|e| ftd::interpreter::Error::DSHttpError {
-            message: format!("{:?}", e),
-        })
-    } else {
-        fastn_core::http::http_get_with_cookie(
-            &req_config.config.ds,
-            &req_config.request,
+    let key = fastn_core::processor_cache::ProcessorCache::key(
+        "http",
+        &[
+            method.as_str(),
             url.as_str(),
-            &conf,
-            false, // disable cache
-        )
-        .await
-        .map_err(|e| ftd::interpreter::Error::DSHttpError {
-            message: format!("{:?}", e),
-        })
+            format!(
+                "{:?}",
+                conf.iter().collect::<std::collections::BTreeMap<_, _>>()
+            )
+            .as_str(),
+            body.as_str(),
+            format!(
+                "{:?}",
+                req_config
+                    .request
+                    .cookies()
+                    .iter()
+                    .collect::<std::collections::BTreeMap<_, _>>()
+            )
+            .as_str(),
+        ],
+    );
+    let request = Request {
+        method,
+        url,
+        headers: conf,
+        body,
+        timeout,
+        retries,
+        retry_delay,
     };
+    let result = req_config
+        .config
+        .processor_cache
+        .clone()
+        .get_or_fetch(key, policy, || fetch(req_config, &request))
+        .await;
 
-    let response = match resp {
-        Ok((Ok(v), cookies)) => {
-            req_config.processor_set_cookies.extend(cookies);
-            v
+    let response_json = match result {
+        Ok(response) => {
+            req_config
+                .processor_set_cookies
+                .extend(response.cookies.iter().cloned());
+            if full_response {
+                response.to_json()
+            } else {
+                serde_json::from_str(response.body.as_str())
+                    .map_err(|e| ftd::interpreter::Error::Serde { source: e })?
+            }
         }
-        Ok((Err(e), cookies)) => {
-            req_config.processor_set_cookies.extend(cookies);
-            return ftd::interpreter::utils::e2(
-                format!("HTTP::get failed: {:?}", e),
-                doc.name,
-                line_number,
-            );
+        Err(FetchError::Status(response)) if full_response => {
+            req_config
+                .processor_set_cookies
+                .extend(response.cookies.iter().cloned());
+            response.to_json()
         }
+        Err(FetchError::Failed(e)) if full_response => serde_json::json!({
+            "status": 0,
+            "ok": false,
+            "error": e,
+            "headers": [],
+        }),
         Err(e) => {
             return ftd::interpreter::utils::e2(
-                format!("HTTP::get failed: {:?}", e),
+                format!(
+                    "HTTP::{} {} failed: {}",
+                    request.method,
+                    request.url,
+                    e.message()
+                ),
                 doc.name,
                 line_number,
             );
         }
     };
 
-    let response_string =
-        String::from_utf8(response.to_vec()).map_err(|e| ftd::interpreter::Error::ParseError {
-            message: format!("`http` processor API response error: {}", e),
-            doc_id: doc.name.to_string(),
-            line_number,
-        })?;
-    let response_json: serde_json::Value = serde_json::from_str(&response_string)
-        .map_err(|e| ftd::interpreter::Error::Serde { source: e })?;
-
     doc.from_json(&response_json, &kind, &value)
 }
+
+#[derive(Debug, Clone, PartialEq)]
+enum ContentType {
+    Json,
+    Form,
+    Text,
+    Mime(String),
+}
+
+enum Body {
+    Json(serde_json::Value),
+    Text(String),
+}
+
+impl ContentType {
+    fn parse(v: &str) -> Option<ContentType> {
+        Some(match v.trim() {
+            "json" => ContentType::Json,
+            "form" => ContentType::Form,
+            "text" => ContentType::Text,
+            v if v.contains('/') => ContentType::Mime(v.to_string()),
+            _ => return None,
+        })
+    }
+
+    fn mime(&self) -> &str {
+        match self {
+            ContentType::Json => "application/json",
+            ContentType::Form => "application/x-www-form-urlencoded",
+            ContentType::Text => "text/plain; charset=utf-8",
+            ContentType::Mime(v) => v.as_str(),
+        }
+    }
+
+    fn encode(&self, body: Body) -> String {
+        match (self, body) {
+            (_, Body::Text(v)) => v,
+            (ContentType::Json, Body::Json(v)) => v.to_string(),
+            (ContentType::Form, Body::Json(serde_json::Value::Object(o))) => self
+                .encode_fields(o.into_iter().collect())
+                .unwrap_or_default(),
+            (_, Body::Json(v)) => as_text(&v),
+        }
+    }
+
+    /// `None` if the fields can not be sent as this content type
+    fn encode_fields(&self, fields: Vec<(String, serde_json::Value)>) -> Option<String> {
+        match self {
+            ContentType::Json => {
+                Some(serde_json::Value::Object(fields.into_iter().collect()).to_string())
+            }
+            ContentType::Form => Some(
+                url::form_urlencoded::Serializer::new(String::new())
+                    .extend_pairs(fields.iter().map(|(k, v)| (k, as_text(v))))
+                    .finish(),
+            ),
+            ContentType::Text | ContentType::Mime(_) if fields.is_empty() => Some(String::new()),
+            ContentType::Text | ContentType::Mime(_) => None,
+        }
+    }
+}
+
+/// the json of the variable `$name`
+pub(crate) fn resolve_json(
+    doc: &ftd::interpreter::TDoc<'_>,
+    name: &str,
+    line_number: usize,
+) -> ftd::interpreter::Result<Option<serde_json::Value>> {
+    Ok(doc
+        .get_value(line_number, name)?
+        .to_json_string(doc, true)?
+        .map(|v| serde_json::from_str(v.as_str()).unwrap_or(serde_json::Value::String(v))))
+}
+
+/// strings without their quotes
+fn as_text(v: &serde_json::Value) -> String {
+    match v {
+        serde_json::Value::String(v) => v.to_string(),
+        v => v.to_string(),
+    }
+}
+
+struct Request {
+    method: String,
+    url: url::Url,
+    headers: std::collections::HashMap<String, String>,
+    body: String,
+    timeout: Option<std::time::Duration>,
+    retries: u32,
+    retry_delay: std::time::Duration,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Response {
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: String,
+    cookies: Vec<String>,
+}
+
+#[derive(Debug)]
+enum FetchError {
+    /// the request could not be sent, or timed out
+    Failed(String),
+    /// the response is not a 2xx response
+    Status(Response),
+}
+
+impl FetchError {
+    fn message(&self) -> String {
+        match self {
+            FetchError::Failed(e) => e.to_string(),
+            FetchError::Status(r) => format!("response_status: {}, response: {}", r.status, r.body),
+        }
+    }
+}
+
+impl Response {
+    fn ok(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    fn retry(&self) -> bool {
+        self.status >= 500 || self.status == 429
+    }
+
+    /// the json of a `pr.http-response`, `data` is the json response if the body is json
+    fn to_json(&self) -> serde_json::Value {
+        let mut json = serde_json::json!({
+            "status": self.status,
+            "ok": self.ok(),
+            "headers": self
+                .headers
+                .iter()
+                .map(|(key, value)| serde_json::json!({ "key": key, "value": value }))
+                .collect::<Vec<_>>(),
+            "body": self.body,
+        });
+        if let Ok(data) = serde_json::from_str::<serde_json::Value>(self.body.as_str()) {
+            json["data"] = data;
+        }
+        json
+    }
+}
+
+async fn fetch(
+    req_config: &fastn_core::RequestConfig,
+    request: &Request,
+) -> Result<Response, FetchError> {
+    if !req_config.config.test_command_running {
+        println!("calling `http` processor with url: {}", &request.url);
+    }
+
+    let mut delay = request.retry_delay;
+    let mut attempt = 0;
+    loop {
+        let result = send(req_config, request).await;
+        let retry = match &result {
+            Ok(response) => response.retry(),
+            Err(_) => true,
+        };
+        if !retry || attempt >= request.retries {
+            return match result {
+                Ok(response) if response.ok() => Ok(response),
+                Ok(response) => Err(FetchError::Status(response)),
+                Err(e) => Err(FetchError::Failed(e)),
+            };
+        }
+
+        attempt += 1;
+        tracing::warn!(
+            msg = "retrying `http` processor request",
+            url = request.url.as_str(),
+            attempt,
+            result = ?result.as_ref().map(|r| r.status)
+        );
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+}
+
+async fn send(
+    req_config: &fastn_core::RequestConfig,
+    request: &Request,
+) -> Result<Response, String> {
+    let send = async {
+        if request.url.scheme() == "wasm+proxy" {
+            // the handler sees the page request, with the method, headers and body of the
+            // processor
+            for (key, value) in request.headers.iter() {
+                if reqwest::header::HeaderName::from_bytes(key.as_bytes()).is_err()
+                    || reqwest::header::HeaderValue::from_str(value.as_str()).is_err()
+                {
+                    return Err(format!("invalid header `{key}: {value}`"));
+                }
+            }
+            let mut wasm_request = req_config.request.clone();
+            wasm_request.set_method(request.method.as_str());
+            wasm_request.set_headers(&request.headers);
+            wasm_request.set_body(request.body.clone().into());
+
+            let r = req_config
+                .config
+                .ds
+                .handle_wasm(request.url.to_string(), &wasm_request, &Default::default())
+                .await
+                .map_err(|e| e.to_string())?;
+            // `to_response` of fastn-ds: the status of a response is in `method`
+            let status = r
+                .method
+                .parse::<u16>()
+                .map_err(|_| format!("wasm returned an invalid status: {}", r.method))?;
+            let headers: Vec<(String, String)> = r
+                .headers
+                .into_iter()
+                .filter_map(|(k, v)| String::from_utf8(v).ok().map(|v| (k, v)))
+                .collect();
+            return Ok::<_, String>(Response {
+                status,
+                cookies: cookies(&headers),
+                headers,
+                body: String::from_utf8_lossy(&r.body).to_string(),
+            });
+        }
+
+        let res = fastn_core::http::http_request(
+            &req_config.config.ds,
+            &req_config.request,
+            request.method.as_str(),
+            request.url.as_str(),
+            &request.headers,
+            request.body.as_str(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        let headers: Vec<(String, String)> = res
+            .headers()
+            .iter()
+            .filter_map(|(k, v)| {
+                v.to_str()
+                    .ok()
+                    .map(|v| (k.as_str().to_string(), v.to_string()))
+            })
+            .collect();
+        Ok(Response {
+            status: res.status().as_u16(),
+            cookies: cookies(&headers),
+            headers,
+            body: String::from_utf8_lossy(res.body()).to_string(),
+        })
+    };
+
+    match request.timeout {
+        Some(timeout) => tokio::time::timeout(timeout, send)
+            .await
+            .unwrap_or_else(|_| Err(format!("timed out after {timeout:?}"))),
+        None => send.await,
+    }
+}
+
+fn cookies(headers: &[(String, String)]) -> Vec<String> {
+    headers
+        .iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case("set-cookie"))
+        .map(|(_, v)| v.to_string())
+        .collect()
+}
+
+/// the response of a request that changes the upstream is not cached, and it is not sent again
+/// unless the page asks for it
+fn check_method(
+    method: &str,
+    cached: bool,
+    retries: u32,
+    retry_non_idempotent: bool,
+) -> Result<(), String> {
+    if cached && method != "get" {
+        return Err(format!(
+            "cache-ttl: only GET requests are cached, found {}",
+            method.to_uppercase()
+        ));
+    }
+    if retries > 0 && ["post", "patch"].contains(&method) && !retry_non_idempotent {
+        return Err(format!(
+            "retries: {} requests are not idempotent, add `retry-non-idempotent: true` to retry \
+            them",
+            method.to_uppercase()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn check_method() {
+        assert!(super::check_method("get", true, 2, false).is_ok());
+        assert!(super::check_method("put", false, 2, false).is_ok());
+        assert!(super::check_method("delete", false, 2, false).is_ok());
+        assert!(super::check_method("post", false, 0, false).is_ok());
+        assert!(super::check_method("post", false, 2, false).is_err());
+        assert!(super::check_method("patch", false, 1, false).is_err());
+        assert!(super::check_method("post", false, 2, true).is_ok());
+        assert!(super::check_method("post", true, 0, false).is_err());
+        assert!(super::check_method("put", true, 0, false).is_err());
+    }
+
+    #[test]
+    fn encode_fields() {
+        let fields = vec![
+            ("name".to_string(), serde_json::json!("a b")),
+            ("age".to_string(), serde_json::json!(30)),
+        ];
+        let json = super::ContentType::Json
+            .encode_fields(fields.clone())
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(json.as_str()).unwrap(),
+            serde_json::json!({"name": "a b", "age": 30})
+        );
+        assert_eq!(
+            super::ContentType::Form.encode_fields(fields.clone()),
+            Some("name=a+b&age=30".to_string())
+        );
+        assert_eq!(super::ContentType::Text.encode_fields(fields), None);
+        assert_eq!(
+            super::ContentType::parse("application/xml"),
+            Some(super::ContentType::Mime("application/xml".to_string()))
+        );
+        assert_eq!(super::ContentType::parse("xml"), None);
+    }
+}
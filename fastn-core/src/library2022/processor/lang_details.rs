@@ -13,6 +13,49 @@ pub async fn process(
     )
 }
 
+/// ```ftd
+/// -- pr.language-link list languages:
+/// $processor$: pr.language-switcher
+/// ```
+///
+/// the current page in every available language of the package, for a language switcher
+pub async fn switcher_process(
+    value: ftd_ast::VariableValue,
+    kind: ftd::interpreter::Kind,
+    doc: &ftd::interpreter::TDoc<'_>,
+    req_config: &mut fastn_core::RequestConfig,
+) -> ftd::interpreter::Result<ftd::interpreter::Value> {
+    let lang = match req_config.config.package.lang {
+        Some(ref lang) => lang,
+        None => return doc.from_json(&Vec::<LanguageLink>::new(), &kind, &value),
+    };
+    let current = req_config.current_language();
+    let path = fastn_core::package::lang::page_path(req_config, req_config.document_id.as_str());
+
+    let links: Vec<LanguageLink> = lang
+        .languages()
+        .into_iter()
+        .map(|id| LanguageLink {
+            human: realm_lang::Language::from_2_letter_code(id.as_str())
+                .map(|l| l.human())
+                .unwrap_or_else(|_| id.to_string()),
+            url: lang.url(id.as_str(), path.as_str()),
+            is_current: current.as_deref() == Some(id.as_str()),
+            id,
+        })
+        .collect();
+    doc.from_json(&links, &kind, &value)
+}
+
+#[derive(Default, Debug, serde::Serialize)]
+pub struct LanguageLink {
+    pub id: String,
+    pub human: String,
+    pub url: String,
+    #[serde(rename = "is-current")]
+    pub is_current: bool,
+}
+
 #[derive(Default, Debug, serde::Serialize)]
 pub struct LanguageData {
     #[serde(rename = "current-language")]
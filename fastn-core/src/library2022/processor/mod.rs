@@ -16,6 +16,7 @@ pub(crate) mod sitemap;
 pub(crate) mod sql;
 pub(crate) mod sqlite;
 pub(crate) mod toc;
+pub(crate) mod translate;
 pub(crate) mod user_details;
 pub(crate) mod user_group;
 
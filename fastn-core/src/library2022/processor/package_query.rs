@@ -32,7 +32,8 @@ pub async fn process(
     }
 
     let query_response = fastn_core::library2022::processor::sqlite::execute_query(
-        &sqlite_database_path,
+        &req_config.config,
+        sqlite_database.as_str(),
         query.as_str(),
         doc,
         headers,
@@ -209,7 +209,7 @@ fn prepare_args(
     expected_args: &[postgres_types::Type],
     doc: &ftd::interpreter::TDoc<'_>,
     line_number: usize,
-    headers: ftd_ast::HeaderValues,
+    headers: &ftd_ast::HeaderValues,
 ) -> ftd::interpreter::Result<QueryArgs> {
     if expected_args.len() != query_args.len() {
         return ftd::interpreter::utils::e2(
@@ -225,7 +225,7 @@ fn prepare_args(
     let mut args = vec![];
     for (e, a) in expected_args.iter().zip(query_args) {
         args.push(
-            match resolve_variable_from_headers(doc, &headers, &a, e, doc.name, line_number)? {
+            match resolve_variable_from_headers(doc, headers, &a, e, doc.name, line_number)? {
                 Some(v) => v,
                 None => resolve_variable_from_doc(doc, &a, e, line_number)?,
             },
@@ -241,21 +241,74 @@ async fn execute_query(
     headers: ftd_ast::HeaderValues,
     req_config: &fastn_core::RequestConfig,
 ) -> fastn_core::Result<Vec<Vec<serde_json::Value>>> {
+    let policy =
+        fastn_core::processor_cache::Policy::from_headers(&headers, doc.name, line_number)?;
     let (query, query_args) = super::sql::extract_arguments(query)?;
-    let pool = req_config.config.ds.default_pg_pool().await?;
-    let client = pool.get().await?;
 
-    let stmt = client.prepare_cached(query.as_str()).await.unwrap();
+    let mut key_parts = vec![query.clone()];
+    for arg in query_args.iter() {
+        key_parts.push(arg_key(doc, &headers, arg, line_number)?);
+    }
+    let key = fastn_core::processor_cache::ProcessorCache::key(
+        "pg",
+        &key_parts.iter().map(String::as_str).collect::<Vec<_>>(),
+    );
+
+    // a cached result is served without a connection, and a stale one if the database is down
+    req_config
+        .config
+        .processor_cache
+        .get_or_fetch(key, policy, || async {
+            let pool = req_config.config.ds.default_pg_pool().await?;
+            let client = pool.get().await?;
+            let stmt = client.prepare_cached(query.as_str()).await.map_err(|e| {
+                fastn_core::Error::DatabaseError {
+                    message: format!("failed to prepare query: {e}"),
+                }
+            })?;
+
+            let args = prepare_args(query_args, stmt.params(), doc, line_number, &headers)?;
+            let rows = client.query(&stmt, &args.pg_args()).await.map_err(|e| {
+                fastn_core::Error::DatabaseError {
+                    message: format!("failed to run query: {e}"),
+                }
+            })?;
+
+            let mut result: Vec<Vec<serde_json::Value>> = vec![];
+            for r in rows {
+                result.push(row_to_json(r, doc.name, line_number)?)
+            }
 
-    let args = prepare_args(query_args, stmt.params(), doc, line_number, headers)?;
-    let rows = client.query(&stmt, &args.pg_args()).await.unwrap();
-    let mut result: Vec<Vec<serde_json::Value>> = vec![];
+            Ok::<_, fastn_core::Error>(result)
+        })
+        .await
+}
 
-    for r in rows {
-        result.push(row_to_json(r, doc.name, line_number)?)
-    }
+/// the cache key part of the argument `var`, the value it resolves to before it is converted to
+/// the type the statement expects, that needs the prepared statement and so a connection
+fn arg_key(
+    doc: &ftd::interpreter::TDoc<'_>,
+    headers: &ftd_ast::HeaderValues,
+    var: &str,
+    line_number: usize,
+) -> ftd::interpreter::Result<String> {
+    let header = headers.optional_header_by_name(var, doc.name, line_number)?;
+    let var = match header.map(|h| &h.value) {
+        Some(ftd_ast::VariableValue::String { value, .. }) => match value.strip_prefix('$') {
+            Some(stripped) => stripped,
+            None => return Ok(format!("header:{value}")),
+        },
+        Some(v) => return Ok(format!("header:{v:?}")),
+        None => var,
+    };
 
-    Ok(result)
+    Ok(match doc.get_thing(var, line_number) {
+        Ok(ftd::interpreter::Thing::Variable(v)) => {
+            format!("{:?}", v.value.resolve(doc, line_number)?)
+        }
+        // `prepare_args` reports the error
+        _ => format!("unresolved:{var}"),
+    })
 }
 
 fn row_to_json(
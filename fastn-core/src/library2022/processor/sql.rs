@@ -10,25 +10,23 @@ impl DatabaseConfig {
     }
 }
 
+/// the database of `FASTN_DB_URL`, see `fastn_ds::DocumentStore::configured_db_url`. Unlike the
+/// wasm handlers, the `sql` processor does not fall back to `fastn.sqlite`.
 pub(crate) async fn get_db_config(
     ds: &fastn_ds::DocumentStore,
 ) -> ftd::interpreter::Result<DatabaseConfig> {
     let db_url = ds
-        .env("FASTN_DB_URL")
+        .configured_db_url()
         .await
-        .map_err(|_| ftd::interpreter::Error::OtherError("FASTN_DB_URL is not set".to_string()))?;
+        .map_err(|e| ftd::interpreter::Error::OtherError(e.to_string()))?
+        .ok_or_else(|| {
+            ftd::interpreter::Error::OtherError("FASTN_DB_URL is not set".to_string())
+        })?;
 
-    if let Some(db_url) = db_url.strip_prefix("sqlite:///") {
-        return Ok(DatabaseConfig::new(
-            db_url.to_string(),
-            "sqlite".to_string(),
-        ));
-    }
-
-    let url = url::Url::parse(&db_url)
-        .map_err(|_| ftd::interpreter::Error::OtherError("Invalid DB URL".to_string()))?;
-
-    Ok(DatabaseConfig::new(db_url, url.scheme().to_string()))
+    Ok(match db_url {
+        fastn_ds::db::DbUrl::Sqlite(path) => DatabaseConfig::new(path, "sqlite".to_string()),
+        fastn_ds::db::DbUrl::Postgres(url) => DatabaseConfig::new(url, "postgres".to_string()),
+    })
 }
 
 pub async fn process(
@@ -68,7 +66,7 @@ pub async fn process(
         )
         .await?),
         "google_sheets" => Ok(fastn_core::library2022::processor::google_sheets::process(
-            &config.config.ds,
+            &config.config,
             value,
             kind,
             doc,
@@ -84,7 +82,12 @@ pub async fn process(
 pub const STATUS_OK: usize = 0;
 pub const STATUS_ERROR: usize = 1;
 const BACKSLASH: char = '\\';
-const SPECIAL_CHARS: [char; 9] = [BACKSLASH, '$', '/', ':', '"', ',', '\'', ';', ' '];
+
+/// `$user.full-name` is an argument, it ends at the first other character, like `)`, `::` or
+/// the space, `/`, `:`, `"`, `,`, `'` and `;` arguments always ended at
+fn is_argument_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '.')
+}
 
 // TODO: Can improve the performance
 // Maybe I should use RegEx?
@@ -128,18 +131,17 @@ pub(crate) fn extract_arguments(query: &str) -> ftd::interpreter::Result<(String
         }
 
         if chars[i] == '$' && !escaped && !quote_open {
-            let mut arg = String::new();
-            i += 1;
-
-            while i < len {
-                if SPECIAL_CHARS.contains(&chars[i]) {
-                    i -= 1;
-                    break;
-                } else {
-                    arg.push(chars[i]);
-                    i += 1;
-                }
+            let start = i + 1;
+            let mut end = start;
+            while end < len && is_argument_char(chars[end]) {
+                end += 1;
+            }
+            // `$id.` at the end of a sentence, the `.` is not part of the argument
+            while end > start && chars[end - 1] == '.' {
+                end -= 1;
             }
+            let arg: String = chars[start..end].iter().collect();
+            i = end - 1;
 
             if !arg.is_empty() {
                 if let Some(index) = args.iter().position(|x| x == &arg) {
@@ -187,11 +189,48 @@ mod test {
             vec!["name"],
         );
         e("hello", "hello", vec![]);
+        e(
+            "INSERT INTO contact (name, email, message) VALUES ($name, $email, $message);",
+            "INSERT INTO contact (name, email, message) VALUES ($1, $2, $3);",
+            vec!["name", "email", "message"],
+        );
+        e(
+            "SELECT * FROM test where id IN ($id)",
+            "SELECT * FROM test where id IN ($1)",
+            vec!["id"],
+        );
+        e(
+            "SELECT * FROM test where name = $user.full-name||$suffix",
+            "SELECT * FROM test where name = $1||$2",
+            vec!["user.full-name", "suffix"],
+        );
         e(
             "SELECT * FROM test where name = $name",
             "SELECT * FROM test where name = $1",
             vec!["name"],
         );
+        // the characters arguments always ended at
+        e(
+            "SELECT $a/$b:$c::text",
+            "SELECT $1/$2:$3::text",
+            vec!["a", "b", "c"],
+        );
+        e(
+            "SELECT $a,$b'$c;$d $e",
+            "SELECT $1,$2'$3;$4 $5",
+            vec!["a", "b", "c", "d", "e"],
+        );
+        e(
+            "SELECT * FROM test where id = $id.",
+            "SELECT * FROM test where id = $1.",
+            vec!["id"],
+        );
+        e(
+            "SELECT * FROM test where name = $person.name and id = $person.id..",
+            "SELECT * FROM test where name = $1 and id = $2..",
+            vec!["person.name", "person.id"],
+        );
+        e("SELECT $", "SELECT ", vec![]);
         e(
             "SELECT * FROM test where name = $name and full_name = $full_name",
             "SELECT * FROM test where name = $1 and full_name = $2",
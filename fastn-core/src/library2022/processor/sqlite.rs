@@ -37,8 +37,6 @@ pub async fn process(
     headers: ftd_ast::HeaderValues,
     query: &str,
 ) -> ftd::interpreter::Result<ftd::interpreter::Value> {
-    let sqlite_database_path = req_config.config.ds.root().join(&db_config.db_url);
-
     // need the query params
     // question is they can be multiple
     // so lets say start with passing attributes from ftd file
@@ -48,7 +46,8 @@ pub async fn process(
     // select * from users where
 
     let query_response = execute_query(
-        &sqlite_database_path,
+        &req_config.config,
+        db_config.db_url.as_str(),
         query,
         doc,
         headers,
@@ -291,30 +290,79 @@ fn extract_named_parameters(
     Ok(params)
 }
 
+/// `database` is relative to the package root. Queries run on a read-only connection of
+/// `fastn_ds::DocumentStore::sqlite_pools`, statements that write go to a read-write one.
 pub(crate) async fn execute_query(
-    database_path: &fastn_ds::Path,
+    config: &fastn_core::Config,
+    database: &str,
     query: &str,
     doc: &ftd::interpreter::TDoc<'_>,
     headers: ftd_ast::HeaderValues,
     line_number: usize,
 ) -> ftd::interpreter::Result<Vec<Vec<serde_json::Value>>> {
-    let doc_name = doc.name;
+    let policy =
+        fastn_core::processor_cache::Policy::from_headers(&headers, doc.name, line_number)?;
+
+    // let mut stmt = conn.prepare("SELECT * FROM test where name = :name")?;
+    // let mut rows = stmt.query(rusqlite::named_params! { ":name": "one" })?
+
+    // let mut stmt = conn.prepare("SELECT * FROM test where name = ?")?;
+    // let mut rows = stmt.query([name])?;
+    let params = extract_named_parameters(query, doc, headers, line_number)?;
+
+    let key = fastn_core::processor_cache::ProcessorCache::key(
+        "sqlite",
+        &[
+            database,
+            query,
+            format!(
+                "{:?}",
+                params.iter().map(|p| p.to_sql().ok()).collect::<Vec<_>>()
+            )
+            .as_str(),
+        ],
+    );
+    config
+        .processor_cache
+        .get_or_fetch(key, policy, || async {
+            run_query(&config.ds, database, query, params, doc.name, line_number)
+        })
+        .await
+}
+
+fn run_query(
+    ds: &fastn_ds::DocumentStore,
+    database: &str,
+    query: &str,
+    params: Vec<Box<dyn rusqlite::ToSql>>,
+    doc_name: &str,
+    line_number: usize,
+) -> ftd::interpreter::Result<Vec<Vec<serde_json::Value>>> {
+    let open = |read_only: bool| match ds.sqlite_pools.get(database, read_only).get() {
+        Ok(conn) => Ok(conn),
+        Err(e) => ftd::interpreter::utils::e2(
+            format!("Failed to open `{}`: {:?}", database, e),
+            doc_name,
+            line_number,
+        ),
+    };
 
-    let conn = match rusqlite::Connection::open_with_flags(
-        database_path.to_string(),
-        rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE,
-    ) {
-        Ok(conn) => conn,
+    let conn = open(true)?;
+    let read_only = match is_read_only(&conn, query) {
+        Ok(v) => v,
         Err(e) => {
             return ftd::interpreter::utils::e2(
-                format!("Failed to open `{}`: {:?}", database_path, e),
+                format!("Failed to prepare query: {:?}", e),
                 doc_name,
                 line_number,
-            );
+            )
         }
     };
+    // a read-only statement runs on the connection that prepared it, so it comes from the
+    // statement cache of that connection below
+    let conn = if read_only { conn } else { open(false)? };
 
-    let mut stmt = match conn.prepare(query) {
+    let mut stmt = match conn.prepare_cached(query) {
         Ok(v) => v,
         Err(e) => {
             return ftd::interpreter::utils::e2(
@@ -327,13 +375,6 @@ pub(crate) async fn execute_query(
 
     let count = stmt.column_count();
 
-    // let mut stmt = conn.prepare("SELECT * FROM test where name = :name")?;
-    // let mut rows = stmt.query(rusqlite::named_params! { ":name": "one" })?
-
-    // let mut stmt = conn.prepare("SELECT * FROM test where name = ?")?;
-    // let mut rows = stmt.query([name])?;
-    let params = extract_named_parameters(query, doc, headers, line_number)?;
-
     let mut rows = match stmt.query(rusqlite::params_from_iter(params)) {
         Ok(v) => v,
         Err(e) => {
@@ -364,6 +405,12 @@ pub(crate) async fn execute_query(
     Ok(result)
 }
 
+/// `false` for statements that write to the database, e.g. `INSERT`, they can not run on a
+/// read-only connection
+fn is_read_only(conn: &rusqlite::Connection, query: &str) -> rusqlite::Result<bool> {
+    Ok(conn.prepare_cached(query)?.readonly())
+}
+
 fn row_to_json(
     r: &rusqlite::Row,
     count: usize,
@@ -397,3 +444,32 @@ fn row_to_json(
     }
     Ok(row)
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn is_read_only() {
+        let root =
+            std::env::temp_dir().join(format!("fastn-sqlite-read-only-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        let ds = fastn_ds::DocumentStore::new(
+            camino::Utf8PathBuf::from_path_buf(root.clone()).unwrap(),
+            Default::default(),
+        );
+        let db = fastn_ds::db::DEFAULT_SQLITE_PATH;
+        ds.sqlite_pools
+            .get(db, false)
+            .get()
+            .unwrap()
+            .execute_batch("CREATE TABLE t (v INTEGER)")
+            .unwrap();
+
+        let conn = ds.sqlite_pools.get(db, true).get().unwrap();
+        assert!(super::is_read_only(&conn, "SELECT * FROM t").unwrap());
+        assert!(!super::is_read_only(&conn, "INSERT INTO t VALUES (1)").unwrap());
+        assert!(!super::is_read_only(&conn, "DELETE FROM t").unwrap());
+        assert!(super::is_read_only(&conn, "SELECT * FROM missing").is_err());
+        drop(conn);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
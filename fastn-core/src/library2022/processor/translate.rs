@@ -0,0 +1,76 @@
+/// ```ftd
+/// -- string greeting:
+/// $processor$: pr.translate
+/// message-id: hello-user
+/// name: $user.name
+/// ```
+///
+/// the message `message-id` of the package catalogs, see `fastn_core::catalog::Catalogs`. The
+/// other headers are the arguments of the message.
+pub async fn process(
+    value: ftd_ast::VariableValue,
+    kind: ftd::interpreter::Kind,
+    doc: &ftd::interpreter::TDoc<'_>,
+    req_config: &mut fastn_core::RequestConfig,
+) -> ftd::interpreter::Result<ftd::interpreter::Value> {
+    let (headers, line_number) = if let Ok(val) = value.get_record(doc.name) {
+        (val.2.to_owned(), val.5.to_owned())
+    } else {
+        (ftd_ast::HeaderValues::new(vec![]), value.line_number())
+    };
+
+    let id = match headers.get_optional_string_by_key(
+        fastn_core::catalog::MESSAGE_ID_HEADER,
+        doc.name,
+        line_number,
+    )? {
+        Some(id) => id,
+        None => {
+            return ftd::interpreter::utils::e2(
+                format!(
+                    "'{}' key is required when using `{}: translate`",
+                    fastn_core::catalog::MESSAGE_ID_HEADER,
+                    ftd::PROCESSOR_MARKER
+                ),
+                doc.name,
+                line_number,
+            )
+        }
+    };
+
+    let mut args = fluent::FluentArgs::new();
+    for header in headers.0 {
+        if header.key.as_str() == ftd::PROCESSOR_MARKER
+            || header.key.as_str() == fastn_core::catalog::MESSAGE_ID_HEADER
+        {
+            continue;
+        }
+
+        let value = header.value.string(doc.name)?;
+        let value = if value.starts_with('$') {
+            match super::http::resolve_json(doc, value.as_str(), header.line_number)? {
+                Some(v) => v,
+                None => continue,
+            }
+        } else {
+            serde_json::Value::String(value)
+        };
+        args.set(header.key, fastn_core::catalog::fluent_value(value));
+    }
+
+    let languages = fastn_core::catalog::request_languages(req_config);
+    let text = match req_config.catalogs().await {
+        Ok(catalogs) => catalogs.format(&languages, id.as_str(), &args),
+        Err(e) => {
+            return ftd::interpreter::utils::e2(
+                format!("could not read the catalogs: {e}"),
+                doc.name,
+                line_number,
+            )
+        }
+    };
+    match text {
+        Ok(text) => doc.from_json(&text, &kind, &value),
+        Err(e) => ftd::interpreter::utils::e2(e.to_string(), doc.name, line_number),
+    }
+}
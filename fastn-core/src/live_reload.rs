@@ -0,0 +1,226 @@
+pub const LIVE_RELOAD_PATH: &str = "/-/fastn/live-reload/";
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// LiveReload is used by `fastn serve --watch`. A background task polls the package for
+/// changes, and every page served subscribes to `LIVE_RELOAD_PATH` (server sent events) so it
+/// can reload itself when one of the files it depends on is modified.
+#[derive(Debug, Clone)]
+pub struct LiveReload {
+    sender: tokio::sync::broadcast::Sender<std::sync::Arc<Vec<String>>>,
+    /// url path -> modules imported while rendering that path
+    dependencies: std::sync::Arc<scc::HashMap<String, Vec<String>>>,
+}
+
+impl Default for LiveReload {
+    fn default() -> Self {
+        LiveReload::new()
+    }
+}
+
+impl LiveReload {
+    pub fn new() -> LiveReload {
+        let (sender, _) = tokio::sync::broadcast::channel(16);
+        LiveReload {
+            sender,
+            dependencies: Default::default(),
+        }
+    }
+
+    /// record the modules `path` imported, so edits in `.packages` only reload pages that use
+    /// the changed module
+    pub(crate) fn record(&self, path: &str, dependencies: &[String]) {
+        fastn_ds::insert_or_update(
+            &self.dependencies,
+            path.trim_matches('/').to_string(),
+            dependencies
+                .iter()
+                .map(|v| module_id(v))
+                .collect::<Vec<String>>(),
+        );
+    }
+
+    pub(crate) fn notify(&self, changed: Vec<String>) {
+        // send fails only when no browser is listening, that is fine
+        let _ = self.sender.send(std::sync::Arc::new(changed));
+    }
+
+    fn should_reload(&self, path: &str, changed: &[String]) -> bool {
+        let dependencies = self
+            .dependencies
+            .get(path.trim_matches('/'))
+            .map(|v| v.get().clone());
+
+        changed.iter().any(
+            |file| match (file.strip_prefix(".packages/"), dependencies.as_ref()) {
+                (Some(file), Some(dependencies)) => dependencies.contains(&module_id(file)),
+                // a change in the package itself (or a page we have not rendered yet)
+                _ => true,
+            },
+        )
+    }
+
+    /// server sent events stream for the page at `?path=`
+    pub(crate) fn events(&self, req: &fastn_core::http::Request) -> fastn_core::http::Response {
+        let path = req
+            .query()
+            .get("path")
+            .and_then(|v| v.as_str())
+            .unwrap_or("/")
+            .to_string();
+
+        let stream = futures::stream::unfold(
+            (self.sender.subscribe(), self.clone(), path),
+            |(mut receiver, live_reload, path)| async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(changed) if !live_reload.should_reload(&path, &changed) => continue,
+                        Ok(_) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => {
+                            return Some((
+                                Ok::<_, std::convert::Infallible>(bytes::Bytes::from_static(
+                                    b"data: reload\n\n",
+                                )),
+                                (receiver, live_reload, path),
+                            ));
+                        }
+                        Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+                    }
+                }
+            },
+        );
+
+        actix_web::HttpResponse::Ok()
+            .content_type("text/event-stream")
+            .append_header(("Cache-Control", "no-cache"))
+            // the compress middleware would buffer the stream
+            .insert_header(actix_web::http::header::ContentEncoding::Identity)
+            .streaming(stream)
+    }
+}
+
+/// script injected in every page rendered while `--watch` is on
+pub(crate) fn script() -> String {
+    format!(
+        indoc::indoc! {r#"
+        <script>
+            (function () {{
+                if (!window.EventSource) return;
+                let source = new EventSource("{path}?path=" + encodeURIComponent(window.location.pathname));
+                source.onmessage = function () {{ window.location.reload(); }};
+            }})();
+        </script>
+        "#},
+        path = LIVE_RELOAD_PATH
+    )
+}
+
+/// foo/index.ftd, foo.ftd, foo/ -> foo
+fn module_id(id: &str) -> String {
+    let id = id.trim_matches('/');
+    let id = id.strip_suffix(".ftd").unwrap_or(id);
+    let id = id.strip_suffix("/index").unwrap_or(id);
+    id.trim_matches('/').to_string()
+}
+
+/// watch polls the package (and `.packages`) for modified, added or removed files. On a change
+/// it drops the document store, catalog and seo caches and tells all connected pages to reload.
+pub async fn watch(config: std::sync::Arc<fastn_core::Config>) {
+    let live_reload = match config.live_reload {
+        Some(ref l) => l.clone(),
+        None => return,
+    };
+
+    let mut previous = snapshot(&config).await;
+
+    loop {
+        tokio::time::sleep(POLL_INTERVAL).await;
+
+        let current = snapshot(&config).await;
+        let changed = previous
+            .iter()
+            .filter(|(path, modified)| current.get(*path) != Some(modified))
+            .chain(
+                current
+                    .iter()
+                    .filter(|(path, _)| !previous.contains_key(*path)),
+            )
+            .map(|(path, _)| path.to_string())
+            .collect::<Vec<String>>();
+        previous = current;
+
+        if changed.is_empty() {
+            continue;
+        }
+
+        tracing::info!(msg = "files changed", changed = ?changed);
+        config.ds.clear_cache();
+        config.catalogs.clear();
+        config.seo.clear();
+
+        if changed.iter().any(|v| v == "FASTN.ftd") {
+            fastn_core::warning!("FASTN.ftd changed, restart `fastn serve` to apply the change");
+        }
+
+        live_reload.notify(changed);
+    }
+}
+
+async fn snapshot(
+    config: &fastn_core::Config,
+) -> std::collections::BTreeMap<String, std::time::SystemTime> {
+    let root = config.ds.root();
+    let mut files = config
+        .get_all_file_paths(&config.package)
+        .await
+        .unwrap_or_default();
+    files.extend(
+        config
+            .ds
+            .get_all_file_path(&config.packages_root, &[])
+            .await,
+    );
+
+    let mut snapshot = std::collections::BTreeMap::new();
+    for file in files {
+        if let (Some(id), Ok(modified)) =
+            (file.strip_prefix(&root), config.ds.modified(&file).await)
+        {
+            snapshot.insert(id.to_string(), modified);
+        }
+    }
+    snapshot
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn should_reload() {
+        let live_reload = super::LiveReload::new();
+        live_reload.record(
+            "/docs/",
+            &[
+                "fastn-community.github.io/doc-site/".to_string(),
+                "fastn-community.github.io/doc-site/page/".to_string(),
+            ],
+        );
+
+        // own files always reload
+        assert!(live_reload.should_reload("/docs/", &["index.ftd".to_string()]));
+        assert!(live_reload.should_reload(
+            "/docs/",
+            &[".packages/fastn-community.github.io/doc-site/page.ftd".to_string()]
+        ));
+        assert!(live_reload.should_reload(
+            "/docs/",
+            &[".packages/fastn-community.github.io/doc-site/index.ftd".to_string()]
+        ));
+        assert!(!live_reload.should_reload(
+            "/docs/",
+            &[".packages/fastn-community.github.io/typography/index.ftd".to_string()]
+        ));
+        // pages we know nothing about reload on every change
+        assert!(live_reload.should_reload(
+            "/blog/",
+            &[".packages/fastn-community.github.io/typography/index.ftd".to_string()]
+        ));
+    }
+}
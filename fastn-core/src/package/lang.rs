@@ -0,0 +1,141 @@
+/// The cookie a language switcher sets, it wins over `Accept-Language`
+pub const LANGUAGE_COOKIE: &str = "fastn-lang";
+
+/// A package with `lang` serves every page in every available language at the url prefixed with
+/// the language, `/hi/docs/` is `/docs/` in Hindi. The url without a prefix is in the language
+/// negotiated from the `fastn-lang` cookie and the `Accept-Language` header, see `negotiate`.
+impl fastn_core::package::Lang {
+    /// the available languages, sorted
+    pub fn languages(&self) -> Vec<String> {
+        let mut languages: Vec<String> = self.available_languages.keys().cloned().collect();
+        languages.sort();
+        languages
+    }
+
+    /// `/hi/docs/` is (`hi`, `/docs/`), `None` if `path` does not start with an available
+    /// language
+    pub fn split_prefix(&self, path: &str) -> Option<(String, String)> {
+        let trimmed = path.strip_prefix('/').unwrap_or(path);
+        let (language, rest) = match trimmed.split_once('/') {
+            Some((language, rest)) => (language, format!("/{rest}")),
+            None => (trimmed, "/".to_string()),
+        };
+        if !self.available_languages.contains_key(language) {
+            return None;
+        }
+        Some((language.to_string(), rest))
+    }
+
+    /// the url of `path`, which does not have a language prefix, in `language`
+    pub fn url(&self, language: &str, path: &str) -> String {
+        format!("/{language}/{}", path.trim_start_matches('/'))
+    }
+
+    /// the `fastn-lang` cookie if it is an available language, else the first available
+    /// language of the `Accept-Language` header, where `hi-IN` also matches `hi`, else the
+    /// default language
+    pub fn negotiate(&self, cookie: Option<&str>, accept_language: Option<&str>) -> String {
+        if let Some(cookie) = cookie.filter(|c| self.available_languages.contains_key(*c)) {
+            return cookie.to_string();
+        }
+
+        accept_language
+            .map(accept_language::parse)
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|l| [l.as_str(), l.split('-').next().unwrap_or_default()])
+            .find(|l| self.available_languages.contains_key(*l))
+            .map(ToString::to_string)
+            .unwrap_or_else(|| self.default_lang.to_string())
+    }
+}
+
+/// the path of the page being rendered, without the language prefix
+pub(crate) fn page_path(req_config: &fastn_core::RequestConfig, document_id: &str) -> String {
+    let path = req_config.request.path();
+    if path.is_empty() {
+        // `fastn build` renders without a request
+        return format!(
+            "/{}",
+            fastn_core::utils::id_to_path(document_id)
+                .trim_start_matches('/')
+                .replace(std::path::MAIN_SEPARATOR, "/")
+        );
+    }
+    req_config
+        .config
+        .package
+        .lang
+        .as_ref()
+        .and_then(|lang| lang.split_prefix(path))
+        .map_or_else(|| path.to_string(), |(_, rest)| rest)
+}
+
+/// `<link rel="alternate" hreflang="...">` for the page in every available language, and
+/// `x-default` for the url without a prefix
+pub(crate) fn alternate_links(req_config: &fastn_core::RequestConfig, document_id: &str) -> String {
+    let lang = match req_config.config.package.lang {
+        Some(ref lang) => lang,
+        None => return String::new(),
+    };
+    let base = fastn_core::sitemap::seo::base_url(&req_config.config.package);
+    let path = page_path(req_config, document_id);
+
+    let mut links: Vec<String> = lang
+        .languages()
+        .iter()
+        .map(|language| {
+            format!(
+                "<link rel=\"alternate\" hreflang=\"{language}\" href=\"{base}{}\">",
+                lang.url(language, path.as_str())
+            )
+        })
+        .collect();
+    links.push(format!(
+        "<link rel=\"alternate\" hreflang=\"x-default\" href=\"{base}{path}\">"
+    ));
+    links.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    fn lang() -> fastn_core::package::Lang {
+        fastn_core::package::Lang {
+            default_lang: "en".to_string(),
+            available_languages: [("en", "en.ftd"), ("hi", "hi.ftd")]
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn split_prefix() {
+        let lang = lang();
+        assert_eq!(
+            lang.split_prefix("/hi/docs/"),
+            Some(("hi".to_string(), "/docs/".to_string()))
+        );
+        assert_eq!(
+            lang.split_prefix("/hi/"),
+            Some(("hi".to_string(), "/".to_string()))
+        );
+        assert_eq!(
+            lang.split_prefix("/hi"),
+            Some(("hi".to_string(), "/".to_string()))
+        );
+        assert_eq!(lang.split_prefix("/history/"), None);
+        assert_eq!(lang.split_prefix("/"), None);
+        assert_eq!(lang.url("hi", "/docs/"), "/hi/docs/");
+    }
+
+    #[test]
+    fn negotiate() {
+        let lang = lang();
+        assert_eq!(lang.negotiate(Some("hi"), Some("en")), "hi");
+        assert_eq!(lang.negotiate(Some("fr"), Some("fr, hi-IN;q=0.8")), "hi");
+        assert_eq!(lang.negotiate(None, Some("de, en;q=0.5, hi;q=0.7")), "hi");
+        assert_eq!(lang.negotiate(None, Some("de")), "en");
+        assert_eq!(lang.negotiate(None, None), "en");
+    }
+}
@@ -1,7 +1,9 @@
 pub mod app;
 pub mod dependency;
+pub mod lang;
 pub mod package_doc;
 pub mod redirects;
+pub mod sql_action;
 
 #[derive(Debug, Clone)]
 pub struct Package {
@@ -46,6 +48,11 @@ pub struct Package {
     pub dynamic_urls: Option<fastn_core::sitemap::DynamicUrls>,
     pub dynamic_urls_temp: Option<fastn_core::sitemap::DynamicUrlsTemp>,
 
+    /// `robots` is the body of `fastn.robots`, served as `/robots.txt`
+    pub robots: Option<String>,
+    /// `feed` is the sitemap section published as `/feed.xml`
+    pub feed: Option<fastn_core::sitemap::seo::Feed>,
+
     /// Optional path for favicon icon to be used.
     ///
     /// By default if any file favicon.* is present in package and favicon is not specified
@@ -57,6 +64,10 @@ pub struct Package {
 
     /// endpoints for proxy service
     pub endpoints: Vec<fastn_package::old_fastn::EndpointData>,
+    /// `fastn.sql-action` statements forms can be submitted to
+    pub sql_actions: Vec<sql_action::SqlAction>,
+    /// url -> name of the sql action handling it, from `fastn.url-mappings`
+    pub sql_action_urls: ftd::Map<String>,
 
     /// Installed Apps
     pub apps: Vec<app::App>,
@@ -98,8 +109,12 @@ impl Package {
             sitemap: None,
             dynamic_urls: None,
             dynamic_urls_temp: None,
+            robots: None,
+            feed: None,
             favicon: None,
             endpoints: vec![],
+            sql_actions: vec![],
+            sql_action_urls: Default::default(),
             apps: vec![],
             icon: None,
             redirects: None,
@@ -601,6 +616,7 @@ impl Package {
         if let Some(url_mappings) = url_mappings {
             package.redirects = Some(url_mappings.redirects);
             package.endpoints = url_mappings.endpoints;
+            package.sql_action_urls = url_mappings.sql_actions;
         }
 
         package.translation_status_summary =
@@ -671,6 +687,7 @@ impl Package {
         if let Some(url_mappings) = url_mappings {
             package.redirects = Some(url_mappings.redirects);
             package.endpoints = url_mappings.endpoints;
+            package.sql_action_urls = url_mappings.sql_actions;
         }
 
         // reading dependencies
@@ -714,6 +731,27 @@ impl Package {
         package.fonts = fastn_doc.get("fastn#font")?;
         package.sitemap_temp = fastn_doc.get("fastn#sitemap")?;
         package.dynamic_urls_temp = fastn_doc.get("fastn#dynamic-urls")?;
+        package.robots = fastn_doc
+            .get::<Option<fastn_core::sitemap::seo::RobotsTemp>>("fastn#robots")?
+            .map(|r| r.body);
+        package.feed = fastn_doc
+            .get::<Option<fastn_core::sitemap::seo::FeedTemp>>("fastn#feed")?
+            .map(|f| f.into_feed())
+            .transpose()?;
+        package.sql_actions = fastn_doc
+            .get::<Vec<sql_action::SqlActionTemp>>("fastn#sql-action")?
+            .into_iter()
+            .map(|a| a.into_sql_action())
+            .collect();
+        if let Some(name) = package
+            .sql_action_urls
+            .values()
+            .find(|name| !package.sql_actions.iter().any(|a| a.name == **name))
+        {
+            return Err(fastn_core::Error::PackageError {
+                message: format!("fastn.url-mappings uses sql+action://{name}, but there is no `fastn.sql-action: {name}`"),
+            });
+        }
 
         // validation logic TODO: It should be ordered
         fastn_core::utils::validate_base_url(&package)?;
@@ -955,8 +993,12 @@ impl PackageTempIntoPackage for fastn_package::old_fastn::PackageTemp {
             sitemap_temp: None,
             dynamic_urls: None,
             dynamic_urls_temp: None,
+            robots: None,
+            feed: None,
             favicon: self.favicon,
             endpoints: self.endpoint,
+            sql_actions: vec![],
+            sql_action_urls: Default::default(),
             apps: vec![],
             icon: self.icon,
             redirects: None,
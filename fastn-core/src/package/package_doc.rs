@@ -432,18 +432,14 @@ pub(crate) async fn read_ftd_2022(
     Ok(FTDResult::Html(file_content.into()))
 }
 
-#[allow(clippy::await_holding_refcell_ref)]
-#[tracing::instrument(name = "read_ftd_2023", skip_all)]
-pub(crate) async fn read_ftd_2023(
+/// `main` with the auto imports of its package, interpreted with the processors executed
+/// against `config.request`
+pub(crate) async fn interpret_ftd_2023(
     config: &mut fastn_core::RequestConfig,
     main: &fastn_core::Document,
     base_url: &str,
     download_assets: bool,
-    only_js: bool,
-) -> fastn_core::Result<FTDResult> {
-    let package_name = config.config.package.name.to_string();
-    let c = &config.config.clone();
-
+) -> fastn_core::Result<ftd::interpreter::Document> {
     let current_package = config
         .config
         .find_package_else_default(main.package_name.as_str(), None);
@@ -458,7 +454,7 @@ pub(crate) async fn read_ftd_2023(
     doc_content = current_package.fix_imports_in_body(doc_content.as_str(), main.id.as_str())?;
 
     let line_number = doc_content.split('\n').count() - main.content.split('\n').count();
-    let main_ftd_doc = match fastn_core::doc::interpret_helper(
+    match fastn_core::doc::interpret_helper(
         main.id_with_package().as_str(),
         doc_content.as_str(),
         config,
@@ -468,14 +464,29 @@ pub(crate) async fn read_ftd_2023(
     )
     .await
     {
-        Ok(v) => v,
+        Ok(v) => Ok(v),
         Err(e) => {
             tracing::error!(msg = "failed to parse", doc = main.id.as_str());
-            return Err(fastn_core::Error::PackageError {
+            Err(fastn_core::Error::PackageError {
                 message: format!("failed to parse {:?}", &e),
-            });
+            })
         }
-    };
+    }
+}
+
+#[allow(clippy::await_holding_refcell_ref)]
+#[tracing::instrument(name = "read_ftd_2023", skip_all)]
+pub(crate) async fn read_ftd_2023(
+    config: &mut fastn_core::RequestConfig,
+    main: &fastn_core::Document,
+    base_url: &str,
+    download_assets: bool,
+    only_js: bool,
+) -> fastn_core::Result<FTDResult> {
+    let package_name = config.config.package.name.to_string();
+    let c = &config.config.clone();
+
+    let main_ftd_doc = interpret_ftd_2023(config, main, base_url, download_assets).await?;
     if let Some((url, code)) = main_ftd_doc.get_redirect()? {
         return Ok(FTDResult::Redirect { url, code });
     }
@@ -508,6 +519,7 @@ pub(crate) async fn read_ftd_2023(
             config.config.get_font_style().as_str(),
             ftd::ftd_js_css(),
             base_url,
+            fastn_core::package::lang::alternate_links(config, main.id.as_str()).as_str(),
             c,
         )
         .await
@@ -2,6 +2,8 @@
 pub struct UrlMappings {
     pub redirects: ftd::Map<String>,
     pub endpoints: Vec<fastn_package::old_fastn::EndpointData>,
+    /// url -> name of the `fastn.sql-action` handling form submissions to it
+    pub sql_actions: ftd::Map<String>,
     // todo: add dynamic-urls
     // pub dynamic_urls: <some-type>
 }
@@ -10,10 +12,12 @@ impl UrlMappings {
     pub fn new(
         redirects: ftd::Map<String>,
         endpoints: Vec<fastn_package::old_fastn::EndpointData>,
+        sql_actions: ftd::Map<String>,
     ) -> UrlMappings {
         UrlMappings {
             redirects,
             endpoints,
+            sql_actions,
         }
     }
 }
@@ -37,6 +41,7 @@ impl UrlMappingsTemp {
     fn find_url_mappings(&self, body: &str) -> fastn_core::Result<UrlMappings> {
         let mut redirects: ftd::Map<String> = ftd::Map::new();
         let mut endpoints = vec![];
+        let mut sql_actions: ftd::Map<String> = ftd::Map::new();
         for line in body.lines() {
             let line = line.trim();
 
@@ -50,15 +55,32 @@ impl UrlMappingsTemp {
             //
             // localhost+proxy - http://127.0.0.1
             // /docs/* -> http+proxy://localhost:7999/*
+            //
+            // key=value options can follow the endpoint, e.g. limits of wasm endpoints
+            // /api/* -> wasm+proxy://api.wasm/* fuel=50000000 memory=64MB timeout=5s
 
             if line.contains("proxy") {
                 if let Some((first, second)) = line.split_once("->") {
                     let mountpoint = first.trim().to_string();
-                    let endpoint = second
-                        .trim()
+                    let mut parts = second.split_whitespace();
+                    let endpoint = parts
+                        .next()
+                        .unwrap_or_default()
                         .replace("http+proxy", "http")
                         .replace("localhost", "127.0.0.1")
                         .to_string();
+                    let options = parts
+                        .map(|option| match option.split_once('=') {
+                            Some((key, value)) => Ok((key.to_string(), value.to_string())),
+                            None => Err(fastn_core::Error::AssertError {
+                                message: format!(
+                                    "Endpoint option {} must be of the form key=value",
+                                    option
+                                ),
+                            }),
+                        })
+                        .collect::<fastn_core::Result<std::collections::BTreeMap<String, String>>>(
+                        )?;
 
                     if !mountpoint.ends_with('*') {
                         return Err(fastn_core::Error::AssertError {
@@ -72,15 +94,42 @@ impl UrlMappingsTemp {
                         });
                     }
 
+                    if endpoint.starts_with("wasm+proxy://") {
+                        fastn_ds::wasm::Limits::default()
+                            .with_options(&options)
+                            .map_err(|e| fastn_core::Error::AssertError {
+                                message: format!("Endpoint {}: {}", second.trim(), e),
+                            })?;
+                    }
+
                     endpoints.push(fastn_package::old_fastn::EndpointData {
                         endpoint: endpoint.trim().trim_end_matches('*').to_string(),
                         mountpoint: mountpoint.trim().trim_end_matches('*').to_string(),
                         user_id: None,
+                        options,
                     });
                 }
                 continue;
             }
 
+            // Form submissions to a `fastn.sql-action`
+            // /contact/submit/ -> sql+action://contact
+
+            if let Some((url, action)) = line.split_once("->").and_then(|(url, action)| {
+                action
+                    .trim()
+                    .strip_prefix(fastn_core::package::sql_action::SQL_ACTION_PREFIX)
+                    .map(|action| (url.trim(), action.trim()))
+            }) {
+                if action.is_empty() {
+                    return Err(fastn_core::Error::AssertError {
+                        message: format!("Sql action for {url} has no name"),
+                    });
+                }
+                sql_actions.insert(url.to_string(), action.to_string());
+                continue;
+            }
+
             // Supported Redirects Syntax under fastn.url-mappings
             // <some link>: <link to redirect>
             // <some link> -> <link to redirect>
@@ -98,7 +147,7 @@ impl UrlMappingsTemp {
                 Self::assert_and_insert_redirect(key, value, &mut redirects)?;
             }
         }
-        Ok(UrlMappings::new(redirects, endpoints))
+        Ok(UrlMappings::new(redirects, endpoints, sql_actions))
     }
 
     // Assert checks on redirects
@@ -160,6 +209,8 @@ mod tests {
                 /ftd/* -> http+proxy://fastn.com/ftd/*
                 /docs/ -> http://fastn.com/docs/
                 /slides/* -> http+proxy://localhost:7999/*
+                /api/* -> wasm+proxy://api.wasm/* memory=64MB timeout=5s
+                /contact/submit/ -> sql+action://contact
             "
         .to_string();
         let url_mappings_temp = crate::package::redirects::UrlMappingsTemp { body };
@@ -170,11 +221,22 @@ mod tests {
                 endpoint: "http://fastn.com/ftd/".to_string(),
                 mountpoint: "/ftd/".to_string(),
                 user_id: None,
+                options: Default::default(),
             },
             fastn_package::old_fastn::EndpointData {
                 endpoint: "http://127.0.0.1:7999/".to_string(),
                 mountpoint: "/slides/".to_string(),
                 user_id: None,
+                options: Default::default(),
+            },
+            fastn_package::old_fastn::EndpointData {
+                endpoint: "wasm+proxy://api.wasm/".to_string(),
+                mountpoint: "/api/".to_string(),
+                user_id: None,
+                options: std::collections::BTreeMap::from([
+                    ("memory".to_string(), "64MB".to_string()),
+                    ("timeout".to_string(), "5s".to_string()),
+                ]),
             },
         ];
 
@@ -189,6 +251,10 @@ mod tests {
 
         assert_eq!(url_mappings.endpoints.clone(), expected_endpoints);
         assert_eq!(url_mappings.redirects.clone(), expected_redirects);
+        assert_eq!(
+            url_mappings.sql_actions,
+            ftd::Map::from([("/contact/submit/".to_string(), "contact".to_string())])
+        );
     }
 
     #[test]
@@ -0,0 +1,362 @@
+/// `SqlAction` is a named, parameterised statement a form can be submitted to. It is declared
+/// in FASTN.ftd and mounted in `fastn.url-mappings`:
+///
+/// ```ftd
+/// -- fastn.url-mappings:
+///
+/// /contact/submit/ -> sql+action://contact
+///
+/// -- fastn.sql-action: contact
+/// required: name, email
+/// redirect: /thanks/
+///
+/// INSERT INTO contact (name, email, message) VALUES ($name, $email, $message);
+/// ```
+///
+/// `$name` arguments are bound to the fields of the POST body, `ftd.submit_form` JSON or a
+/// urlencoded html form, and the statement runs against `FASTN_DB_URL`. A POST from a page on
+/// another host, or without an `Origin` or `Referer` header, is rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SqlAction {
+    pub name: String,
+    pub statement: String,
+    /// where the browser goes once the statement ran, the page is reloaded if not set
+    pub redirect: Option<String>,
+    /// fields that can not be missing or empty
+    pub required: Vec<String>,
+}
+
+#[derive(Debug, serde::Deserialize, Clone)]
+pub struct SqlActionTemp {
+    pub name: String,
+    pub statement: String,
+    pub redirect: Option<String>,
+    pub required: Option<String>,
+}
+
+impl SqlActionTemp {
+    pub fn into_sql_action(self) -> SqlAction {
+        SqlAction {
+            name: self.name,
+            statement: self.statement,
+            redirect: self.redirect,
+            required: self
+                .required
+                .unwrap_or_default()
+                .split(',')
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .collect(),
+        }
+    }
+}
+
+pub const SQL_ACTION_PREFIX: &str = "sql+action://";
+const REQUIRED_MESSAGE: &str = "this field is required";
+
+impl SqlAction {
+    /// runs the statement for the request, the response is what `ftd.submit_form` expects:
+    /// `{"redirect": ..}`, `{"reload": true}` or `{"errors": {<field>: [..]}}`
+    pub async fn handle(
+        &self,
+        config: &fastn_core::Config,
+        req: &fastn_core::http::Request,
+    ) -> fastn_core::Result<fastn_core::http::Response> {
+        if req.method() != "POST" {
+            return Ok(actix_web::HttpResponse::MethodNotAllowed()
+                .insert_header(("Allow", "POST"))
+                .finish());
+        }
+
+        if !is_same_origin(req) {
+            return Ok(actix_web::HttpResponse::Forbidden().body("cross-origin sql action"));
+        }
+
+        let is_json = req.content_type() == Some(mime_guess::mime::APPLICATION_JSON);
+        let fields = if is_json {
+            req.json::<std::collections::HashMap<String, serde_json::Value>>()?
+        } else {
+            url::form_urlencoded::parse(req.body())
+                .map(|(k, v)| (k.to_string(), serde_json::Value::String(v.to_string())))
+                .collect()
+        };
+
+        let (query, args) =
+            fastn_core::library2022::processor::sql::extract_arguments(self.statement.as_str())?;
+
+        let errors = self.validate(&fields, &args);
+        if !errors.is_empty() {
+            return fastn_core::http::user_err(
+                errors,
+                match is_json {
+                    // `ftd.submit_form` only shows the errors of a successful response
+                    true => fastn_core::http::StatusCode::OK,
+                    false => fastn_core::http::StatusCode::UNPROCESSABLE_ENTITY,
+                },
+            );
+        }
+
+        let values = args
+            .iter()
+            .map(|a| fields.get(a).cloned().unwrap_or(serde_json::Value::Null))
+            .collect::<Vec<_>>();
+
+        let result = match config.ds.default_db_pool(false).await? {
+            fastn_ds::db::Pool::Sqlite(pool) => execute_sqlite(&pool, query.as_str(), values),
+            fastn_ds::db::Pool::Postgres(pool) => {
+                execute_pg(&pool, query.as_str(), args.as_slice(), values).await?
+            }
+        };
+
+        if let Err(errors) = result {
+            return fastn_core::http::user_err(
+                errors,
+                fastn_core::http::StatusCode::UNPROCESSABLE_ENTITY,
+            );
+        }
+
+        let redirect = self.redirect.clone();
+        if is_json {
+            let response = match redirect {
+                Some(redirect) => serde_json::json!({ "redirect": redirect }),
+                None => serde_json::json!({ "reload": true }),
+            };
+            return Ok(fastn_core::http::ok_with_content_type(
+                serde_json::to_vec(&response)?,
+                mime_guess::mime::APPLICATION_JSON,
+            ));
+        }
+
+        let redirect = redirect
+            .or_else(|| {
+                req.headers()
+                    .get("referer")
+                    .and_then(|v| v.to_str().ok())
+                    .map(ToString::to_string)
+            })
+            .unwrap_or_else(|| "/".to_string());
+        Ok(fastn_core::http::redirect_with_code(redirect, 303))
+    }
+
+    /// the missing required fields, and the arguments of the statement the request has no
+    /// field for
+    fn validate(
+        &self,
+        fields: &std::collections::HashMap<String, serde_json::Value>,
+        args: &[String],
+    ) -> Vec<(String, Vec<String>)> {
+        let mut errors = vec![];
+        for name in self.required.iter() {
+            match fields.get(name) {
+                None | Some(serde_json::Value::Null) => {}
+                Some(serde_json::Value::String(v)) if v.trim().is_empty() => {}
+                Some(_) => continue,
+            }
+            errors.push((name.to_string(), vec![REQUIRED_MESSAGE.to_string()]));
+        }
+
+        for name in args {
+            if !fields.contains_key(name) && !self.required.contains(name) {
+                errors.push((name.to_string(), vec![REQUIRED_MESSAGE.to_string()]));
+            }
+        }
+
+        errors
+    }
+}
+
+/// A browser sends `Origin`, or at least `Referer`, with a form POST. A page on another site
+/// must not be able to run the statement with the cookies of the visitor, so the host of either
+/// has to be the host of the request. A request with neither is rejected too, the statement
+/// changes the database and we can not tell where the request came from.
+fn is_same_origin(req: &fastn_core::http::Request) -> bool {
+    let source = match req
+        .headers()
+        .get("origin")
+        .or_else(|| req.headers().get("referer"))
+    {
+        Some(source) => source,
+        None => return false,
+    };
+
+    source
+        .to_str()
+        .ok()
+        .and_then(|v| url::Url::parse(v).ok())
+        .and_then(|u| {
+            u.host_str().map(|host| match u.port() {
+                Some(port) => format!("{host}:{port}"),
+                None => host.to_string(),
+            })
+        })
+        .map_or(false, |host| host == req.host())
+}
+
+/// `Err` is the error of the database, e.g. a violated constraint, shown on the form
+fn execute_sqlite(
+    pool: &fastn_ds::sqlite::Pool,
+    query: &str,
+    values: Vec<serde_json::Value>,
+) -> Result<(), Vec<(String, Vec<String>)>> {
+    let values = values
+        .into_iter()
+        .map(|v| match v {
+            serde_json::Value::Null => rusqlite::types::Value::Null,
+            serde_json::Value::Bool(b) => rusqlite::types::Value::Integer(b as i64),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => rusqlite::types::Value::Integer(i),
+                None => rusqlite::types::Value::Real(n.as_f64().unwrap_or_default()),
+            },
+            serde_json::Value::String(s) => rusqlite::types::Value::Text(s),
+            v => rusqlite::types::Value::Text(v.to_string()),
+        })
+        .collect::<Vec<_>>();
+
+    pool.get()
+        .and_then(|conn| {
+            let mut stmt = conn.prepare_cached(query)?;
+            stmt.execute(rusqlite::params_from_iter(values))
+        })
+        .map(|_| ())
+        .map_err(|e| {
+            tracing::error!(msg = "sql action failed", error = e.to_string());
+            vec![("__all__".to_string(), vec![e.to_string()])]
+        })
+}
+
+type PGData = dyn postgres_types::ToSql + Sync;
+
+async fn execute_pg(
+    pool: &deadpool_postgres::Pool,
+    query: &str,
+    args: &[String],
+    values: Vec<serde_json::Value>,
+) -> fastn_core::Result<Result<(), Vec<(String, Vec<String>)>>> {
+    let client = pool.get().await?;
+    let stmt = match client.prepare_cached(query).await {
+        Ok(stmt) => stmt,
+        Err(e) => {
+            return Err(fastn_core::Error::DatabaseError {
+                message: format!("failed to prepare sql action: {e}"),
+            })
+        }
+    };
+
+    let mut errors = vec![];
+    let mut params: Vec<Box<PGData>> = vec![];
+    for ((name, value), t) in args.iter().zip(values).zip(stmt.params()) {
+        match pg_value(t, value) {
+            Some(v) => params.push(v),
+            None => errors.push((name.to_string(), vec![format!("expected a {t}")])),
+        }
+    }
+    if !errors.is_empty() {
+        return Ok(Err(errors));
+    }
+
+    let params = params.iter().map(|v| v.as_ref()).collect::<Vec<_>>();
+    Ok(client
+        .execute(&stmt, params.as_slice())
+        .await
+        .map(|_| ())
+        .map_err(|e| {
+            tracing::error!(msg = "sql action failed", error = e.to_string());
+            vec![(
+                "__all__".to_string(),
+                vec![e
+                    .as_db_error()
+                    .map(|e| e.message().to_string())
+                    .unwrap_or_else(|| e.to_string())],
+            )]
+        }))
+}
+
+/// `None` if `value` can not be converted to `t`
+fn pg_value(t: &postgres_types::Type, value: serde_json::Value) -> Option<Box<PGData>> {
+    let text = match value {
+        serde_json::Value::Null => None,
+        serde_json::Value::String(s) => Some(s),
+        v => Some(v.to_string()),
+    };
+
+    let value: Box<PGData> = match t {
+        &postgres_types::Type::INT2 => Box::new(text.map(|v| v.parse::<i16>()).transpose().ok()?),
+        &postgres_types::Type::INT4 => Box::new(text.map(|v| v.parse::<i32>()).transpose().ok()?),
+        &postgres_types::Type::INT8 => Box::new(text.map(|v| v.parse::<i64>()).transpose().ok()?),
+        &postgres_types::Type::FLOAT4 => Box::new(text.map(|v| v.parse::<f32>()).transpose().ok()?),
+        &postgres_types::Type::FLOAT8 => Box::new(text.map(|v| v.parse::<f64>()).transpose().ok()?),
+        &postgres_types::Type::BOOL => Box::new(text.map(|v| v.parse::<bool>()).transpose().ok()?),
+        &postgres_types::Type::TEXT
+        | &postgres_types::Type::VARCHAR
+        | &postgres_types::Type::BPCHAR
+        | &postgres_types::Type::NAME => Box::new(text),
+        _ => return None,
+    };
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn validate() {
+        let action = super::SqlActionTemp {
+            name: "contact".to_string(),
+            statement:
+                "INSERT INTO contact (name, email, message) VALUES ($name, $email, $message);"
+                    .to_string(),
+            redirect: None,
+            required: Some("name, email".to_string()),
+        }
+        .into_sql_action();
+        assert_eq!(action.required, vec!["name", "email"]);
+
+        let fields = std::collections::HashMap::from([
+            ("name".to_string(), serde_json::json!(" ")),
+            ("message".to_string(), serde_json::json!("hi")),
+        ]);
+        let (query, args) =
+            fastn_core::library2022::processor::sql::extract_arguments(action.statement.as_str())
+                .unwrap();
+        assert_eq!(
+            query,
+            "INSERT INTO contact (name, email, message) VALUES ($1, $2, $3);"
+        );
+        assert_eq!(args, vec!["name", "email", "message"]);
+        assert_eq!(
+            action.validate(&fields, &args),
+            vec![
+                (
+                    "name".to_string(),
+                    vec![super::REQUIRED_MESSAGE.to_string()]
+                ),
+                (
+                    "email".to_string(),
+                    vec![super::REQUIRED_MESSAGE.to_string()]
+                ),
+            ]
+        );
+    }
+
+    #[track_caller]
+    fn same_origin(headers: &[(&str, &str)], expected: bool) {
+        let mut req = actix_web::test::TestRequest::post()
+            .uri("/contact/submit/")
+            .insert_header(("host", "example.com"));
+        for header in headers {
+            req = req.insert_header(*header);
+        }
+        let req = fastn_core::http::Request::from_actix(req.to_http_request(), Default::default());
+        assert_eq!(super::is_same_origin(&req), expected);
+    }
+
+    #[test]
+    fn is_same_origin() {
+        same_origin(&[], false);
+        same_origin(&[("origin", "https://example.com")], true);
+        same_origin(&[("origin", "https://evil.com")], false);
+        same_origin(&[("origin", "https://example.com:8000")], false);
+        same_origin(&[("origin", "null")], false);
+        same_origin(&[("referer", "https://example.com/contact/")], true);
+        same_origin(&[("referer", "https://evil.com/example.com")], false);
+    }
+}
@@ -0,0 +1,303 @@
+pub const PURGE_PATH: &str = "/-/fastn/processor-cache/purge/";
+/// headers of a processor that configure its caching, processors do not pass them on
+pub const CACHE_HEADERS: [&str; 2] = ["cache-ttl", "stale-while-revalidate"];
+const MAX_ENTRIES: usize = 10_000;
+
+/// `ProcessorCache` keeps the results of the `sql`, `pg`, `http` and `google-sheets` processors
+/// between requests. It is opt-in, only a processor with a `cache-ttl` header is cached:
+///
+/// ```ftd
+/// -- person list people:
+/// $processor$: processor.sql
+/// cache-ttl: 5m
+/// stale-while-revalidate: 1h
+///
+/// SELECT * FROM people;
+/// ```
+///
+/// Entries are keyed by the processor and everything its result depends on, the query or url
+/// with the resolved arguments, and the cookies for `http`. An entry is fresh for `cache-ttl`.
+/// For `stale-while-revalidate` after that, one request runs the processor again while every
+/// other request is served the stale entry, and the stale entry is also served if that run
+/// fails.
+///
+/// `POST /-/fastn/processor-cache/purge/` empties the cache, see `purge_route`.
+#[derive(Debug, Clone, Default)]
+pub struct ProcessorCache {
+    entries: std::sync::Arc<scc::HashMap<String, Entry>>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    value: serde_json::Value,
+    created: std::time::Instant,
+    policy: Policy,
+    /// a request is running the processor again
+    refreshing: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Policy {
+    pub ttl: std::time::Duration,
+    pub stale_while_revalidate: std::time::Duration,
+}
+
+impl Policy {
+    /// `None` if the processor does not have a `cache-ttl` header
+    pub fn from_headers(
+        headers: &ftd_ast::HeaderValues,
+        doc_name: &str,
+        line_number: usize,
+    ) -> ftd::interpreter::Result<Option<Policy>> {
+        let duration = |key: &str| duration_header(headers, key, doc_name, line_number);
+
+        Ok(match duration(CACHE_HEADERS[0])? {
+            Some(ttl) => Some(Policy {
+                ttl,
+                stale_while_revalidate: duration(CACHE_HEADERS[1])?.unwrap_or_default(),
+            }),
+            None => None,
+        })
+    }
+}
+
+/// the duration in the header `key` of a processor, `None` if there is no such header
+pub(crate) fn duration_header(
+    headers: &ftd_ast::HeaderValues,
+    key: &str,
+    doc_name: &str,
+    line_number: usize,
+) -> ftd::interpreter::Result<Option<std::time::Duration>> {
+    match headers.get_optional_string_by_key(key, doc_name, line_number)? {
+        Some(v) => match fastn_ds::utils::parse_duration(v.as_str()) {
+            Some(d) => Ok(Some(d)),
+            None => ftd::interpreter::utils::e2(
+                format!("{key}: expected a duration like `30s`, `5m` or `1h`, found {v}"),
+                doc_name,
+                line_number,
+            ),
+        },
+        None => Ok(None),
+    }
+}
+
+enum Lookup {
+    Hit(serde_json::Value),
+    /// this request refreshes the entry, the value is served if that fails
+    Refresh(serde_json::Value),
+    Miss,
+}
+
+impl ProcessorCache {
+    /// `parts` are everything the result of `processor` depends on
+    pub fn key(processor: &str, parts: &[&str]) -> String {
+        format!(
+            "{processor}:{}",
+            fastn_core::utils::generate_hash(parts.join("\0"))
+        )
+    }
+
+    /// the cached result for `key`, or the result of `fetch`. `fetch` always runs if `policy` is
+    /// `None`.
+    pub async fn get_or_fetch<T, E, F, Fut>(
+        &self,
+        key: String,
+        policy: Option<Policy>,
+        fetch: F,
+    ) -> Result<T, E>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned,
+        E: std::fmt::Debug,
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<T, E>>,
+    {
+        let policy = match policy {
+            Some(policy) => policy,
+            None => return fetch().await,
+        };
+
+        let stale = match self.lookup(key.as_str()) {
+            Lookup::Hit(value) => match serde_json::from_value(value) {
+                Ok(v) => return Ok(v),
+                Err(_) => None,
+            },
+            Lookup::Refresh(value) => Some(value),
+            Lookup::Miss => None,
+        };
+
+        match fetch().await {
+            Ok(v) => {
+                if let Ok(value) = serde_json::to_value(&v) {
+                    self.insert(key, value, policy);
+                }
+                Ok(v)
+            }
+            Err(e) => {
+                if let Some(Ok(v)) = stale.map(serde_json::from_value) {
+                    tracing::warn!(msg = "serving stale processor result", key = key, error = ?e);
+                    if let Some(mut entry) = self.entries.get(&key) {
+                        entry.get_mut().refreshing = false;
+                    }
+                    return Ok(v);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    fn lookup(&self, key: &str) -> Lookup {
+        let mut entry = match self.entries.get(key) {
+            Some(entry) => entry,
+            None => return Lookup::Miss,
+        };
+        let entry = entry.get_mut();
+
+        let age = entry.created.elapsed();
+        if age < entry.policy.ttl {
+            return Lookup::Hit(entry.value.clone());
+        }
+        if age >= entry.policy.ttl + entry.policy.stale_while_revalidate {
+            return Lookup::Miss;
+        }
+        if entry.refreshing {
+            return Lookup::Hit(entry.value.clone());
+        }
+
+        entry.refreshing = true;
+        Lookup::Refresh(entry.value.clone())
+    }
+
+    fn insert(&self, key: String, value: serde_json::Value, policy: Policy) {
+        if self.entries.len() >= MAX_ENTRIES {
+            self.entries.retain(|_, e| {
+                e.created.elapsed() < e.policy.ttl + e.policy.stale_while_revalidate
+            });
+            if self.entries.len() >= MAX_ENTRIES {
+                tracing::warn!(msg = "processor cache is full, not caching", key = key);
+                return;
+            }
+        }
+
+        fastn_ds::insert_or_update(
+            &self.entries,
+            key,
+            Entry {
+                value,
+                created: std::time::Instant::now(),
+                policy,
+                refreshing: false,
+            },
+        );
+    }
+
+    /// removes the entries of `processor`, or all entries, and returns how many were removed
+    pub fn purge(&self, processor: Option<&str>) -> usize {
+        let before = self.entries.len();
+        match processor {
+            Some(processor) => {
+                let prefix = format!("{processor}:");
+                self.entries.retain(|k, _| !k.starts_with(prefix.as_str()));
+            }
+            None => self.entries.clear(),
+        }
+        before.saturating_sub(self.entries.len())
+    }
+}
+
+/// `POST /-/fastn/processor-cache/purge/` purges the cache, `?processor=http` only the entries
+/// of one processor (`sqlite`, `pg`, `http` or `google-sheets`). The request needs the header
+/// `Authorization: Bearer <FASTN_PROCESSOR_CACHE_TOKEN>`, the route does not exist if the token
+/// is not set.
+pub async fn purge_route(
+    config: &fastn_core::Config,
+    req: &fastn_core::http::Request,
+) -> Option<fastn_core::Result<fastn_core::http::Response>> {
+    if req.path() != PURGE_PATH {
+        return None;
+    }
+    let token = config.ds.env("FASTN_PROCESSOR_CACHE_TOKEN").await.ok()?;
+
+    if req.method() != "POST" {
+        return Some(Ok(actix_web::HttpResponse::MethodNotAllowed()
+            .insert_header(("Allow", "POST"))
+            .finish()));
+    }
+
+    let authorization = req
+        .headers()
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if authorization != Some(token.as_str()) {
+        return Some(Ok(fastn_core::unauthorised!(
+            "invalid token for {PURGE_PATH}"
+        )));
+    }
+
+    let processor = req.query().get("processor").and_then(|v| v.as_str());
+    let purged = config.processor_cache.purge(processor);
+    tracing::info!(
+        msg = "purged processor cache",
+        processor = processor,
+        purged
+    );
+
+    Some(
+        fastn_core::http::api_ok(serde_json::json!({ "purged": purged }))
+            .map_err(fastn_core::Error::from),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_duration() {
+        let d = std::time::Duration::from_secs;
+        assert_eq!(fastn_ds::utils::parse_duration("30"), Some(d(30)));
+        assert_eq!(fastn_ds::utils::parse_duration("30s"), Some(d(30)));
+        assert_eq!(fastn_ds::utils::parse_duration("5m"), Some(d(300)));
+        assert_eq!(fastn_ds::utils::parse_duration(" 1h "), Some(d(3600)));
+        assert_eq!(fastn_ds::utils::parse_duration("1d"), Some(d(86400)));
+        assert_eq!(
+            fastn_ds::utils::parse_duration("250ms"),
+            Some(std::time::Duration::from_millis(250))
+        );
+        assert_eq!(fastn_ds::utils::parse_duration("1w"), None);
+        assert_eq!(fastn_ds::utils::parse_duration("m"), None);
+        // overflows
+        assert_eq!(
+            fastn_ds::utils::parse_duration("18446744073709551615d"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn get_or_fetch() {
+        let cache = super::ProcessorCache::default();
+        let policy = Some(super::Policy {
+            ttl: std::time::Duration::from_secs(60),
+            stale_while_revalidate: Default::default(),
+        });
+        let key = super::ProcessorCache::key("sql", &["SELECT 1"]);
+
+        let v: Result<i32, ()> = cache
+            .get_or_fetch(key.clone(), policy, || async { Ok(1) })
+            .await;
+        assert_eq!(v, Ok(1));
+        // fresh, the second fetch does not run
+        let v: Result<i32, ()> = cache
+            .get_or_fetch(key.clone(), policy, || async { Ok(2) })
+            .await;
+        assert_eq!(v, Ok(1));
+        // not cached without a policy
+        let v: Result<i32, ()> = cache
+            .get_or_fetch(key.clone(), None, || async { Ok(3) })
+            .await;
+        assert_eq!(v, Ok(3));
+
+        assert_eq!(cache.purge(Some("http")), 0);
+        assert_eq!(cache.purge(Some("sql")), 1);
+        let v: Result<i32, ()> = cache.get_or_fetch(key, policy, || async { Ok(4) }).await;
+        assert_eq!(v, Ok(4));
+    }
+}
@@ -16,6 +16,100 @@ pub struct DynamicUrls {
     pub sections: Vec<fastn_core::sitemap::section::Section>,
 }
 
+pub const BUILD_PARAMS: &str = "build-params";
+pub const BUILD_PARAMS_FROM: &str = "build-params-from";
+
+/// A dynamic url that `fastn build` can render to static files, because it lists the values of
+/// its path parameters, either inline:
+///
+/// ```ftd
+/// - Person:
+///   url: /person/<string:name>/<integer:age>/
+///   document: person.ftd
+///   build-params: name=arpita&age=28; name=amitu&age=30
+/// ```
+///
+/// or with `build-params-from: person-params.ftd`, a document that defines a `build-params`
+/// record list, which can come from any processor (`sql`, `pg`, `http` etc).
+#[derive(Debug, Clone, PartialEq)]
+pub struct BuildUrl {
+    pub document: String,
+    pub path_parameters: Vec<fastn_core::sitemap::PathParams>,
+    pub extra_data: std::collections::BTreeMap<String, String>,
+}
+
+impl BuildUrl {
+    /// parameter values given with `build-params`, one map per url
+    pub fn inline_params(&self) -> Vec<std::collections::BTreeMap<String, String>> {
+        self.extra_data
+            .get(BUILD_PARAMS)
+            .map(|v| {
+                v.split(';')
+                    .map(str::trim)
+                    .filter(|v| !v.is_empty())
+                    .map(|v| {
+                        url::form_urlencoded::parse(v.as_bytes())
+                            .into_owned()
+                            .collect()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn params_document(&self) -> Option<&str> {
+        self.extra_data.get(BUILD_PARAMS_FROM).map(|v| v.as_str())
+    }
+
+    /// `/person/<string:name>/` and `{name: arpita}` -> `/person/arpita/`
+    pub fn path(
+        &self,
+        params: &std::collections::BTreeMap<String, String>,
+    ) -> fastn_core::Result<String> {
+        let mut parts = vec![];
+        for param in self.path_parameters.iter() {
+            match param {
+                fastn_core::sitemap::PathParams::ValueParam { value, .. } => {
+                    parts.push(value.to_string())
+                }
+                fastn_core::sitemap::PathParams::NamedParm { name, .. } => {
+                    match params.get(name).and_then(|v| path_segment(v.trim())) {
+                        Some(segment) => parts.push(segment),
+                        None => {
+                            return fastn_core::usage_error(format!(
+                                "{}: invalid or missing value for `{}` in {:?}",
+                                self.document, name, params
+                            ))
+                        }
+                    }
+                }
+            }
+        }
+
+        let path = format!("/{}/", parts.join("/"));
+        // make sure the values are of the declared types
+        if !fastn_core::sitemap::utils::url_match(path.as_str(), &self.path_parameters)?.0 {
+            return fastn_core::usage_error(format!(
+                "{}: {} does not match the dynamic url",
+                self.document, path
+            ));
+        }
+
+        Ok(path)
+    }
+}
+
+/// `value` percent-encoded as one segment of a url path, `None` for values that are not a single
+/// segment, like `a/b` or `..`, as the page would be written outside of its folder in `.build`
+fn path_segment(value: &str) -> Option<String> {
+    if value.is_empty() || value == "." || value == ".." || value.contains(['/', '\\']) {
+        return None;
+    }
+    let mut url = url::Url::parse("http://localhost/").ok()?;
+    url.path_segments_mut().ok()?.pop_if_empty().push(value);
+    Some(url.path().trim_start_matches('/').to_string())
+}
+
 impl DynamicUrls {
     pub fn parse(
         global_ids: &std::collections::HashMap<String, String>,
@@ -107,6 +201,58 @@ impl DynamicUrls {
         false
     }
 
+    /// all the urls which declare `build-params` or `build-params-from`
+    pub fn build_urls(&self) -> Vec<BuildUrl> {
+        fn push(
+            urls: &mut Vec<BuildUrl>,
+            document: &Option<String>,
+            path_parameters: &[fastn_core::sitemap::PathParams],
+            extra_data: &std::collections::BTreeMap<String, String>,
+        ) {
+            if let Some(document) = document {
+                if !path_parameters.is_empty()
+                    && (extra_data.contains_key(BUILD_PARAMS)
+                        || extra_data.contains_key(BUILD_PARAMS_FROM))
+                {
+                    urls.push(BuildUrl {
+                        document: document.to_string(),
+                        path_parameters: path_parameters.to_vec(),
+                        extra_data: extra_data.clone(),
+                    });
+                }
+            }
+        }
+
+        fn from_toc(urls: &mut Vec<BuildUrl>, toc: &fastn_core::sitemap::toc::TocItem) {
+            push(urls, &toc.document, &toc.path_parameters, &toc.extra_data);
+            for child in toc.children.iter() {
+                from_toc(urls, child);
+            }
+        }
+
+        let mut urls = vec![];
+        for section in self.sections.iter() {
+            push(
+                &mut urls,
+                &section.document,
+                &section.path_parameters,
+                &section.extra_data,
+            );
+            for sub_section in section.subsections.iter() {
+                push(
+                    &mut urls,
+                    &sub_section.document,
+                    &sub_section.path_parameters,
+                    &sub_section.extra_data,
+                );
+                for toc in sub_section.toc.iter() {
+                    from_toc(&mut urls, toc);
+                }
+            }
+        }
+        urls
+    }
+
     #[tracing::instrument(name = "dynamic-urls-resolve-document", skip(self))]
     pub fn resolve_document<'a>(&'a self, path: &str) -> fastn_core::Result<ResolveDocOutput> {
         fn resolve_in_toc(
@@ -217,6 +363,49 @@ impl DynamicUrls {
 
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn build_url_path() {
+        let build_url = super::BuildUrl {
+            document: "person.ftd".to_string(),
+            path_parameters: vec![
+                fastn_core::sitemap::PathParams::value(0, "person".to_string()),
+                fastn_core::sitemap::PathParams::named(1, "name".to_string(), "string".to_string()),
+                fastn_core::sitemap::PathParams::named(2, "age".to_string(), "integer".to_string()),
+            ],
+            extra_data: Default::default(),
+        };
+        let path = |name: &str, age: &str| {
+            build_url
+                .path(
+                    &[("name", name), ("age", age)]
+                        .into_iter()
+                        .map(|(k, v)| (k.to_string(), v.to_string()))
+                        .collect(),
+                )
+                .ok()
+        };
+
+        assert_eq!(path("arpita", "28"), Some("/person/arpita/28/".to_string()));
+        assert_eq!(
+            path(" arpita ", "28"),
+            Some("/person/arpita/28/".to_string())
+        );
+        assert_eq!(
+            path("a b?c#d%e", "28"),
+            Some("/person/a%20b%3Fc%23d%25e/28/".to_string())
+        );
+        assert_eq!(
+            path("अमित", "28"),
+            Some("/person/%E0%A4%85%E0%A4%AE%E0%A4%BF%E0%A4%A4/28/".to_string())
+        );
+        // these would be written outside of `.build/person/`
+        assert_eq!(path("..", "28"), None);
+        assert_eq!(path(".", "28"), None);
+        assert_eq!(path("a/b", "28"), None);
+        assert_eq!(path("..\\index", "28"), None);
+        assert_eq!(path("", "28"), None);
+        assert_eq!(path("arpita", "old"), None);
+    }
 
     #[test]
     fn parse_dynamic_urls() {
@@ -345,4 +534,51 @@ mod tests {
         });
         assert_eq!(left, right)
     }
+
+    #[test]
+    fn build_urls() {
+        let dynamic_urls = fastn_core::sitemap::DynamicUrls::parse(
+            &std::collections::HashMap::new(),
+            "abrark.com",
+            r#"
+# Dynamic Urls Section
+- Person
+  url: /person/<string:name>/<integer:age>/
+  document: person.ftd
+  build-params: name=arpita&age=28; name=amitu&age=30
+- Book
+  url: /book/<string:slug>/
+  document: book.ftd
+  build-params-from: books.ftd
+- Order
+  url: /order/<integer:id>/
+  document: order.ftd
+"#,
+        )
+        .unwrap();
+
+        let urls = dynamic_urls.build_urls();
+        assert_eq!(urls.len(), 2);
+        assert_eq!(urls[1].params_document(), Some("books.ftd"));
+
+        let paths = urls[0]
+            .inline_params()
+            .iter()
+            .map(|v| urls[0].path(v).unwrap())
+            .collect::<Vec<String>>();
+        assert_eq!(paths, vec!["/person/arpita/28/", "/person/amitu/30/"]);
+
+        assert!(urls[0]
+            .path(&std::collections::BTreeMap::from([
+                ("name".to_string(), "arpita".to_string()),
+                ("age".to_string(), "twenty".to_string())
+            ]))
+            .is_err());
+        assert!(urls[0]
+            .path(&std::collections::BTreeMap::from([(
+                "name".to_string(),
+                "arpita".to_string()
+            )]))
+            .is_err());
+    }
 }
@@ -15,6 +15,7 @@
 /// the table od content (TOC).
 pub mod dynamic_urls;
 pub mod section;
+pub mod seo;
 pub mod toc;
 pub mod utils;
 
@@ -0,0 +1,683 @@
+//! `sitemap.xml`, `robots.txt` and the optional feed, generated from `fastn.sitemap` by
+//! `fastn build` (written to `.build`) and `fastn serve` (served on the fly). A file with the
+//! same name in the package always wins over the generated one.
+//!
+//! ```ftd
+//! -- fastn.robots:
+//!
+//! User-agent: *
+//! Disallow: /drafts/
+//!
+//! -- fastn.feed: /blog/
+//! title: My Blog
+//! format: rss
+//! ```
+
+pub const SITEMAP_XML: &str = "sitemap.xml";
+pub const ROBOTS_TXT: &str = "robots.txt";
+pub const FEED_XML: &str = "feed.xml";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FeedFormat {
+    Atom,
+    Rss,
+}
+
+impl std::str::FromStr for FeedFormat {
+    type Err = fastn_core::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "atom" => Ok(FeedFormat::Atom),
+            "rss" => Ok(FeedFormat::Rss),
+            t => Err(fastn_core::Error::PackageError {
+                message: format!(
+                    "fastn.feed: unknown format `{}`, expected `atom` or `rss`",
+                    t
+                ),
+            }),
+        }
+    }
+}
+
+/// `-- fastn.feed: <section-id>` publishes the pages of that sitemap section as a feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct Feed {
+    pub section: String,
+    pub title: Option<String>,
+    pub format: FeedFormat,
+}
+
+#[derive(Debug, serde::Deserialize, Clone)]
+pub struct FeedTemp {
+    pub section: String,
+    pub title: Option<String>,
+    pub format: String,
+}
+
+impl FeedTemp {
+    pub fn into_feed(self) -> fastn_core::Result<Feed> {
+        Ok(Feed {
+            section: self.section,
+            title: self.title,
+            format: self.format.parse()?,
+        })
+    }
+}
+
+#[derive(Debug, serde::Deserialize, Clone)]
+pub struct RobotsTemp {
+    #[serde(rename = "robots-body")]
+    pub body: String,
+}
+
+/// A public page of the sitemap, with what we know about it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// absolute url of the page
+    pub loc: String,
+    pub lastmod: Option<chrono::DateTime<chrono::Utc>>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Page of the sitemap, before we look at its document
+#[derive(Debug, Clone)]
+struct Page<'a> {
+    id: &'a str,
+    title: Option<&'a str>,
+    /// id of the section the page belongs to
+    section: &'a str,
+    file_location: Option<&'a fastn_ds::Path>,
+}
+
+/// Names of the files this module can generate for the package, `robots.txt` is only generated
+/// when it is configured or when there is a `sitemap.xml` to point to.
+pub fn generated_files(package: &fastn_core::Package) -> Vec<&'static str> {
+    let mut files = vec![];
+    if package.sitemap.is_some() {
+        files.push(SITEMAP_XML);
+    }
+    if package.sitemap.is_some() || package.robots.is_some() {
+        files.push(ROBOTS_TXT);
+    }
+    if package.sitemap.is_some() && package.feed.is_some() {
+        files.push(FEED_XML);
+    }
+    files
+}
+
+/// how long `SeoCache` keeps a generated file, so pages added without `--watch` show up
+pub const SEO_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// `SeoCache` keeps the files generated by `fastn serve` for `SEO_CACHE_TTL`, generating them
+/// runs `git` for every page. `fastn serve --watch` clears it when a file changes.
+#[derive(Debug, Clone, Default)]
+pub struct SeoCache {
+    files: std::sync::Arc<scc::HashMap<String, (std::time::Instant, (mime_guess::Mime, String))>>,
+}
+
+impl SeoCache {
+    /// `generate`, once per file and `SEO_CACHE_TTL`
+    pub async fn get(
+        &self,
+        config: &fastn_core::Config,
+        name: &str,
+    ) -> fastn_core::Result<Option<(mime_guess::Mime, String)>> {
+        if let Some(file) = self.files.get(name) {
+            let (generated_on, file) = file.get();
+            if generated_on.elapsed() < SEO_CACHE_TTL {
+                return Ok(Some(file.clone()));
+            }
+        }
+        let file = generate(config, name).await?;
+        match file {
+            Some(ref file) => fastn_ds::insert_or_update(
+                &self.files,
+                name.to_string(),
+                (std::time::Instant::now(), file.clone()),
+            ),
+            None => {
+                self.files.remove(name);
+            }
+        }
+        Ok(file)
+    }
+
+    pub fn clear(&self) {
+        self.files.clear();
+    }
+}
+
+/// Content type and content of the generated file `name`, `None` if the package does not
+/// generate it.
+pub async fn generate(
+    config: &fastn_core::Config,
+    name: &str,
+) -> fastn_core::Result<Option<(mime_guess::Mime, String)>> {
+    if !generated_files(&config.package).iter().any(|f| *f == name) {
+        return Ok(None);
+    }
+
+    let base = base_url(&config.package);
+    let sitemap = match config.package.sitemap {
+        Some(ref sitemap) => sitemap,
+        None if name == ROBOTS_TXT => {
+            return Ok(Some((
+                mime_guess::mime::TEXT_PLAIN,
+                robots_txt(config.package.robots.as_deref(), None),
+            )))
+        }
+        None => return Ok(None),
+    };
+
+    Ok(Some(match name {
+        SITEMAP_XML => {
+            let mut entries = vec![];
+            for page in pages(sitemap) {
+                entries.push(Entry {
+                    loc: page_url(base.as_str(), page.id),
+                    lastmod: lastmod(config, page.file_location).await,
+                    title: None,
+                    description: None,
+                });
+            }
+            (mime_guess::mime::TEXT_XML, sitemap_xml(&entries))
+        }
+        ROBOTS_TXT => (
+            mime_guess::mime::TEXT_PLAIN,
+            robots_txt(
+                config.package.robots.as_deref(),
+                Some(format!("{}/{}", base, SITEMAP_XML).as_str()),
+            ),
+        ),
+        FEED_XML => {
+            // generated_files() made sure the feed is configured
+            let feed = config.package.feed.as_ref().unwrap();
+            let section = feed.section.trim_matches('/');
+            let mut entries = vec![];
+            for page in pages(sitemap)
+                .into_iter()
+                .filter(|p| p.section.trim_matches('/') == section && p.id != p.section)
+            {
+                let (title, description) = match page.file_location {
+                    Some(file_location) => document_meta(config, file_location).await,
+                    None => (None, None),
+                };
+                entries.push(Entry {
+                    loc: page_url(base.as_str(), page.id),
+                    lastmod: lastmod(config, page.file_location).await,
+                    title: title.or_else(|| page.title.map(ToString::to_string)),
+                    description,
+                });
+            }
+            // newest first
+            entries.sort_by(|a, b| b.lastmod.cmp(&a.lastmod));
+
+            let title = feed
+                .title
+                .clone()
+                .unwrap_or_else(|| config.package.name.to_string());
+            let links = FeedLinks {
+                feed: format!("{}/{}", base, FEED_XML),
+                site: page_url(base.as_str(), feed.section.as_str()),
+            };
+            match feed.format {
+                FeedFormat::Atom => (
+                    "application/atom+xml".parse().unwrap(),
+                    atom_xml(title.as_str(), &links, &entries),
+                ),
+                FeedFormat::Rss => (
+                    "application/rss+xml".parse().unwrap(),
+                    rss_xml(title.as_str(), &links, &entries),
+                ),
+            }
+        }
+        _ => return Ok(None),
+    }))
+}
+
+/// `canonical-url` of the package, or `https://<package-name>`, without the trailing slash
+pub(crate) fn base_url(package: &fastn_core::Package) -> String {
+    let base = package
+        .canonical_url
+        .clone()
+        .unwrap_or_else(|| package.name.to_string());
+    let base = base.trim_end_matches('/');
+    if base.starts_with("http://") || base.starts_with("https://") {
+        base.to_string()
+    } else {
+        format!("https://{}", base)
+    }
+}
+
+fn page_url(base: &str, id: &str) -> String {
+    let id = id.trim_matches('/');
+    if id.is_empty() {
+        format!("{}/", base)
+    } else if id.rsplit('/').next().map_or(false, |v| v.contains('.')) {
+        format!("{}/{}", base, id)
+    } else {
+        format!("{}/{}/", base, id)
+    }
+}
+
+/// Pages that can be crawled: no external links, no dynamic urls, no dependency documents and
+/// nothing that is skipped or needs a reader.
+fn is_public(
+    id: &str,
+    skip: bool,
+    readers: &[String],
+    path_parameters: &[fastn_core::sitemap::PathParams],
+) -> bool {
+    !skip
+        && readers.is_empty()
+        && !id.starts_with("http://")
+        && !id.starts_with("https://")
+        && !id.trim_start_matches('/').starts_with("-/")
+        && !path_parameters.iter().any(|p| p.is_named_param())
+}
+
+fn pages(sitemap: &fastn_core::sitemap::Sitemap) -> Vec<Page> {
+    let mut pages = vec![];
+    for section in sitemap.sections.iter() {
+        if is_public(
+            section.id.as_str(),
+            section.skip,
+            &section.readers,
+            &section.path_parameters,
+        ) {
+            pages.push(Page {
+                id: section.id.as_str(),
+                title: section.title.as_deref(),
+                section: section.id.as_str(),
+                file_location: section.file_location.as_ref(),
+            });
+        }
+        for subsection in section.subsections.iter() {
+            if let Some(ref id) = subsection.id {
+                if subsection.visible
+                    && is_public(
+                        id.as_str(),
+                        subsection.skip,
+                        &subsection.readers,
+                        &subsection.path_parameters,
+                    )
+                {
+                    pages.push(Page {
+                        id: id.as_str(),
+                        title: subsection.title.as_deref(),
+                        section: section.id.as_str(),
+                        file_location: subsection.file_location.as_ref(),
+                    });
+                }
+            }
+            for toc in subsection.toc.iter() {
+                toc_pages(toc, section.id.as_str(), &mut pages);
+            }
+        }
+    }
+
+    // the same document can be listed more than once, e.g. as a section and as its first toc
+    let mut seen = std::collections::HashSet::new();
+    pages.retain(|p| seen.insert(p.id.trim_matches('/')));
+    return pages;
+
+    fn toc_pages<'a>(
+        toc: &'a fastn_core::sitemap::toc::TocItem,
+        section: &'a str,
+        pages: &mut Vec<Page<'a>>,
+    ) {
+        if is_public(
+            toc.id.as_str(),
+            toc.skip,
+            &toc.readers,
+            &toc.path_parameters,
+        ) {
+            pages.push(Page {
+                id: toc.id.as_str(),
+                title: toc.title.as_deref(),
+                section,
+                file_location: toc.file_location.as_ref(),
+            });
+        }
+        for child in toc.children.iter() {
+            toc_pages(child, section, pages);
+        }
+    }
+}
+
+/// Last commit touching the file if the package is in git, else the modification time. Left out
+/// in `--test` mode so the output stays stable.
+async fn lastmod(
+    config: &fastn_core::Config,
+    file_location: Option<&fastn_ds::Path>,
+) -> Option<chrono::DateTime<chrono::Utc>> {
+    let file_location = file_location?;
+    if fastn_core::utils::is_test() {
+        return None;
+    }
+
+    let git = tokio::process::Command::new("git")
+        .arg("log")
+        .arg("-1")
+        .arg("--format=%cI")
+        .arg("--")
+        .arg(file_location.to_string())
+        .current_dir(config.ds.root().to_string())
+        .stderr(std::process::Stdio::null())
+        .output()
+        .await
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| {
+            chrono::DateTime::parse_from_rfc3339(String::from_utf8_lossy(&output.stdout).trim())
+                .ok()
+        });
+
+    match git {
+        Some(date) => Some(date.with_timezone(&chrono::Utc)),
+        None => config
+            .ds
+            .modified(file_location)
+            .await
+            .ok()
+            .map(chrono::DateTime::<chrono::Utc>::from),
+    }
+}
+
+/// `title` and `description` of the first top level component of the document that takes them,
+/// that is `ftd.document` or a page component wrapping it.
+async fn document_meta(
+    config: &fastn_core::Config,
+    file_location: &fastn_ds::Path,
+) -> (Option<String>, Option<String>) {
+    let document = match fastn_core::get_file(
+        &config.ds,
+        config.package.name.to_string(),
+        file_location,
+        &config.ds.root(),
+    )
+    .await
+    {
+        Ok(fastn_core::File::Ftd(document)) => document,
+        _ => return (None, None),
+    };
+
+    let interpreted = match fastn_core::commands::check::interpret_document(config, &document).await
+    {
+        Ok(interpreted) => interpreted,
+        Err(e) => {
+            tracing::warn!(
+                msg = "feed: failed to interpret",
+                doc = e.doc_id,
+                e = e.message
+            );
+            return (None, None);
+        }
+    };
+
+    let doc = interpreted.tdoc();
+    for component in interpreted.tree.iter() {
+        let arguments = match component.get_interpreter_property_value_of_all_arguments(&doc) {
+            Ok(arguments) => arguments,
+            Err(_) => continue,
+        };
+        let value = |name: &str| {
+            let value = arguments.get(name)?.clone().resolve(&doc, 0).ok()?;
+            match value {
+                ftd::interpreter::Value::Optional { data, .. } => (*data)?,
+                v => v,
+            }
+            .string(interpreted.name.as_str(), 0)
+            .ok()
+        };
+        let (title, description) = (value("title"), value("description"));
+        if title.is_some() || description.is_some() {
+            return (title, description);
+        }
+    }
+
+    (None, None)
+}
+
+pub fn sitemap_xml(entries: &[Entry]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for entry in entries {
+        xml.push_str("  <url>\n");
+        xml.push_str(format!("    <loc>{}</loc>\n", escape(entry.loc.as_str())).as_str());
+        if let Some(lastmod) = entry.lastmod {
+            xml.push_str(format!("    <lastmod>{}</lastmod>\n", rfc3339(lastmod)).as_str());
+        }
+        xml.push_str("  </url>\n");
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+/// the configured `fastn.robots` body (everything is allowed by default), pointing crawlers to
+/// `sitemap` unless the body already has a `Sitemap:` line.
+pub fn robots_txt(body: Option<&str>, sitemap: Option<&str>) -> String {
+    let mut robots = body
+        .map(|b| b.trim().to_string())
+        .unwrap_or_else(|| "User-agent: *\nAllow: /".to_string());
+    robots.push('\n');
+    if let Some(sitemap) = sitemap {
+        if !robots
+            .lines()
+            .any(|l| l.trim().to_lowercase().starts_with("sitemap:"))
+        {
+            robots.push_str(format!("\nSitemap: {}\n", sitemap).as_str());
+        }
+    }
+    robots
+}
+
+pub struct FeedLinks {
+    /// url of the feed itself
+    pub feed: String,
+    /// url of the section the feed is for
+    pub site: String,
+}
+
+pub fn atom_xml(title: &str, links: &FeedLinks, entries: &[Entry]) -> String {
+    let updated = entries
+        .iter()
+        .filter_map(|e| e.lastmod)
+        .max()
+        .unwrap_or(chrono::DateTime::<chrono::Utc>::UNIX_EPOCH);
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\">\n",
+    );
+    xml.push_str(format!("  <title>{}</title>\n", escape(title)).as_str());
+    xml.push_str(format!("  <id>{}</id>\n", escape(links.site.as_str())).as_str());
+    xml.push_str(
+        format!(
+            "  <link href=\"{}\" rel=\"self\"/>\n",
+            escape(links.feed.as_str())
+        )
+        .as_str(),
+    );
+    xml.push_str(format!("  <link href=\"{}\"/>\n", escape(links.site.as_str())).as_str());
+    xml.push_str(format!("  <updated>{}</updated>\n", rfc3339(updated)).as_str());
+    for entry in entries {
+        xml.push_str("  <entry>\n");
+        xml.push_str(
+            format!(
+                "    <title>{}</title>\n",
+                escape(entry.title.as_deref().unwrap_or(entry.loc.as_str()))
+            )
+            .as_str(),
+        );
+        xml.push_str(format!("    <id>{}</id>\n", escape(entry.loc.as_str())).as_str());
+        xml.push_str(format!("    <link href=\"{}\"/>\n", escape(entry.loc.as_str())).as_str());
+        xml.push_str(
+            format!(
+                "    <updated>{}</updated>\n",
+                rfc3339(entry.lastmod.unwrap_or(updated))
+            )
+            .as_str(),
+        );
+        if let Some(ref description) = entry.description {
+            xml.push_str(format!("    <summary>{}</summary>\n", escape(description)).as_str());
+        }
+        xml.push_str("  </entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+pub fn rss_xml(title: &str, links: &FeedLinks, entries: &[Entry]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <rss version=\"2.0\">\n  <channel>\n",
+    );
+    xml.push_str(format!("    <title>{}</title>\n", escape(title)).as_str());
+    xml.push_str(format!("    <link>{}</link>\n", escape(links.site.as_str())).as_str());
+    xml.push_str(format!("    <description>{}</description>\n", escape(title)).as_str());
+    for entry in entries {
+        xml.push_str("    <item>\n");
+        xml.push_str(
+            format!(
+                "      <title>{}</title>\n",
+                escape(entry.title.as_deref().unwrap_or(entry.loc.as_str()))
+            )
+            .as_str(),
+        );
+        xml.push_str(format!("      <link>{}</link>\n", escape(entry.loc.as_str())).as_str());
+        xml.push_str(format!("      <guid>{}</guid>\n", escape(entry.loc.as_str())).as_str());
+        if let Some(lastmod) = entry.lastmod {
+            xml.push_str(format!("      <pubDate>{}</pubDate>\n", lastmod.to_rfc2822()).as_str());
+        }
+        if let Some(ref description) = entry.description {
+            xml.push_str(
+                format!("      <description>{}</description>\n", escape(description)).as_str(),
+            );
+        }
+        xml.push_str("    </item>\n");
+    }
+    xml.push_str("  </channel>\n</rss>\n");
+    xml
+}
+
+fn rfc3339(date: chrono::DateTime<chrono::Utc>) -> String {
+    date.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    fn entries() -> Vec<super::Entry> {
+        vec![
+            super::Entry {
+                loc: "https://example.com/blog/b/".to_string(),
+                lastmod: Some(
+                    chrono::DateTime::parse_from_rfc3339("2024-02-01T10:00:00Z")
+                        .unwrap()
+                        .into(),
+                ),
+                title: Some("Tom & Jerry".to_string()),
+                description: Some("second <post>".to_string()),
+            },
+            super::Entry {
+                loc: "https://example.com/blog/a/".to_string(),
+                lastmod: None,
+                title: None,
+                description: None,
+            },
+        ]
+    }
+
+    #[test]
+    fn page_url() {
+        assert_eq!(super::page_url("https://a.com", "/"), "https://a.com/");
+        assert_eq!(
+            super::page_url("https://a.com", "/b/c"),
+            "https://a.com/b/c/"
+        );
+        assert_eq!(
+            super::page_url("https://a.com", "b/c.html"),
+            "https://a.com/b/c.html"
+        );
+    }
+
+    #[test]
+    fn sitemap_xml() {
+        assert_eq!(
+            super::sitemap_xml(&entries()),
+            indoc::indoc! {r#"
+                <?xml version="1.0" encoding="UTF-8"?>
+                <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                  <url>
+                    <loc>https://example.com/blog/b/</loc>
+                    <lastmod>2024-02-01T10:00:00Z</lastmod>
+                  </url>
+                  <url>
+                    <loc>https://example.com/blog/a/</loc>
+                  </url>
+                </urlset>
+            "#}
+        );
+    }
+
+    #[test]
+    fn robots_txt() {
+        assert_eq!(
+            super::robots_txt(None, Some("https://a.com/sitemap.xml")),
+            "User-agent: *\nAllow: /\n\nSitemap: https://a.com/sitemap.xml\n"
+        );
+        assert_eq!(
+            super::robots_txt(
+                Some("User-agent: *\nDisallow: /drafts/\nSitemap: https://b.com/s.xml\n"),
+                Some("https://a.com/sitemap.xml")
+            ),
+            "User-agent: *\nDisallow: /drafts/\nSitemap: https://b.com/s.xml\n"
+        );
+    }
+
+    #[test]
+    fn atom_xml() {
+        let links = super::FeedLinks {
+            feed: "https://example.com/feed.xml".to_string(),
+            site: "https://example.com/blog/".to_string(),
+        };
+        assert_eq!(
+            super::atom_xml("Blog", &links, &entries()),
+            indoc::indoc! {r#"
+                <?xml version="1.0" encoding="UTF-8"?>
+                <feed xmlns="http://www.w3.org/2005/Atom">
+                  <title>Blog</title>
+                  <id>https://example.com/blog/</id>
+                  <link href="https://example.com/feed.xml" rel="self"/>
+                  <link href="https://example.com/blog/"/>
+                  <updated>2024-02-01T10:00:00Z</updated>
+                  <entry>
+                    <title>Tom &amp; Jerry</title>
+                    <id>https://example.com/blog/b/</id>
+                    <link href="https://example.com/blog/b/"/>
+                    <updated>2024-02-01T10:00:00Z</updated>
+                    <summary>second &lt;post&gt;</summary>
+                  </entry>
+                  <entry>
+                    <title>https://example.com/blog/a/</title>
+                    <id>https://example.com/blog/a/</id>
+                    <link href="https://example.com/blog/a/"/>
+                    <updated>2024-02-01T10:00:00Z</updated>
+                  </entry>
+                </feed>
+            "#}
+        );
+    }
+}
@@ -36,3 +36,91 @@ pub(crate) async fn get_tracks(
     }
     Ok(tracks)
 }
+
+/// sets `last-merged-version` of `file` in its `.tracks` file to `timestamp`, the snapshot of
+/// the original package the translation is up to date with
+pub(crate) async fn mark_upto_date(
+    config: &fastn_core::Config,
+    base_path: &fastn_ds::Path,
+    file: &str,
+    timestamp: u128,
+) -> fastn_core::Result<()> {
+    let track_path = fastn_core::utils::track_path(file, base_path);
+    let mut tracks = get_tracks(config, base_path, &track_path).await?;
+    let self_timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+
+    let track = tracks.entry(file.to_string()).or_insert_with(|| Track {
+        filename: file.to_string(),
+        package: None,
+        version: None,
+        other_timestamp: None,
+        self_timestamp,
+        last_merged_version: None,
+    });
+    track.self_timestamp = self_timestamp;
+    track.other_timestamp = Some(timestamp);
+    track.last_merged_version = Some(timestamp);
+
+    fastn_core::utils::update(&track_path, to_ftd(&tracks).as_bytes(), &config.ds).await
+}
+
+fn to_ftd(tracks: &std::collections::BTreeMap<String, Track>) -> String {
+    let mut ftd = "-- import: fastn".to_string();
+    for track in tracks.values() {
+        ftd.push_str(format!("\n\n-- fastn.track: {}", track.filename).as_str());
+        if let Some(ref package) = track.package {
+            ftd.push_str(format!("\npackage: {package}").as_str());
+        }
+        if let Some(ref version) = track.version {
+            ftd.push_str(format!("\nversion: {version}").as_str());
+        }
+        if let Some(other_timestamp) = track.other_timestamp {
+            ftd.push_str(format!("\nother-timestamp: {other_timestamp}").as_str());
+        }
+        ftd.push_str(format!("\nself-timestamp: {}", track.self_timestamp).as_str());
+        if let Some(last_merged_version) = track.last_merged_version {
+            ftd.push_str(format!("\nlast-merged-version: {last_merged_version}").as_str());
+        }
+    }
+    ftd.push('\n');
+    ftd
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn to_ftd() {
+        let track = super::Track {
+            filename: "index.ftd".to_string(),
+            package: None,
+            version: None,
+            other_timestamp: Some(1700000000000000000),
+            self_timestamp: 1700000000000000001,
+            last_merged_version: Some(1700000000000000000),
+        };
+        let ftd = super::to_ftd(&[("index.ftd".to_string(), track)].into_iter().collect());
+        assert_eq!(
+            ftd,
+            indoc::indoc! {"
+                -- import: fastn
+
+                -- fastn.track: index.ftd
+                other-timestamp: 1700000000000000000
+                self-timestamp: 1700000000000000001
+                last-merged-version: 1700000000000000000
+            "}
+        );
+
+        let lib = fastn_core::FastnLibrary::default();
+        let tracks: Vec<super::Track> =
+            fastn_core::doc::parse_ftd("index.track", ftd.as_str(), &lib)
+                .unwrap()
+                .get("fastn#track")
+                .unwrap();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!(tracks[0].last_merged_version, Some(1700000000000000000));
+    }
+}
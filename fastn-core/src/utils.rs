@@ -712,6 +712,7 @@ pub async fn replace_markers_2023(
     font_style: &str,
     default_css: &str,
     base_url: &str,
+    language_links: &str,
     config: &fastn_core::Config,
 ) -> String {
     format!(
@@ -731,6 +732,12 @@ pub async fn replace_markers_2023(
         .await
         .unwrap_or_default()
         .as_str(),
+        language_links = if language_links.is_empty() {
+            String::new()
+        } else {
+            format!("\n    {}", language_links.replace('\n', "\n    "))
+        }
+        .as_str(),
         js_script = format!("{js_script}{}", fastn_core::utils::available_code_themes()).as_str(),
         script_file = format!(
             r#"
@@ -751,7 +758,12 @@ pub async fn replace_markers_2023(
             config,
             config.ftd_external_js.as_slice(),
             config.ftd_inline_js.as_slice(),
-            "",
+            config
+                .live_reload
+                .as_ref()
+                .map(|_| fastn_core::live_reload::script())
+                .unwrap_or_default()
+                .as_str(),
             "",
         )
         .await
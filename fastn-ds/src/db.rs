@@ -0,0 +1,344 @@
+/// the database of a package when neither `FASTN_DB_URL` nor `DATABASE_URL` is set
+pub const DEFAULT_DB_URL: &str = "sqlite:///fastn.sqlite";
+/// the file of `DEFAULT_DB_URL`, the only sqlite database fastn creates if it does not exist
+pub const DEFAULT_SQLITE_PATH: &str = "fastn.sqlite";
+
+/// `DbUrl` is the parsed value of `FASTN_DB_URL`, the same package runs against sqlite in
+/// development and postgres in production by changing only the environment.
+///
+/// `sqlite:///fastn.sqlite` and `sqlite://fastn.sqlite` are a sqlite database relative to the
+/// package root, `sqlite:////var/db/fastn.sqlite` is an absolute path. `postgres://` and
+/// `postgresql://` urls are postgres databases.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DbUrl {
+    /// path of the database file
+    Sqlite(String),
+    Postgres(String),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum DbError {
+    #[error("invalid database url {url}: {message}")]
+    InvalidUrl { url: String, message: String },
+    #[error("unsupported database `{0}`, only sqlite:/// and postgres:// urls are supported")]
+    UnsupportedScheme(String),
+    #[error("expected a {expected} database, found a {found} database")]
+    Mismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[error("sqlite error {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("create pool error {0}")]
+    CreatePool(#[from] fastn_ds::CreatePoolError),
+    #[error("postgres error {0}")]
+    Postgres(#[from] tokio_postgres::Error),
+    #[error("postgres pool error {0}")]
+    PgPool(#[from] deadpool_postgres::PoolError),
+}
+
+impl DbUrl {
+    pub fn parse(url: &str) -> Result<DbUrl, DbError> {
+        let url = url.trim();
+        if url.is_empty() {
+            return Err(DbError::InvalidUrl {
+                url: url.to_string(),
+                message: "empty url".to_string(),
+            });
+        }
+
+        if let Some(path) = url
+            .strip_prefix("sqlite:///")
+            .or_else(|| url.strip_prefix("sqlite://"))
+        {
+            if path.is_empty() {
+                return Err(DbError::InvalidUrl {
+                    url: url.to_string(),
+                    message: "no database file".to_string(),
+                });
+            }
+            return Ok(DbUrl::Sqlite(path.to_string()));
+        }
+
+        match url::Url::parse(url) {
+            Ok(u) if u.scheme() == "postgres" || u.scheme() == "postgresql" => {
+                Ok(DbUrl::Postgres(url.to_string()))
+            }
+            Ok(u) => Err(DbError::UnsupportedScheme(u.scheme().to_string())),
+            Err(url::ParseError::RelativeUrlWithoutBase) => Err(DbError::InvalidUrl {
+                url: url.to_string(),
+                message: format!("a path is not a url, use sqlite:///{url}"),
+            }),
+            Err(e) => Err(DbError::InvalidUrl {
+                url: url.to_string(),
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    /// `DATABASE_URL` used to be the path of a sqlite database, like `fastn.sqlite`, such a path
+    /// is still read as `sqlite:///<path>`
+    pub fn parse_database_url(url: &str) -> Result<DbUrl, DbError> {
+        let url = url.trim();
+        if let Err(url::ParseError::RelativeUrlWithoutBase) = url::Url::parse(url) {
+            if !url.is_empty() && !url.starts_with("sqlite:") {
+                tracing::warn!(
+                    msg = "DATABASE_URL is a path, use FASTN_DB_URL=sqlite:///<path>",
+                    path = url
+                );
+                return Ok(DbUrl::Sqlite(url.to_string()));
+            }
+        }
+        DbUrl::parse(url)
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DbUrl::Sqlite(_) => "sqlite",
+            DbUrl::Postgres(_) => "postgres",
+        }
+    }
+}
+
+impl Default for DbUrl {
+    fn default() -> Self {
+        DbUrl::parse(DEFAULT_DB_URL).unwrap()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Pool {
+    Sqlite(fastn_ds::sqlite::Pool),
+    Postgres(deadpool_postgres::Pool),
+}
+
+impl Pool {
+    /// the pool of `url`, created on first use. `read_only` only applies to sqlite, postgres
+    /// permissions are managed by the database.
+    pub async fn get(
+        url: &DbUrl,
+        pg_pools: &scc::HashMap<String, deadpool_postgres::Pool>,
+        sqlite_pools: &fastn_ds::sqlite::Pools,
+        read_only: bool,
+    ) -> Result<Pool, DbError> {
+        match url {
+            DbUrl::Sqlite(path) => Ok(Pool::Sqlite(sqlite_pools.get(path, read_only))),
+            DbUrl::Postgres(url) => Ok(Pool::Postgres(pg_pool(pg_pools, url).await?)),
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Pool::Sqlite(_) => "sqlite",
+            Pool::Postgres(_) => "postgres",
+        }
+    }
+
+    pub fn sqlite(self) -> Result<fastn_ds::sqlite::Pool, DbError> {
+        match self {
+            Pool::Sqlite(pool) => Ok(pool),
+            p => Err(DbError::Mismatch {
+                expected: "sqlite",
+                found: p.kind(),
+            }),
+        }
+    }
+
+    /// runs `sql`, which may have many statements, like a seed file
+    pub async fn execute_batch(&self, sql: &str) -> Result<(), DbError> {
+        match self {
+            Pool::Sqlite(pool) => pool.get()?.execute_batch(sql)?,
+            Pool::Postgres(pool) => pool.get().await?.batch_execute(sql).await?,
+        }
+        Ok(())
+    }
+
+    pub fn postgres(self) -> Result<deadpool_postgres::Pool, DbError> {
+        match self {
+            Pool::Postgres(pool) => Ok(pool),
+            p => Err(DbError::Mismatch {
+                expected: "postgres",
+                found: p.kind(),
+            }),
+        }
+    }
+}
+
+async fn pg_pool(
+    pg_pools: &scc::HashMap<String, deadpool_postgres::Pool>,
+    url: &str,
+) -> Result<deadpool_postgres::Pool, fastn_ds::CreatePoolError> {
+    if let Some(p) = pg_pools.get(url) {
+        return Ok(p.get().clone());
+    }
+
+    let pool = fastn_ds::create_pool(url).await?;
+    fastn_ds::insert_or_update(pg_pools, url.to_string(), pool.clone());
+    Ok(pool)
+}
+
+/// `TestDatabase` keeps what one `fastn test` file writes to the database from the others.
+///
+/// A sqlite database is copied to a temporary file, the test file runs against the copy, see
+/// `fastn_ds::DocumentStore::with_db_url`, and the copy is deleted when it finishes. A postgres
+/// database is replaced by a pool of one connection inside a transaction, which is rolled back
+/// when the test file finishes. Statements that end that transaction, like a `COMMIT`, are not
+/// isolated.
+#[derive(Debug)]
+pub enum TestDatabase {
+    /// path of the copy
+    Sqlite(camino::Utf8PathBuf),
+    Postgres {
+        url: String,
+        pool: deadpool_postgres::Pool,
+    },
+}
+
+impl TestDatabase {
+    pub async fn start(ds: &fastn_ds::DocumentStore) -> Result<TestDatabase, DbError> {
+        match ds.default_db_url().await? {
+            DbUrl::Sqlite(path) => {
+                let copy = std::env::temp_dir()
+                    .join(format!("fastn-test-{:016x}.sqlite", rand::random::<u64>()));
+                let copy =
+                    camino::Utf8PathBuf::from_path_buf(copy).map_err(|p| DbError::InvalidUrl {
+                        url: p.display().to_string(),
+                        message: "the temporary directory is not a utf-8 path".to_string(),
+                    })?;
+                // a package without a database starts with an empty one
+                if ds.exists(&ds.root().join(path.as_str())).await {
+                    ds.sqlite_pools
+                        .get(path.as_str(), true)
+                        .get()?
+                        .execute("VACUUM INTO ?1", [copy.as_str()])?;
+                } else {
+                    rusqlite::Connection::open(copy.as_str())?;
+                }
+                Ok(TestDatabase::Sqlite(copy))
+            }
+            DbUrl::Postgres(url) => {
+                let pool = deadpool_postgres::Config {
+                    url: Some(url.clone()),
+                    // a query waiting for the connection held by another query fails instead
+                    // of waiting forever
+                    pool: Some(deadpool_postgres::PoolConfig {
+                        timeouts: deadpool_postgres::Timeouts::wait_millis(30_000),
+                        ..deadpool_postgres::PoolConfig::new(1)
+                    }),
+                    ..Default::default()
+                }
+                .create_pool(
+                    Some(deadpool_postgres::Runtime::Tokio1),
+                    tokio_postgres::NoTls,
+                )
+                .map_err(fastn_ds::CreatePoolError::from)?;
+                pool.get().await?.batch_execute("BEGIN").await?;
+                fastn_ds::insert_or_update(&ds.pg_pools, url.clone(), pool.clone());
+                Ok(TestDatabase::Postgres { url, pool })
+            }
+        }
+    }
+
+    /// the database the test file runs against
+    pub fn url(&self) -> DbUrl {
+        match self {
+            TestDatabase::Sqlite(path) => DbUrl::Sqlite(path.to_string()),
+            TestDatabase::Postgres { url, .. } => DbUrl::Postgres(url.to_string()),
+        }
+    }
+
+    /// deletes the copy, or rolls back the transaction
+    pub async fn finish(self, ds: &fastn_ds::DocumentStore) -> Result<(), DbError> {
+        match self {
+            TestDatabase::Sqlite(path) => {
+                ds.sqlite_pools.remove(path.as_str());
+                for suffix in ["", "-wal", "-shm"] {
+                    // the test file may not have opened the database at all
+                    let _ = tokio::fs::remove_file(format!("{path}{suffix}")).await;
+                }
+                Ok(())
+            }
+            TestDatabase::Postgres { url, pool } => {
+                let rollback = match pool.get().await {
+                    Ok(client) => client
+                        .batch_execute("ROLLBACK")
+                        .await
+                        .map_err(DbError::from),
+                    Err(e) => Err(e.into()),
+                };
+                ds.pg_pools.remove(&url);
+                rollback
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DbUrl;
+
+    #[test]
+    fn parse() {
+        let sqlite = |path: &str| Some(DbUrl::Sqlite(path.to_string()));
+        assert_eq!(
+            DbUrl::parse("sqlite:///fastn.sqlite").ok(),
+            sqlite("fastn.sqlite")
+        );
+        assert_eq!(
+            DbUrl::parse(" sqlite:///db/a.sqlite ").ok(),
+            sqlite("db/a.sqlite")
+        );
+        assert_eq!(
+            DbUrl::parse("sqlite:////var/db/a.sqlite").ok(),
+            sqlite("/var/db/a.sqlite")
+        );
+        assert_eq!(
+            DbUrl::parse("sqlite://fastn.sqlite").ok(),
+            sqlite("fastn.sqlite")
+        );
+        assert_eq!(
+            DbUrl::parse(super::DEFAULT_DB_URL).ok(),
+            sqlite(super::DEFAULT_SQLITE_PATH)
+        );
+        assert!(matches!(
+            DbUrl::parse("sqlite:///"),
+            Err(super::DbError::InvalidUrl { .. })
+        ));
+
+        assert_eq!(
+            DbUrl::parse("postgres://user@localhost/db").ok(),
+            Some(DbUrl::Postgres("postgres://user@localhost/db".to_string()))
+        );
+        assert_eq!(
+            DbUrl::parse("postgresql://localhost/db").ok(),
+            Some(DbUrl::Postgres("postgresql://localhost/db".to_string()))
+        );
+
+        assert!(matches!(
+            DbUrl::parse("fastn.sqlite"),
+            Err(super::DbError::InvalidUrl { .. })
+        ));
+        assert!(matches!(
+            DbUrl::parse(""),
+            Err(super::DbError::InvalidUrl { .. })
+        ));
+        assert!(matches!(
+            DbUrl::parse("mysql://localhost/db"),
+            Err(super::DbError::UnsupportedScheme(s)) if s == "mysql"
+        ));
+    }
+
+    #[test]
+    fn parse_database_url() {
+        assert_eq!(
+            DbUrl::parse_database_url("fastn.sqlite").ok(),
+            Some(DbUrl::Sqlite("fastn.sqlite".to_string()))
+        );
+        assert_eq!(
+            DbUrl::parse_database_url("postgres://localhost/db").ok(),
+            Some(DbUrl::Postgres("postgres://localhost/db".to_string()))
+        );
+        assert!(DbUrl::parse_database_url("").is_err());
+        assert!(DbUrl::parse_database_url("mysql://localhost/db").is_err());
+    }
+}
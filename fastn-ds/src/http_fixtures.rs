@@ -0,0 +1,313 @@
+/// `HttpFixtures` records the outgoing requests of `DocumentStore::http` and the wasm
+/// `http_send_request` export, and replays them, so `fastn test` runs without the network.
+///
+/// Requests are matched by method, url and body. Requests recorded more than once are replayed
+/// in the order they were recorded, and the last response is repeated after that. In `Replay`
+/// mode a request without a recorded response fails with `UnmatchedRequest`.
+#[derive(Debug, Clone, Default)]
+pub struct HttpFixtures {
+    inner: std::sync::Arc<std::sync::Mutex<Inner>>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Mode {
+    /// requests are sent, nothing is recorded
+    #[default]
+    Live,
+    Record,
+    Replay,
+}
+
+#[derive(Debug, Default)]
+struct Inner {
+    mode: Mode,
+    fixtures: Vec<Fixture>,
+    /// how many times each fixture has been replayed
+    replayed: Vec<usize>,
+    unmatched: Vec<UnmatchedRequest>,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Fixture {
+    pub method: String,
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Body::is_empty")]
+    pub body: Body,
+    pub status: u16,
+    #[serde(default)]
+    pub headers: Vec<(String, String)>,
+    #[serde(default)]
+    pub response: Body,
+}
+
+/// text bodies are stored as strings, others as a list of bytes
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum Body {
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Default for Body {
+    fn default() -> Self {
+        Body::Text(String::new())
+    }
+}
+
+impl Body {
+    pub fn new(bytes: &[u8]) -> Body {
+        match std::str::from_utf8(bytes) {
+            Ok(text) => Body::Text(text.to_string()),
+            Err(_) => Body::Bytes(bytes.to_vec()),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Body::Text(text) => text.as_bytes(),
+            Body::Bytes(bytes) => bytes.as_slice(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.as_bytes().is_empty()
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+#[error("no recorded response for {method} {url}, record it with `fastn test --record`")]
+pub struct UnmatchedRequest {
+    pub method: String,
+    pub url: String,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum FixtureError {
+    #[error("invalid fixture: {0}")]
+    Fixture(#[from] http::Error),
+    #[error("{0}")]
+    Unmatched(#[from] UnmatchedRequest),
+}
+
+impl HttpFixtures {
+    pub fn mode(&self) -> Mode {
+        self.inner.lock().unwrap().mode
+    }
+
+    /// starts recording or replaying `fixtures`, forgetting what was recorded before
+    pub fn start(&self, mode: Mode, fixtures: Vec<Fixture>) {
+        let mut inner = self.inner.lock().unwrap();
+        *inner = Inner {
+            mode,
+            replayed: vec![0; fixtures.len()],
+            fixtures,
+            unmatched: vec![],
+        };
+    }
+
+    /// stops recording or replaying, and returns the fixtures and the requests that had no
+    /// recorded response
+    pub fn finish(&self) -> (Vec<Fixture>, Vec<UnmatchedRequest>) {
+        let inner = std::mem::take(&mut *self.inner.lock().unwrap());
+        (inner.fixtures, inner.unmatched)
+    }
+
+    /// `None` if not replaying, the request has to be sent
+    pub fn replay(
+        &self,
+        method: &str,
+        url: &str,
+        body: &[u8],
+    ) -> Option<Result<fastn_ds::HttpResponse, FixtureError>> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.mode != Mode::Replay {
+            return None;
+        }
+
+        let matching: Vec<usize> = inner
+            .fixtures
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| f.method.eq_ignore_ascii_case(method) && f.url == url)
+            .filter(|(_, f)| f.body.as_bytes() == body)
+            .map(|(i, _)| i)
+            .collect();
+        let index = match matching
+            .iter()
+            .find(|i| inner.replayed[**i] == 0)
+            .or_else(|| matching.last())
+        {
+            Some(i) => *i,
+            None => {
+                let unmatched = UnmatchedRequest {
+                    method: method.to_string(),
+                    url: url.to_string(),
+                };
+                tracing::error!(msg = "unmatched http fixture", method = method, url = url);
+                inner.unmatched.push(unmatched.clone());
+                return Some(Err(unmatched.into()));
+            }
+        };
+        inner.replayed[index] += 1;
+
+        let fixture = &inner.fixtures[index];
+        let mut response = http::Response::builder().status(fixture.status);
+        for (k, v) in fixture.headers.iter() {
+            response = response.header(k.as_str(), v.as_str());
+        }
+        Some(
+            response
+                .body(bytes::Bytes::copy_from_slice(fixture.response.as_bytes()))
+                .map_err(FixtureError::from),
+        )
+    }
+
+    /// records `response` if recording
+    pub fn record(&self, method: &str, url: &str, body: &[u8], response: &fastn_ds::HttpResponse) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.mode != Mode::Record {
+            return;
+        }
+
+        inner.fixtures.push(Fixture {
+            method: method.to_uppercase(),
+            url: url.to_string(),
+            body: Body::new(body),
+            status: response.status().as_u16(),
+            headers: response
+                .headers()
+                .iter()
+                .filter_map(|(k, v)| {
+                    v.to_str()
+                        .ok()
+                        .map(|v| (k.as_str().to_string(), v.to_string()))
+                })
+                .collect(),
+            response: Body::new(response.body()),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn fixture(method: &str, url: &str, body: &str, response: &str) -> super::Fixture {
+        super::Fixture {
+            method: method.to_string(),
+            url: url.to_string(),
+            body: super::Body::new(body.as_bytes()),
+            status: 200,
+            headers: vec![("content-type".to_string(), "text/plain".to_string())],
+            response: super::Body::new(response.as_bytes()),
+        }
+    }
+
+    #[track_caller]
+    fn replayed(fixtures: &super::HttpFixtures, method: &str, url: &str, body: &str) -> String {
+        let response = fixtures
+            .replay(method, url, body.as_bytes())
+            .unwrap()
+            .unwrap();
+        String::from_utf8(response.body().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn replays_in_order_and_repeats_the_last() {
+        let fixtures = super::HttpFixtures::default();
+        assert!(fixtures.replay("GET", "https://a.com/", b"").is_none());
+
+        fixtures.start(
+            super::Mode::Replay,
+            vec![
+                fixture("GET", "https://a.com/", "", "first"),
+                fixture("POST", "https://a.com/", "x", "post"),
+                fixture("GET", "https://a.com/", "", "second"),
+            ],
+        );
+        assert_eq!(replayed(&fixtures, "get", "https://a.com/", ""), "first");
+        assert_eq!(replayed(&fixtures, "GET", "https://a.com/", ""), "second");
+        assert_eq!(replayed(&fixtures, "GET", "https://a.com/", ""), "second");
+        assert_eq!(replayed(&fixtures, "POST", "https://a.com/", "x"), "post");
+        assert_eq!(replayed(&fixtures, "POST", "https://a.com/", "x"), "post");
+
+        let response = fixtures
+            .replay("GET", "https://a.com/", b"")
+            .unwrap()
+            .unwrap();
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers()["content-type"], "text/plain");
+
+        let (_, unmatched) = fixtures.finish();
+        assert!(unmatched.is_empty());
+        assert!(fixtures.replay("GET", "https://a.com/", b"").is_none());
+    }
+
+    #[test]
+    fn reports_unmatched_requests() {
+        let fixtures = super::HttpFixtures::default();
+        fixtures.start(
+            super::Mode::Replay,
+            vec![fixture("POST", "https://a.com/", "x", "post")],
+        );
+
+        // the method, url and body all have to match
+        for (method, url, body) in [
+            ("GET", "https://a.com/", "x"),
+            ("POST", "https://b.com/", "x"),
+            ("POST", "https://a.com/", "y"),
+        ] {
+            assert!(matches!(
+                fixtures.replay(method, url, body.as_bytes()),
+                Some(Err(super::FixtureError::Unmatched(_)))
+            ));
+        }
+
+        let (fixtures, unmatched) = fixtures.finish();
+        assert_eq!(fixtures.len(), 1);
+        assert_eq!(
+            unmatched,
+            vec![
+                super::UnmatchedRequest {
+                    method: "GET".to_string(),
+                    url: "https://a.com/".to_string()
+                },
+                super::UnmatchedRequest {
+                    method: "POST".to_string(),
+                    url: "https://b.com/".to_string()
+                },
+                super::UnmatchedRequest {
+                    method: "POST".to_string(),
+                    url: "https://a.com/".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn records() {
+        let fixtures = super::HttpFixtures::default();
+        let response = http::Response::builder()
+            .status(201)
+            .header("content-type", "application/octet-stream")
+            .body(bytes::Bytes::from_static(&[0xff, 0x00]))
+            .unwrap();
+        fixtures.record("get", "https://a.com/", b"", &response);
+
+        fixtures.start(super::Mode::Record, vec![]);
+        fixtures.record("get", "https://a.com/", b"", &response);
+        let (recorded, _) = fixtures.finish();
+        assert_eq!(
+            recorded,
+            vec![super::Fixture {
+                method: "GET".to_string(),
+                url: "https://a.com/".to_string(),
+                body: super::Body::Text(String::new()),
+                status: 201,
+                headers: vec![(
+                    "content-type".to_string(),
+                    "application/octet-stream".to_string()
+                )],
+                response: super::Body::Bytes(vec![0xff, 0x00]),
+            }]
+        );
+    }
+}
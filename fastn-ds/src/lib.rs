@@ -4,17 +4,30 @@
 extern crate self as fastn_ds;
 
 mod create_pool;
+pub mod db;
 pub mod http;
+pub mod http_fixtures;
 pub mod reqwest_util;
-mod utils;
+pub mod sqlite;
+pub mod utils;
 pub mod wasm;
 
 pub use create_pool::create_pool;
 
 #[derive(Debug, Clone)]
 pub struct DocumentStore {
-    pub wasm_modules: scc::HashMap<String, wasmtime::Module>,
+    pub wasm_modules: scc::HashMap<String, fastn_ds::wasm::Module>,
+    /// migrations that have been applied, keyed by the path of the `.wasm` file
+    pub wasm_migrations: scc::HashMap<String, fastn_ds::wasm::AppliedMigration>,
     pub pg_pools: actix_web::web::Data<scc::HashMap<String, deadpool_postgres::Pool>>,
+    /// sqlite connections of the `sql` processors and the wasm handlers
+    pub sqlite_pools: fastn_ds::sqlite::Pools,
+    /// outgoing requests recorded or replayed by `fastn test`
+    pub http_fixtures: fastn_ds::http_fixtures::HttpFixtures,
+    /// overrides `FASTN_DB_URL`, `fastn test` runs each test file against its own database
+    db_url: Option<fastn_ds::db::DbUrl>,
+    /// overrides `FASTN_WASM_CACHE_DIR`
+    wasm_cache_dir: Option<camino::Utf8PathBuf>,
     root: Path,
 }
 
@@ -125,6 +138,14 @@ pub enum WasmReadError {
     WasmError(#[from] wasmtime::Error),
     #[error("env error {0}")]
     BoolEnvironmentError(#[from] BoolEnvironmentError),
+    #[error("wasm cache error {0}")]
+    WasmCacheError(#[from] fastn_ds::wasm::WasmCacheError),
+    #[error("migration error {0}")]
+    MigrationError(#[from] fastn_ds::wasm::MigrationError),
+    #[error("wasm limits error {0}")]
+    WasmLimits(#[from] fastn_ds::wasm::LimitsError),
+    #[error("db error {0}")]
+    DbError(#[from] fastn_ds::db::DbError),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -143,6 +164,12 @@ pub enum HttpError {
     EnvironmentError(#[from] EnvironmentError),
     #[error("create pool error {0}")]
     CreatePoolError(#[from] CreatePoolError),
+    #[error("wasm limits error {0}")]
+    WasmLimits(#[from] fastn_ds::wasm::LimitsError),
+    #[error("db error {0}")]
+    DbError(#[from] fastn_ds::db::DbError),
+    #[error("http fixture error {0}")]
+    Fixture(#[from] fastn_ds::http_fixtures::FixtureError),
 }
 
 pub type HttpResponse = ::http::Response<bytes::Bytes>;
@@ -160,7 +187,15 @@ pub trait RequestType {
 
 pub static WASM_ENGINE: once_cell::sync::Lazy<wasmtime::Engine> =
     once_cell::sync::Lazy::new(|| {
-        wasmtime::Engine::new(wasmtime::Config::new().async_support(true)).unwrap()
+        wasmtime::Engine::new(
+            wasmtime::Config::new()
+                .async_support(true)
+                // fuel and epochs back the per request `fastn_ds::wasm::Limits`
+                .consume_fuel(true)
+                .epoch_interruption(true)
+                .allocation_strategy(fastn_ds::wasm::allocation_strategy()),
+        )
+        .unwrap()
     });
 
 #[derive(thiserror::Error, Debug)]
@@ -171,68 +206,125 @@ pub enum CreatePoolError {
     EnvError(#[from] EnvironmentError),
 }
 
-/// wasmc compiles path.wasm to path.wasmc
-pub async fn wasmc(path: &str) -> wasmtime::Result<()> {
-    Ok(tokio::fs::write(
-        format!("{path}c"),
-        wasmtime::Module::from_file(&WASM_ENGINE, path)?.serialize()?,
-    )
-    .await?)
+/// the `.wasmc` file older versions of `fastn wasmc` wrote for the `.wasm` file at `path`
+fn wasmc_path(path: &str) -> fastn_ds::Path {
+    fastn_ds::Path::new(format!("{path}c"))
+}
+
+fn legacy_wasmc(path: &str) -> wasmtime::Result<wasmtime::Module> {
+    let wasmc = wasmc_path(path);
+    tracing::warn!(
+        msg = "loading a .wasmc file, ship the .wasm file and run `fastn wasmc` instead",
+        path = %wasmc
+    );
+    // safety: the `.wasmc` is part of the package, it is as trusted as the package is
+    unsafe { wasmtime::Module::from_trusted_file(&WASM_ENGINE, &wasmc.path) }
 }
 
 impl DocumentStore {
-    pub async fn default_pg_pool(&self) -> Result<deadpool_postgres::Pool, CreatePoolError> {
-        let db_url = match self.env("FASTN_DB_URL").await {
-            Ok(v) => v,
-            Err(_) => self
-                .env("DATABASE_URL")
-                .await
-                .unwrap_or_else(|_| "fastn.sqlite".to_string()),
-        };
+    /// `FASTN_DB_URL`, or `DATABASE_URL` (which can also be a path), the sqlite database
+    /// `fastn.sqlite` if neither is set
+    pub async fn default_db_url(&self) -> Result<fastn_ds::db::DbUrl, fastn_ds::db::DbError> {
+        Ok(self.configured_db_url().await?.unwrap_or_default())
+    }
 
-        if let Some(p) = self.pg_pools.get(db_url.as_str()) {
-            return Ok(p.get().clone());
+    /// `default_db_url`, `None` if neither `with_db_url`, `FASTN_DB_URL` nor `DATABASE_URL` set it
+    pub async fn configured_db_url(
+        &self,
+    ) -> Result<Option<fastn_ds::db::DbUrl>, fastn_ds::db::DbError> {
+        if let Some(ref url) = self.db_url {
+            return Ok(Some(url.clone()));
         }
+        match self.env("FASTN_DB_URL").await {
+            Ok(v) => fastn_ds::db::DbUrl::parse(v.as_str()).map(Some),
+            Err(_) => match self.env("DATABASE_URL").await {
+                Ok(v) => fastn_ds::db::DbUrl::parse_database_url(v.as_str()).map(Some),
+                Err(_) => Ok(None),
+            },
+        }
+    }
 
-        let pool = fastn_ds::create_pool(db_url.as_str()).await?;
+    pub async fn db_pool(
+        &self,
+        url: &fastn_ds::db::DbUrl,
+        read_only: bool,
+    ) -> Result<fastn_ds::db::Pool, fastn_ds::db::DbError> {
+        fastn_ds::db::Pool::get(url, &self.pg_pools, &self.sqlite_pools, read_only).await
+    }
 
-        fastn_ds::insert_or_update(&self.pg_pools, db_url.to_string(), pool.clone());
+    /// the pool of `default_db_url`, `read_only` connections are used at render time
+    pub async fn default_db_pool(
+        &self,
+        read_only: bool,
+    ) -> Result<fastn_ds::db::Pool, fastn_ds::db::DbError> {
+        self.db_pool(&self.default_db_url().await?, read_only).await
+    }
 
-        Ok(pool)
+    pub async fn default_pg_pool(&self) -> Result<deadpool_postgres::Pool, fastn_ds::db::DbError> {
+        self.default_db_pool(false).await?.postgres()
     }
 
     pub fn new<T: AsRef<camino::Utf8Path>>(
         root: T,
         pg_pools: actix_web::web::Data<scc::HashMap<String, deadpool_postgres::Pool>>,
     ) -> Self {
+        let root = Path::new(root.as_ref().as_str());
         Self {
             wasm_modules: Default::default(),
+            wasm_migrations: Default::default(),
             pg_pools,
-            root: Path::new(root.as_ref().as_str()),
+            sqlite_pools: fastn_ds::sqlite::Pools::new(root.clone()),
+            http_fixtures: Default::default(),
+            db_url: None,
+            wasm_cache_dir: None,
+            root,
+        }
+    }
+
+    /// a document store using the database `url` instead of `default_db_url`. Wasm modules are
+    /// loaded again, so their migrations run against that database.
+    pub fn with_db_url(mut self, url: fastn_ds::db::DbUrl) -> Self {
+        self.db_url = Some(url);
+        self.wasm_modules = Default::default();
+        self.wasm_migrations = Default::default();
+        self
+    }
+
+    /// a document store whose sqlite connections use `FASTN_SQLITE_BUSY_TIMEOUT`, in
+    /// milliseconds, and `FASTN_SQLITE_STATEMENT_CACHE`, the number of prepared statements kept
+    /// per connection
+    pub async fn with_sqlite_config_from_env(mut self) -> Self {
+        let mut config = fastn_ds::sqlite::Config::default();
+        if let Some(v) = self.env_parsed("FASTN_SQLITE_BUSY_TIMEOUT").await {
+            config.busy_timeout = std::time::Duration::from_millis(v);
         }
+        if let Some(v) = self.env_parsed("FASTN_SQLITE_STATEMENT_CACHE").await {
+            config.statement_cache_capacity = v;
+        }
+        self.sqlite_pools = self.sqlite_pools.with_config(config);
+        self
+    }
+
+    /// a document store keeping the compiled wasm modules in `dir`, see `wasm_cache`
+    pub fn with_wasm_cache_dir(mut self, dir: camino::Utf8PathBuf) -> Self {
+        self.wasm_cache_dir = Some(dir);
+        self
     }
 
     #[tracing::instrument(skip(self))]
-    pub async fn get_wasm(&self, path: &str) -> Result<wasmtime::Module, WasmReadError> {
-        // TODO: implement wasm module on disc caching, so modules load faster across
-        //       cache purge
+    pub async fn get_wasm(&self, path: &str) -> Result<fastn_ds::wasm::Module, WasmReadError> {
         match self.wasm_modules.get(path) {
             Some(module) => Ok(module.get().clone()),
             None => {
-                let wasmc_path = fastn_ds::Path::new(format!("{path}c").as_str());
-                let module = match unsafe {
-                    wasmtime::Module::from_trusted_file(&WASM_ENGINE, &wasmc_path.path)
-                } {
-                    Ok(m) => {
-                        tracing::info!("loaded wasmc file for {path}");
-                        m
-                    }
-                    Err(e) => {
-                        tracing::info!("could not read {wasmc_path:?} file: {e:?}");
-                        let source = self.read_content(&fastn_ds::Path::new(path)).await?;
-                        wasmtime::Module::from_binary(&WASM_ENGINE, &source)?
+                let module = match self.read_content(&fastn_ds::Path::new(path)).await {
+                    Ok(source) => self.wasm_cache().await.get_or_compile(&source).await?,
+                    Err(ReadError::NotFound(_)) if self.exists(&wasmc_path(path)).await => {
+                        legacy_wasmc(path)?
                     }
+                    Err(e) => return Err(e.into()),
                 };
+                let module = fastn_ds::wasm::Module::new(module)?;
+                self.migrate_wasm(path, &module).await?;
 
                 // we are only storing compiled module if we are not in debug mode
                 if !self.env_bool("FASTN_DEBUG", false).await? {
@@ -244,6 +336,88 @@ impl DocumentStore {
         }
     }
 
+    /// wasmc compiles the `.wasm` file at `path` into the `fastn_ds::wasm::WasmCache`, so the
+    /// server does not have to compile it on the first request. `fastn wasmc` used to write a
+    /// `.wasmc` file next to the `.wasm`, such a file is still loaded if there is no `.wasm`.
+    pub async fn wasmc(&self, path: &str) -> Result<(), WasmReadError> {
+        let source = self.read_content(&fastn_ds::Path::new(path)).await?;
+        self.wasm_cache().await.get_or_compile(&source).await?;
+        Ok(())
+    }
+
+    /// The compiled wasm modules, in `with_wasm_cache_dir` or `FASTN_WASM_CACHE_DIR`, which
+    /// defaults to `fastn/wasm` in the cache directory of the user. `FASTN_WASM_CACHE_MAX_SIZE`
+    /// is in bytes.
+    pub async fn wasm_cache(&self) -> fastn_ds::wasm::WasmCache {
+        let dir = match self.wasm_cache_dir {
+            Some(ref dir) => dir.clone(),
+            None => self
+                .env("FASTN_WASM_CACHE_DIR")
+                .await
+                .map(camino::Utf8PathBuf::from)
+                .unwrap_or_else(|_| fastn_ds::wasm::WasmCache::default_dir()),
+        };
+        let mut cache = fastn_ds::wasm::WasmCache::new(dir);
+        if let Some(v) = self.env_parsed("FASTN_WASM_CACHE_MAX_SIZE").await {
+            cache.max_size = v;
+        }
+        cache
+    }
+
+    /// runs the `migration__entrypoint` of the module at `path`, unless it has already been
+    /// applied to the current database for this version of the `.wasm` file
+    async fn migrate_wasm(
+        &self,
+        path: &str,
+        module: &fastn_ds::wasm::Module,
+    ) -> Result<(), WasmReadError> {
+        let db_url = self.default_db_url().await?;
+        let modified = self.modified(&fastn_ds::Path::new(path)).await.ok();
+
+        if let Some(applied) = self.wasm_migrations.get(path) {
+            let applied = applied.get();
+            if applied.db_url == db_url
+                && applied.modified.is_some()
+                && applied.modified == modified
+            {
+                return Ok(());
+            }
+        }
+
+        let has_migration = module
+            .migrate(fastn_ds::wasm::Store::new(
+                ft_sys_shared::Request {
+                    uri: format!("wasm+proxy://{path}/"),
+                    method: "GET".to_string(),
+                    headers: vec![],
+                    body: vec![],
+                },
+                None,
+                self.pg_pools.clone(),
+                self.sqlite_pools.clone(),
+                db_url.clone(),
+                self.tejar().await,
+                fastn_ds::wasm::exports::AwsConfig::read(self).await,
+                self.wasm_limits(&Default::default()).await?,
+                self.http_fixtures.clone(),
+            ))
+            .await?;
+
+        tracing::info!(msg = "applied wasm migration", path = path, has_migration);
+        fastn_ds::insert_or_update(
+            &self.wasm_migrations,
+            path.to_string(),
+            fastn_ds::wasm::AppliedMigration {
+                db_url,
+                modified,
+                applied_on: chrono::Utc::now(),
+                has_migration,
+            },
+        );
+
+        Ok(())
+    }
+
     pub fn root(&self) -> fastn_ds::Path {
         self.root.clone()
     }
@@ -369,6 +543,30 @@ impl DocumentStore {
         path.path.exists()
     }
 
+    pub async fn modified(
+        &self,
+        path: &fastn_ds::Path,
+    ) -> Result<std::time::SystemTime, ReadError> {
+        tokio::fs::metadata(&path.path)
+            .await
+            .and_then(|m| m.modified())
+            .map_err(|e| {
+                if e.kind() == std::io::ErrorKind::NotFound {
+                    ReadError::NotFound(path.to_string())
+                } else {
+                    ReadError::IOError(e, path.to_string())
+                }
+            })
+    }
+
+    /// clear_cache drops everything the document store has cached in memory, so the next
+    /// read goes to disk. Used by `fastn serve --watch` when package files change.
+    pub fn clear_cache(&self) {
+        self.wasm_modules.clear();
+        self.wasm_migrations.clear();
+        self.sqlite_pools.clear();
+    }
+
     pub async fn env_bool(&self, key: &str, default: bool) -> Result<bool, BoolEnvironmentError> {
         match self.env(key).await {
             Ok(t) if t.eq("true") => Ok(true),
@@ -382,10 +580,13 @@ impl DocumentStore {
         std::env::var(key).map_err(|_| EnvironmentError::NotSet(key.to_string()))
     }
 
+    /// `options` are the `key=value` options of the endpoint in `fastn.url-mappings`, they set
+    /// the `fastn_ds::wasm::Limits` of the request
     pub async fn handle_wasm<T>(
         &self,
         wasm_url: String,
         req: &T,
+        options: &std::collections::BTreeMap<String, String>,
     ) -> Result<ft_sys_shared::Request, HttpError>
     where
         T: RequestType,
@@ -410,13 +611,69 @@ impl DocumentStore {
             req.ud(self).await,
             module,
             self.pg_pools.clone(),
-            self.env("DATABASE_URL")
-                .await
-                .unwrap_or_else(|_| "fastn.sqlite".to_string()),
+            self.sqlite_pools.clone(),
+            self.default_db_url().await?,
+            self.tejar().await,
+            fastn_ds::wasm::exports::AwsConfig::read(self).await,
+            self.wasm_limits(options).await?,
+            self.http_fixtures.clone(),
         )
         .await?)
     }
 
+    /// `options` of the endpoint override the `FASTN_WASM_FUEL`, `FASTN_WASM_MEMORY` and
+    /// `FASTN_WASM_TIMEOUT` defaults
+    pub async fn wasm_limits(
+        &self,
+        options: &std::collections::BTreeMap<String, String>,
+    ) -> Result<fastn_ds::wasm::Limits, fastn_ds::wasm::LimitsError> {
+        let mut defaults = std::collections::BTreeMap::new();
+        for (key, env) in [
+            ("fuel", "FASTN_WASM_FUEL"),
+            ("memory", "FASTN_WASM_MEMORY"),
+            ("timeout", "FASTN_WASM_TIMEOUT"),
+        ] {
+            if let Ok(value) = self.env(env).await {
+                defaults.insert(key.to_string(), value);
+            }
+        }
+
+        fastn_ds::wasm::Limits::default()
+            .with_options(&defaults)?
+            .with_options(options)
+    }
+
+    /// The key/blob store of the wasm handlers, `FASTN_TEJAR_DIR` (relative to the package root)
+    /// defaults to `.fastn/tejar`. `FASTN_TEJAR_MAX_VALUE_SIZE` and `FASTN_TEJAR_MAX_TOTAL_SIZE` are in
+    /// bytes.
+    pub async fn tejar(&self) -> fastn_ds::wasm::exports::Tejar {
+        let mut tejar = fastn_ds::wasm::exports::Tejar::new(
+            self.root.join(
+                self.env("FASTN_TEJAR_DIR")
+                    .await
+                    .unwrap_or_else(|_| fastn_ds::wasm::exports::TEJAR_DEFAULT_DIR.to_string()),
+            ),
+        );
+        if let Some(v) = self.env_parsed("FASTN_TEJAR_MAX_VALUE_SIZE").await {
+            tejar.max_value_size = v;
+        }
+        if let Some(v) = self.env_parsed("FASTN_TEJAR_MAX_TOTAL_SIZE").await {
+            tejar.max_total_size = v;
+        }
+        tejar
+    }
+
+    async fn env_parsed<T: std::str::FromStr>(&self, key: &str) -> Option<T> {
+        let value = self.env(key).await.ok()?;
+        match value.parse() {
+            Ok(v) => Some(v),
+            Err(_) => {
+                tracing::warn!(msg = "ignoring invalid value", key = key, value = value);
+                None
+            }
+        }
+    }
+
     // This method will connect client request to the out of the world
     #[tracing::instrument(skip(req, extra_headers))]
     pub async fn http<T>(
@@ -497,6 +754,11 @@ impl DocumentStore {
             body = ?proxy_request.body(),
         );
 
+        let method = proxy_request.method().to_string();
+        if let Some(response) = self.http_fixtures.replay(&method, &url, req.body()) {
+            return response.map_err(HttpError::from);
+        }
+
         *proxy_request.body_mut() = Some(req.body().to_vec().into());
         let response = fastn_ds::http::DEFAULT_CLIENT
             .execute(proxy_request)
@@ -505,7 +767,10 @@ impl DocumentStore {
         tracing::info!("Response details");
         tracing::info!(status = ?response.status(),headers = ?response.headers());
 
-        Ok(fastn_ds::reqwest_util::to_http_response(response).await?)
+        let response = fastn_ds::reqwest_util::to_http_response(response).await?;
+        self.http_fixtures
+            .record(&method, &url, req.body(), &response);
+        Ok(response)
     }
 }
 
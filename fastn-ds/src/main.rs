@@ -7,20 +7,39 @@ async fn main() {
         body: vec![],
     };
 
-    let module = wasmtime::Module::from_binary(
-        &fastn_ds::WASM_ENGINE,
-        &tokio::fs::read(
-            "../../ft-sdk/sample-wasm/target/wasm32-unknown-unknown/release/sample_wasm.wasm",
+    let module = fastn_ds::wasm::Module::new(
+        wasmtime::Module::from_binary(
+            &fastn_ds::WASM_ENGINE,
+            &tokio::fs::read(
+                "../../ft-sdk/sample-wasm/target/wasm32-unknown-unknown/release/sample_wasm.wasm",
+            )
+            .await
+            .unwrap(),
         )
-        .await
         .unwrap(),
     )
     .unwrap();
 
-    let resp =
-        fastn_ds::wasm::process_http_request(req, None, module, Default::default(), "".to_string())
-            .await
-            .unwrap();
+    let resp = fastn_ds::wasm::process_http_request(
+        req,
+        None,
+        module,
+        Default::default(),
+        fastn_ds::sqlite::Pools::new(fastn_ds::Path::new(".")),
+        Default::default(),
+        fastn_ds::wasm::exports::Tejar::new(fastn_ds::Path::new(
+            fastn_ds::wasm::exports::TEJAR_DEFAULT_DIR,
+        )),
+        fastn_ds::wasm::exports::AwsConfig::read(&fastn_ds::DocumentStore::new(
+            ".",
+            Default::default(),
+        ))
+        .await,
+        Default::default(),
+        Default::default(),
+    )
+    .await
+    .unwrap();
 
     println!("{:?}", resp);
 }
@@ -0,0 +1,266 @@
+pub const DEFAULT_BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+pub const DEFAULT_STATEMENT_CACHE_CAPACITY: usize = 64;
+pub const DEFAULT_MAX_IDLE: usize = 8;
+
+/// `Pools` are the sqlite connection pools of a package, one read-write and one read-only pool
+/// per database file. Database paths are relative to the package root.
+///
+/// A database file is not created, except `fastn_ds::db::DEFAULT_SQLITE_PATH`, the database of a
+/// package without `FASTN_DB_URL`, by its read-write pool.
+///
+/// Connections of the read-write pools use WAL mode, so the read-only connections used at
+/// render time do not wait for writers. All connections have a busy timeout and a cache of
+/// prepared statements, use `rusqlite::Connection::prepare_cached`.
+#[derive(Debug, Clone)]
+pub struct Pools {
+    root: fastn_ds::Path,
+    pools: std::sync::Arc<scc::HashMap<(String, bool), Pool>>,
+    pub config: Config,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub busy_timeout: std::time::Duration,
+    pub statement_cache_capacity: usize,
+    /// connections kept open when not in use, per pool
+    pub max_idle: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            busy_timeout: DEFAULT_BUSY_TIMEOUT,
+            statement_cache_capacity: DEFAULT_STATEMENT_CACHE_CAPACITY,
+            max_idle: DEFAULT_MAX_IDLE,
+        }
+    }
+}
+
+impl Pools {
+    pub fn new(root: fastn_ds::Path) -> Pools {
+        Pools {
+            root,
+            pools: Default::default(),
+            config: Default::default(),
+        }
+    }
+
+    pub fn with_config(mut self, config: Config) -> Pools {
+        self.config = config;
+        self
+    }
+
+    pub fn get(&self, db: &str, read_only: bool) -> Pool {
+        let path = self.root.join(db).path;
+        let key = (path.to_string(), read_only);
+        if let Some(pool) = self.pools.get(&key) {
+            return pool.get().clone();
+        }
+
+        let pool = Pool {
+            inner: std::sync::Arc::new(PoolInner {
+                path,
+                read_only,
+                create: !read_only && db == fastn_ds::db::DEFAULT_SQLITE_PATH,
+                config: self.config.clone(),
+                idle: Default::default(),
+            }),
+        };
+        match self.pools.entry(key) {
+            scc::hash_map::Entry::Occupied(o) => o.get().clone(),
+            scc::hash_map::Entry::Vacant(v) => v.insert_entry(pool).get().clone(),
+        }
+    }
+
+    pub fn clear(&self) {
+        self.pools.clear();
+    }
+
+    /// drops the pools of `db`, idle connections are closed
+    pub fn remove(&self, db: &str) {
+        let path = self.root.join(db).path;
+        self.pools.retain(|(p, _), _| p != path.as_str());
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Pool {
+    inner: std::sync::Arc<PoolInner>,
+}
+
+#[derive(Debug)]
+struct PoolInner {
+    path: camino::Utf8PathBuf,
+    read_only: bool,
+    /// the database file is created if it does not exist
+    create: bool,
+    config: Config,
+    idle: std::sync::Mutex<Vec<rusqlite::Connection>>,
+}
+
+impl Pool {
+    /// an idle connection, or a new one if there is none
+    pub fn get(&self) -> rusqlite::Result<Connection> {
+        let idle = self.inner.idle.lock().unwrap().pop();
+        let conn = match idle {
+            Some(conn) => conn,
+            None => self.connect()?,
+        };
+
+        Ok(Connection {
+            conn: Some(conn),
+            pool: self.clone(),
+        })
+    }
+
+    fn connect(&self) -> rusqlite::Result<rusqlite::Connection> {
+        let inner = &self.inner;
+        let mode = if inner.read_only {
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY
+        } else if inner.create {
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE | rusqlite::OpenFlags::SQLITE_OPEN_CREATE
+        } else {
+            rusqlite::OpenFlags::SQLITE_OPEN_READ_WRITE
+        };
+        let flags =
+            mode | rusqlite::OpenFlags::SQLITE_OPEN_URI | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX;
+
+        let conn = rusqlite::Connection::open_with_flags(inner.path.as_str(), flags)?;
+        conn.busy_timeout(inner.config.busy_timeout)?;
+        conn.set_prepared_statement_cache_capacity(inner.config.statement_cache_capacity);
+        if !inner.read_only {
+            // the journal mode is stored in the database file, so read-only connections of
+            // this database use WAL too
+            conn.query_row("PRAGMA journal_mode = WAL", [], |_| Ok(()))?;
+            conn.execute_batch("PRAGMA synchronous = NORMAL")?;
+        }
+
+        tracing::info!(
+            msg = "opened sqlite connection",
+            path = inner.path.as_str(),
+            read_only = inner.read_only
+        );
+        Ok(conn)
+    }
+}
+
+/// `Connection` goes back to its pool when dropped
+pub struct Connection {
+    conn: Option<rusqlite::Connection>,
+    pool: Pool,
+}
+
+impl std::fmt::Debug for Connection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Connection")
+            .field("path", &self.pool.inner.path)
+            .field("read_only", &self.pool.inner.read_only)
+            .finish()
+    }
+}
+
+impl std::ops::Deref for Connection {
+    type Target = rusqlite::Connection;
+
+    fn deref(&self) -> &Self::Target {
+        self.conn.as_ref().unwrap()
+    }
+}
+
+impl std::ops::DerefMut for Connection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.conn.as_mut().unwrap()
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let conn = match self.conn.take() {
+            Some(conn) => conn,
+            None => return,
+        };
+
+        // a connection dropped in the middle of a transaction is not reused
+        if !conn.is_autocommit() {
+            return;
+        }
+
+        if let Ok(mut idle) = self.pool.inner.idle.lock() {
+            if idle.len() < self.pool.inner.config.max_idle {
+                idle.push(conn);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn pools() -> (std::path::PathBuf, super::Pools) {
+        let root =
+            std::env::temp_dir().join(format!("fastn-sqlite-{:016x}", rand::random::<u64>()));
+        std::fs::create_dir_all(&root).unwrap();
+        let pools = super::Pools::new(fastn_ds::Path::new(root.to_str().unwrap()));
+        (root, pools)
+    }
+
+    #[test]
+    fn only_the_default_database_is_created() {
+        let (root, pools) = pools();
+
+        assert!(pools.get("missing.sqlite", false).get().is_err());
+        assert!(pools.get("missing.sqlite", true).get().is_err());
+        assert!(!root.join("missing.sqlite").exists());
+
+        assert!(pools
+            .get(fastn_ds::db::DEFAULT_SQLITE_PATH, true)
+            .get()
+            .is_err());
+        let conn = pools
+            .get(fastn_ds::db::DEFAULT_SQLITE_PATH, false)
+            .get()
+            .unwrap();
+        assert!(root.join(fastn_ds::db::DEFAULT_SQLITE_PATH).exists());
+        assert_eq!(
+            conn.query_row("PRAGMA journal_mode", [], |r| r.get::<_, String>(0))
+                .unwrap(),
+            "wal"
+        );
+
+        drop(conn);
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn read_only_and_idle_connections() {
+        let (root, pools) = pools();
+        let db = fastn_ds::db::DEFAULT_SQLITE_PATH;
+        pools
+            .get(db, false)
+            .get()
+            .unwrap()
+            .execute_batch("CREATE TABLE t (v INTEGER); INSERT INTO t VALUES (1);")
+            .unwrap();
+
+        let read_only = pools.get(db, true);
+        {
+            let conn = read_only.get().unwrap();
+            assert!(conn.execute("INSERT INTO t VALUES (2)", []).is_err());
+            assert_eq!(
+                conn.query_row("SELECT COUNT(*) FROM t", [], |r| r.get::<_, i64>(0))
+                    .unwrap(),
+                1
+            );
+        }
+        // the connection went back to the pool, and is reused
+        assert_eq!(read_only.inner.idle.lock().unwrap().len(), 1);
+        let _conn = read_only.get().unwrap();
+        assert_eq!(read_only.inner.idle.lock().unwrap().len(), 0);
+
+        // a connection dropped in a transaction is closed
+        let read_write = pools.get(db, false);
+        read_write.get().unwrap().execute_batch("BEGIN").unwrap();
+        assert_eq!(read_write.inner.idle.lock().unwrap().len(), 0);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
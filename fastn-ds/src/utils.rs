@@ -1,3 +1,57 @@
 pub fn ignore_headers() -> Vec<&'static str> {
     vec!["host", "x-forwarded-ssl"]
 }
+
+/// `500ms` is milliseconds, `30` and `30s` are seconds, `5m` minutes, `1h` hours and `1d` days,
+/// `None` if the unit is unknown or the duration overflows
+pub fn parse_duration(v: &str) -> Option<std::time::Duration> {
+    let v = v.trim();
+    let (number, unit) = v.split_at(v.find(|c: char| !c.is_ascii_digit()).unwrap_or(v.len()));
+    let millis: u64 = match unit.trim() {
+        "ms" => 1,
+        "" | "s" => 1000,
+        "m" => 60 * 1000,
+        "h" => 60 * 60 * 1000,
+        "d" => 24 * 60 * 60 * 1000,
+        _ => return None,
+    };
+    number
+        .parse::<u64>()
+        .ok()?
+        .checked_mul(millis)
+        .map(std::time::Duration::from_millis)
+}
+
+pub fn sha256(data: &[u8]) -> Vec<u8> {
+    use sha2::Digest;
+
+    sha2::Sha256::digest(data).to_vec()
+}
+
+/// lowercase hex of `bytes`
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_duration() {
+        let secs = std::time::Duration::from_secs;
+        assert_eq!(super::parse_duration("30"), Some(secs(30)));
+        assert_eq!(super::parse_duration("30s"), Some(secs(30)));
+        assert_eq!(super::parse_duration(" 2 m "), Some(secs(120)));
+        assert_eq!(super::parse_duration("1h"), Some(secs(3600)));
+        assert_eq!(super::parse_duration("1d"), Some(secs(86400)));
+        assert_eq!(
+            super::parse_duration("500ms"),
+            Some(std::time::Duration::from_millis(500))
+        );
+        assert_eq!(super::parse_duration(""), None);
+        assert_eq!(super::parse_duration("s"), None);
+        assert_eq!(super::parse_duration("-5s"), None);
+        assert_eq!(super::parse_duration("1.5s"), None);
+        assert_eq!(super::parse_duration("5 parsecs"), None);
+        assert_eq!(super::parse_duration("18446744073709551615s"), None);
+    }
+}
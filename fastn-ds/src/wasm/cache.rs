@@ -0,0 +1,303 @@
+pub const DEFAULT_MAX_SIZE: u64 = 1024 * 1024 * 1024;
+const EXTENSION: &str = "wasmc";
+const CHECKSUM_LENGTH: usize = 32;
+
+/// `WasmCache` keeps compiled modules on disk, so a module is compiled once and not on every
+/// start of `fastn serve`. Entries are named after the sha256 of the `.wasm` and the sha256 of
+/// the configuration of `WASM_ENGINE`, a changed `.wasm` or a new fastn release never picks up
+/// a stale entry. Every entry starts with the sha256 of the compiled module, an entry that is
+/// truncated or corrupted, or does not match the engine, is dropped and compiled again.
+///
+/// Compiled modules are loaded as native code, anyone who can write to `dir` can run code in
+/// fastn, so `dir` must only be writable by the user running fastn.
+///
+/// Once the entries are larger than `max_size` the least recently used are removed.
+#[derive(Debug, Clone)]
+pub struct WasmCache {
+    pub dir: camino::Utf8PathBuf,
+    /// limit on the size of all entries together, in bytes
+    pub max_size: u64,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum WasmCacheError {
+    #[error("wasm error {0}")]
+    Wasm(#[from] wasmtime::Error),
+    #[error("io error {1}: {0}")]
+    IOError(std::io::Error, camino::Utf8PathBuf),
+}
+
+impl WasmCache {
+    pub fn new(dir: camino::Utf8PathBuf) -> WasmCache {
+        WasmCache {
+            dir,
+            max_size: DEFAULT_MAX_SIZE,
+        }
+    }
+
+    /// `fastn/wasm` in the cache directory of the user
+    pub fn default_dir() -> camino::Utf8PathBuf {
+        dirs::cache_dir()
+            .and_then(|v| camino::Utf8PathBuf::from_path_buf(v).ok())
+            .unwrap_or_else(|| camino::Utf8PathBuf::from(".fastn"))
+            .join("fastn")
+            .join("wasm")
+    }
+
+    /// name of the entry for `source`
+    pub fn key(source: &[u8]) -> String {
+        use std::hash::Hash;
+
+        // not `DefaultHasher`, its output can change with the rust release fastn is built with
+        let mut engine = EngineHash::default();
+        fastn_ds::WASM_ENGINE
+            .precompile_compatibility_hash()
+            .hash(&mut engine);
+
+        format!(
+            "{}-{}",
+            fastn_ds::utils::hex(&fastn_ds::utils::sha256(source)),
+            &fastn_ds::utils::hex(&fastn_ds::utils::sha256(&engine.0))[..16]
+        )
+    }
+
+    fn path(&self, key: &str) -> camino::Utf8PathBuf {
+        self.dir.join(format!("{key}.{EXTENSION}"))
+    }
+
+    /// the compiled module for `source`, from the cache if possible
+    pub async fn get_or_compile(&self, source: &[u8]) -> Result<wasmtime::Module, WasmCacheError> {
+        let key = WasmCache::key(source);
+        if let Some(module) = self.get(key.as_str()).await {
+            return Ok(module);
+        }
+
+        let module = wasmtime::Module::from_binary(&fastn_ds::WASM_ENGINE, source)?;
+        self.put(key.as_str(), &module).await?;
+        self.evict().await?;
+        Ok(module)
+    }
+
+    async fn get(&self, key: &str) -> Option<wasmtime::Module> {
+        let path = self.path(key);
+        let content = tokio::fs::read(&path).await.ok()?;
+
+        let (checksum, artifact) = content.split_at(CHECKSUM_LENGTH.min(content.len()));
+        let module = match checksum == fastn_ds::utils::sha256(artifact).as_slice() {
+            true => {
+                // safety: the artifact was written by `put` with an engine of the same
+                //         configuration, as long as `dir` is trusted, see `WasmCache`. The
+                //         checksum is in the same file, it only catches corrupted entries.
+                unsafe { wasmtime::Module::deserialize(&fastn_ds::WASM_ENGINE, artifact) }
+                    .map_err(|e| e.to_string())
+            }
+            false => Err("checksum mismatch".to_string()),
+        };
+
+        match module {
+            Ok(module) => {
+                tracing::info!(msg = "loaded compiled wasm from cache", key = key);
+                // the modification time is used to find the least recently used entries
+                if let Err(e) = touch(&path) {
+                    tracing::warn!(msg = "failed to touch wasm cache entry", path = %path, e = %e);
+                }
+                Some(module)
+            }
+            Err(e) => {
+                tracing::warn!(msg = "dropping invalid wasm cache entry", path = %path, e = e);
+                tokio::fs::remove_file(&path).await.ok();
+                None
+            }
+        }
+    }
+
+    async fn put(&self, key: &str, module: &wasmtime::Module) -> Result<(), WasmCacheError> {
+        let artifact = module.serialize()?;
+        let mut content = fastn_ds::utils::sha256(&artifact);
+        content.extend(artifact);
+
+        let path = self.path(key);
+        let io_error = |e| WasmCacheError::IOError(e, path.clone());
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(io_error)?;
+
+        // write to a hidden file and rename, so a concurrent reader never sees half an entry
+        let temp = self
+            .dir
+            .join(format!(".{key}.{}.tmp", rand::random::<u64>()));
+        tokio::fs::write(&temp, content).await.map_err(io_error)?;
+        if let Err(e) = tokio::fs::rename(&temp, &path).await {
+            tokio::fs::remove_file(&temp).await.ok();
+            return Err(io_error(e));
+        }
+
+        Ok(())
+    }
+
+    /// removes the least recently used entries till all of them fit in `max_size`
+    pub async fn evict(&self) -> Result<(), WasmCacheError> {
+        let mut entries = self.entries().await?;
+        let mut size = entries.iter().map(|(_, size, _)| size).sum::<u64>();
+        entries.sort_by_key(|(_, _, modified)| *modified);
+
+        for (path, entry_size, _) in entries {
+            if size <= self.max_size {
+                break;
+            }
+            tracing::info!(msg = "evicting wasm cache entry", path = %path);
+            tokio::fs::remove_file(&path)
+                .await
+                .map_err(|e| WasmCacheError::IOError(e, path.clone()))?;
+            size -= entry_size;
+        }
+
+        Ok(())
+    }
+
+    /// all the entries with their size and modification time
+    async fn entries(
+        &self,
+    ) -> Result<Vec<(camino::Utf8PathBuf, u64, std::time::SystemTime)>, WasmCacheError> {
+        let io_error = |e| WasmCacheError::IOError(e, self.dir.clone());
+        let mut read_dir = match tokio::fs::read_dir(&self.dir).await {
+            Ok(v) => v,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(io_error(e)),
+        };
+
+        let mut entries = vec![];
+        while let Some(entry) = read_dir.next_entry().await.map_err(io_error)? {
+            let path = match camino::Utf8PathBuf::from_path_buf(entry.path()) {
+                Ok(path) => path,
+                Err(_) => continue,
+            };
+            if path.extension() != Some(EXTENSION) {
+                continue;
+            }
+            let metadata = entry.metadata().await.map_err(io_error)?;
+            entries.push((
+                path,
+                metadata.len(),
+                metadata.modified().unwrap_or(std::time::UNIX_EPOCH),
+            ));
+        }
+
+        Ok(entries)
+    }
+}
+
+/// collects the bytes a `Hash` implementation writes
+#[derive(Default)]
+struct EngineHash(Vec<u8>);
+
+impl std::hash::Hasher for EngineHash {
+    fn finish(&self) -> u64 {
+        unimplemented!("the bytes are hashed with sha256")
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+}
+
+fn touch(path: &camino::Utf8Path) -> std::io::Result<()> {
+    std::fs::File::options()
+        .append(true)
+        .open(path)?
+        .set_modified(std::time::SystemTime::now())
+}
+
+#[cfg(test)]
+mod tests {
+    /// an empty module with the custom section `name`, so every name compiles to another entry
+    fn source(name: u8) -> Vec<u8> {
+        vec![
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, 0x00, 0x02, 0x01, name,
+        ]
+    }
+
+    fn cache() -> super::WasmCache {
+        super::WasmCache::new(
+            camino::Utf8PathBuf::from_path_buf(
+                std::env::temp_dir()
+                    .join(format!("fastn-wasm-cache-{:016x}", rand::random::<u64>())),
+            )
+            .unwrap(),
+        )
+    }
+
+    fn set_modified(path: &camino::Utf8Path, secs: u64) {
+        std::fs::File::options()
+            .append(true)
+            .open(path)
+            .unwrap()
+            .set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    #[test]
+    fn key() {
+        let key = super::WasmCache::key(&source(b'a'));
+        let (source_hash, engine_hash) = key.split_once('-').unwrap();
+        assert_eq!(
+            source_hash,
+            fastn_ds::utils::hex(&fastn_ds::utils::sha256(&source(b'a')))
+        );
+        assert_eq!(engine_hash.len(), 16);
+        assert_eq!(super::WasmCache::key(&source(b'a')), key);
+        assert_ne!(super::WasmCache::key(&source(b'b')), key);
+    }
+
+    #[tokio::test]
+    async fn rejects_a_modified_entry() {
+        let cache = cache();
+        let key = super::WasmCache::key(&source(b'a'));
+        cache.get_or_compile(&source(b'a')).await.unwrap();
+        assert!(cache.get(key.as_str()).await.is_some());
+
+        let path = cache.path(key.as_str());
+        let mut content = std::fs::read(&path).unwrap();
+        let last = content.len() - 1;
+        content[last] ^= 0xff;
+        std::fs::write(&path, content).unwrap();
+
+        assert!(cache.get(key.as_str()).await.is_none());
+        assert!(!path.exists());
+        // compiled again
+        cache.get_or_compile(&source(b'a')).await.unwrap();
+        assert!(cache.get(key.as_str()).await.is_some());
+
+        std::fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn evicts_the_least_recently_used_entry() {
+        let mut cache = cache();
+        let mut paths = vec![];
+        for (i, name) in [b'a', b'b', b'c'].into_iter().enumerate() {
+            cache.get_or_compile(&source(name)).await.unwrap();
+            let path = cache.path(super::WasmCache::key(&source(name)).as_str());
+            set_modified(&path, 1000 + i as u64);
+            paths.push(path);
+        }
+
+        // reading `a` makes `b` the least recently used
+        assert!(cache
+            .get(super::WasmCache::key(&source(b'a')).as_str())
+            .await
+            .is_some());
+
+        let size: u64 = paths
+            .iter()
+            .map(|p| std::fs::metadata(p).unwrap().len())
+            .sum();
+        cache.max_size = size - 1;
+        cache.evict().await.unwrap();
+        assert!(paths[0].exists());
+        assert!(!paths[1].exists());
+        assert!(paths[2].exists());
+
+        std::fs::remove_dir_all(&cache.dir).unwrap();
+    }
+}
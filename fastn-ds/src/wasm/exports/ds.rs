@@ -1,15 +1,362 @@
+/// `hostn_tejar_write` takes `{"op": "write", "key": "..", "value": [..]}` or
+/// `{"op": "delete", "key": ".."}` and returns `{"Ok": null}` or `{"Err": <TejarError>}`.
 pub async fn tejar_write(
-    _caller: wasmtime::Caller<'_, fastn_ds::wasm::Store>,
-    _ptr: i32,
-    _len: i32,
+    mut caller: wasmtime::Caller<'_, fastn_ds::wasm::Store>,
+    ptr: i32,
+    len: i32,
 ) -> wasmtime::Result<i32> {
-    unimplemented!()
+    let bytes = fastn_ds::wasm::helpers::get_bytes(ptr, len, &mut caller)?;
+    let tejar = caller.data().tejar.clone();
+    let res = match serde_json::from_slice::<TejarWrite>(&bytes) {
+        Ok(TejarWrite::Write { key, value }) => tejar.write(key.as_str(), &value).await,
+        Ok(TejarWrite::Delete { key }) => tejar.delete(key.as_str()).await,
+        Err(e) => Err(TejarError::InvalidRequest {
+            message: e.to_string(),
+        }),
+    };
+    fastn_ds::wasm::helpers::send_json(res, &mut caller).await
 }
 
+/// `hostn_tejar_read` takes `{"op": "read", "key": ".."}`, returning the value as bytes, or
+/// `{"op": "list", "prefix": ".."}`, returning the sorted keys starting with `prefix`.
 pub async fn tejar_read(
-    _caller: wasmtime::Caller<'_, fastn_ds::wasm::Store>,
-    _ptr: i32,
-    _len: i32,
+    mut caller: wasmtime::Caller<'_, fastn_ds::wasm::Store>,
+    ptr: i32,
+    len: i32,
 ) -> wasmtime::Result<i32> {
-    unimplemented!()
+    let bytes = fastn_ds::wasm::helpers::get_bytes(ptr, len, &mut caller)?;
+    let tejar = caller.data().tejar.clone();
+    match serde_json::from_slice::<TejarRead>(&bytes) {
+        Ok(TejarRead::Read { key }) => {
+            fastn_ds::wasm::helpers::send_json(tejar.read(key.as_str()).await, &mut caller).await
+        }
+        Ok(TejarRead::List { prefix }) => {
+            fastn_ds::wasm::helpers::send_json(tejar.list(prefix.as_str()).await, &mut caller).await
+        }
+        Err(e) => {
+            fastn_ds::wasm::helpers::send_json(
+                Err::<(), _>(TejarError::InvalidRequest {
+                    message: e.to_string(),
+                }),
+                &mut caller,
+            )
+            .await
+        }
+    }
+}
+
+/// inside `.fastn`, `fastn serve` does not serve the dot directories of the package
+pub const TEJAR_DEFAULT_DIR: &str = ".fastn/tejar";
+pub const TEJAR_DEFAULT_MAX_VALUE_SIZE: usize = 1024 * 1024;
+pub const TEJAR_DEFAULT_MAX_TOTAL_SIZE: u64 = 100 * 1024 * 1024;
+const MAX_KEY_LENGTH: usize = 512;
+
+/// a `Tejar` is created for every request, writes to the same `root` take this lock so two
+/// requests can not both pass the quota check
+static WRITE_LOCKS: once_cell::sync::Lazy<
+    scc::HashMap<camino::Utf8PathBuf, std::sync::Arc<async_lock::Mutex<()>>>,
+> = once_cell::sync::Lazy::new(Default::default);
+
+/// `Tejar` is the key/blob store available to wasm handlers. Every key is stored as a file
+/// below `root`. Keys are `/` separated segments of `[a-zA-Z0-9._-]` that do not start with a
+/// `.`, so a handler can not reach anything outside `root`.
+#[derive(Debug, Clone)]
+pub struct Tejar {
+    pub root: fastn_ds::Path,
+    /// largest value `write` accepts, in bytes
+    pub max_value_size: usize,
+    /// limit on the size of all the values together, in bytes
+    pub max_total_size: u64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+pub enum TejarRead {
+    Read {
+        key: String,
+    },
+    List {
+        #[serde(default)]
+        prefix: String,
+    },
+}
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+pub enum TejarWrite {
+    Write { key: String, value: Vec<u8> },
+    Delete { key: String },
+}
+
+#[derive(thiserror::Error, Debug, serde::Serialize, PartialEq)]
+#[serde(tag = "error", rename_all = "kebab-case")]
+pub enum TejarError {
+    #[error("invalid request: {message}")]
+    InvalidRequest { message: String },
+    #[error("invalid key: {key}")]
+    InvalidKey { key: String },
+    #[error("key not found: {key}")]
+    NotFound { key: String },
+    #[error("value of {size} bytes is larger than the limit of {limit} bytes")]
+    ValueTooLarge { size: usize, limit: usize },
+    #[error("store would use {size} bytes, the limit is {limit} bytes")]
+    QuotaExceeded { size: u64, limit: u64 },
+    #[error("io error: {message}")]
+    IOError { message: String },
+}
+
+impl From<std::io::Error> for TejarError {
+    fn from(e: std::io::Error) -> Self {
+        TejarError::IOError {
+            message: e.to_string(),
+        }
+    }
+}
+
+impl Tejar {
+    pub fn new(root: fastn_ds::Path) -> Tejar {
+        Tejar {
+            root,
+            max_value_size: TEJAR_DEFAULT_MAX_VALUE_SIZE,
+            max_total_size: TEJAR_DEFAULT_MAX_TOTAL_SIZE,
+        }
+    }
+
+    fn path(&self, key: &str) -> Result<camino::Utf8PathBuf, TejarError> {
+        let valid = !key.is_empty()
+            && key.len() <= MAX_KEY_LENGTH
+            && key.split('/').all(|segment| {
+                !segment.is_empty()
+                    && !segment.starts_with('.')
+                    && segment
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+            });
+
+        if !valid {
+            return Err(TejarError::InvalidKey {
+                key: key.to_string(),
+            });
+        }
+
+        Ok(self.root.path.join(key))
+    }
+
+    pub async fn read(&self, key: &str) -> Result<Vec<u8>, TejarError> {
+        match tokio::fs::read(self.path(key)?).await {
+            Ok(v) => Ok(v),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(TejarError::NotFound {
+                key: key.to_string(),
+            }),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn write(&self, key: &str, value: &[u8]) -> Result<(), TejarError> {
+        let path = self.path(key)?;
+        if value.len() > self.max_value_size {
+            return Err(TejarError::ValueTooLarge {
+                size: value.len(),
+                limit: self.max_value_size,
+            });
+        }
+
+        let lock = WRITE_LOCKS
+            .entry(self.root.path.clone())
+            .or_default()
+            .get()
+            .clone();
+        let _guard = lock.lock().await;
+
+        let size = self
+            .entries()
+            .await?
+            .into_iter()
+            .filter(|(k, _)| k != key)
+            .map(|(_, size)| size)
+            .sum::<u64>()
+            + value.len() as u64;
+        if size > self.max_total_size {
+            return Err(TejarError::QuotaExceeded {
+                size,
+                limit: self.max_total_size,
+            });
+        }
+
+        // write to a hidden file and rename, so readers never see half written values
+        let parent = path.parent().unwrap_or(self.root.path.as_path());
+        tokio::fs::create_dir_all(parent).await?;
+        let temp = parent.join(format!(".{}.tmp", rand::random::<u64>()));
+        tokio::fs::write(&temp, value).await?;
+        if let Err(e) = tokio::fs::rename(&temp, &path).await {
+            tokio::fs::remove_file(&temp).await.ok();
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    /// deleting a key that does not exist is not an error
+    pub async fn delete(&self, key: &str) -> Result<(), TejarError> {
+        match tokio::fs::remove_file(self.path(key)?).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn list(&self, prefix: &str) -> Result<Vec<String>, TejarError> {
+        let mut keys = self
+            .entries()
+            .await?
+            .into_iter()
+            .map(|(k, _)| k)
+            .filter(|k| k.starts_with(prefix))
+            .collect::<Vec<_>>();
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// all the keys with the size of their value
+    async fn entries(&self) -> Result<Vec<(String, u64)>, TejarError> {
+        let mut entries = vec![];
+        let mut dirs = vec![self.root.path.clone()];
+        while let Some(dir) = dirs.pop() {
+            let mut read_dir = match tokio::fs::read_dir(&dir).await {
+                Ok(v) => v,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            };
+            while let Some(entry) = read_dir.next_entry().await? {
+                let path = match camino::Utf8PathBuf::from_path_buf(entry.path()) {
+                    Ok(path) => path,
+                    Err(_) => continue,
+                };
+                if path.file_name().map_or(true, |v| v.starts_with('.')) {
+                    continue;
+                }
+                let metadata = entry.metadata().await?;
+                if metadata.is_dir() {
+                    dirs.push(path);
+                } else if let Ok(key) = path.strip_prefix(&self.root.path) {
+                    entries.push((key.as_str().replace('\\', "/"), metadata.len()));
+                }
+            }
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn tejar() -> super::Tejar {
+        super::Tejar::new(fastn_ds::Path::new(
+            std::env::temp_dir()
+                .join(format!("fastn-tejar-{:016x}", rand::random::<u64>()))
+                .to_str()
+                .unwrap(),
+        ))
+    }
+
+    #[test]
+    fn path() {
+        let tejar = tejar();
+        assert_eq!(
+            tejar.path("a/b-c_d.txt").unwrap(),
+            tejar.root.path.join("a/b-c_d.txt")
+        );
+        for key in [
+            "",
+            "..",
+            "../secret",
+            "a/../../secret",
+            "a/./b",
+            ".hidden",
+            "a/.tmp",
+            "/etc/passwd",
+            "a/",
+            "a//b",
+            "a\\b",
+            "..\\secret",
+            "c:secret",
+            "a b",
+            "a\0b",
+        ] {
+            assert_eq!(
+                tejar.path(key),
+                Err(super::TejarError::InvalidKey {
+                    key: key.to_string()
+                }),
+                "{key:?}"
+            );
+        }
+        assert!(tejar
+            .path("a".repeat(super::MAX_KEY_LENGTH).as_str())
+            .is_ok());
+        assert!(tejar
+            .path("a".repeat(super::MAX_KEY_LENGTH + 1).as_str())
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn write_read_list_delete() {
+        let tejar = tejar();
+        tejar.write("b/two", b"2").await.unwrap();
+        tejar.write("a/one", b"1").await.unwrap();
+        tejar.write("a/one", b"one").await.unwrap();
+
+        assert_eq!(tejar.read("a/one").await, Ok(b"one".to_vec()));
+        assert_eq!(
+            tejar.list("").await,
+            Ok(vec!["a/one".to_string(), "b/two".to_string()])
+        );
+        assert_eq!(tejar.list("a/").await, Ok(vec!["a/one".to_string()]));
+
+        tejar.delete("a/one").await.unwrap();
+        tejar.delete("a/one").await.unwrap();
+        assert_eq!(
+            tejar.read("a/one").await,
+            Err(super::TejarError::NotFound {
+                key: "a/one".to_string()
+            })
+        );
+
+        std::fs::remove_dir_all(&tejar.root.path).unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_writes_stay_within_the_quota() {
+        let mut tejar = tejar();
+        tejar.max_total_size = 10;
+
+        let writes = (0..20)
+            .map(|i| {
+                let tejar = tejar.clone();
+                tokio::spawn(async move { tejar.write(format!("key-{i}").as_str(), b"12").await })
+            })
+            .collect::<Vec<_>>();
+        let mut written = 0;
+        for write in writes {
+            match write.await.unwrap() {
+                Ok(()) => written += 1,
+                Err(e) => assert!(matches!(e, super::TejarError::QuotaExceeded { .. }), "{e}"),
+            }
+        }
+
+        assert_eq!(written, 5);
+        // a handler of another request gets its own `Tejar` for the same root
+        let other = super::Tejar {
+            max_total_size: 10,
+            ..super::Tejar::new(fastn_ds::Path::new(tejar.root.path.as_str()))
+        };
+        assert!(matches!(
+            other.write("other", b"1").await,
+            Err(super::TejarError::QuotaExceeded {
+                size: 11,
+                limit: 10
+            })
+        ));
+
+        std::fs::remove_dir_all(&tejar.root.path).unwrap();
+    }
 }
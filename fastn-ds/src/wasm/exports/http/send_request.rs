@@ -5,6 +5,17 @@ pub async fn send_request(
 ) -> wasmtime::Result<i32> {
     let r: ft_sys_shared::Request = fastn_ds::wasm::helpers::get_json(ptr, len, &mut caller)?;
 
+    let http_fixtures = caller.data().http_fixtures.clone();
+    if let Some(response) = http_fixtures.replay(r.method.as_str(), r.uri.as_str(), &r.body) {
+        let response = match response {
+            Ok(response) => ft_sys_shared::Request::from(response),
+            // `replay` keeps the miss for `fastn test` to report, the handler sees a failed
+            // request instead of a trap
+            Err(e) => ft_sys_shared::Request::server_error(e.to_string()),
+        };
+        return fastn_ds::wasm::helpers::send_json(response, &mut caller).await;
+    }
+
     let mut headers = reqwest::header::HeaderMap::new();
     for (header_name, header_value) in r.headers {
         let header_name = reqwest::header::HeaderName::from_bytes(header_name.as_bytes()).unwrap(); // todo: remove unwrap()
@@ -13,9 +24,11 @@ pub async fn send_request(
         headers.insert(header_name, header_value);
     }
     let reqwest_response = if r.method.to_uppercase().eq("GET") {
-        reqwest::Client::new().get(r.uri)
+        reqwest::Client::new().get(r.uri.as_str())
     } else {
-        reqwest::Client::new().post(r.uri).body(r.body)
+        reqwest::Client::new()
+            .post(r.uri.as_str())
+            .body(r.body.clone())
     }
     .headers(headers)
     .send()
@@ -26,6 +39,7 @@ pub async fn send_request(
         response = response.header(header_name, header_value);
     }
     let response = response.body(reqwest_response.bytes().await?)?;
+    http_fixtures.record(r.method.as_str(), r.uri.as_str(), &r.body, &response);
 
     fastn_ds::wasm::helpers::send_json(ft_sys_shared::Request::from(response), &mut caller).await
 }
@@ -6,3 +6,6 @@ mod http;
 mod pg;
 mod register;
 mod sqlite;
+
+pub use aws::{AwsConfig, AwsError};
+pub use ds::{Tejar, TejarError, TEJAR_DEFAULT_DIR};
@@ -10,29 +10,18 @@ pub async fn connect(
 impl fastn_ds::wasm::Store {
     pub async fn pg_connect(&mut self, db_url: &str) -> wasmtime::Result<i32> {
         let db_url = if db_url == "default" {
-            self.db_url.as_str()
+            self.db_url.clone()
         } else {
-            db_url
+            fastn_ds::db::DbUrl::parse(db_url)?
         };
 
-        let mut clients = self.clients.lock().await;
-
-        return match self.pg_pools.get(db_url) {
-            Some(pool) => get_client(pool.get(), &mut clients).await,
-            None => {
-                let pool = fastn_ds::create_pool(db_url).await?;
-                fastn_ds::insert_or_update(&self.pg_pools, db_url.to_string(), pool);
-                get_client(self.pg_pools.get(db_url).unwrap().get(), &mut clients).await
-            }
-        };
+        let pool = fastn_ds::db::Pool::get(&db_url, &self.pg_pools, &self.sqlite_pools, false)
+            .await?
+            .postgres()?;
+        let client = pool.get().await?;
 
-        async fn get_client(
-            pool: &deadpool_postgres::Pool,
-            clients: &mut Vec<fastn_ds::wasm::Conn>,
-        ) -> wasmtime::Result<i32> {
-            let client = pool.get().await?;
-            clients.push(fastn_ds::wasm::Conn { client });
-            Ok(clients.len() as i32 - 1)
-        }
+        let mut clients = self.clients.lock().await;
+        clients.push(fastn_ds::wasm::Conn { client });
+        Ok(clients.len() as i32 - 1)
     }
 }
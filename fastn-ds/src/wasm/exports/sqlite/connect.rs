@@ -10,11 +10,16 @@ pub async fn connect(
 
 impl fastn_ds::wasm::Store {
     pub async fn sqlite_connect(&mut self, db_url: &str) -> wasmtime::Result<i32> {
-        let db = rusqlite::Connection::open(if db_url == "default" {
-            self.db_url.as_str()
+        let db_url = if db_url == "default" {
+            self.db_url.clone()
         } else {
-            db_url
-        })?;
+            fastn_ds::db::DbUrl::parse(db_url)?
+        };
+
+        let db = fastn_ds::db::Pool::get(&db_url, &self.pg_pools, &self.sqlite_pools, false)
+            .await?
+            .sqlite()?
+            .get()?;
 
         self.sqlite = Some(std::sync::Arc::new(async_lock::Mutex::new(db)));
         Ok(0)
@@ -22,7 +22,10 @@ impl fastn_ds::wasm::Store {
         };
 
         let conn = conn.lock().await;
-        match conn.execute(q.sql.as_str(), rusqlite::params_from_iter(q.binds)) {
+        match conn
+            .prepare_cached(q.sql.as_str())
+            .and_then(|mut stmt| stmt.execute(rusqlite::params_from_iter(q.binds)))
+        {
             Ok(cursor) => Ok(Ok(cursor)),
             Err(e) => Ok(Err(ft_sys_shared::DbError::UnableToSendCommand(
                 e.to_string(),
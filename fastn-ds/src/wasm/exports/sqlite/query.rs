@@ -87,7 +87,7 @@ impl fastn_ds::wasm::Store {
 
         let conn = conn.lock().await;
         println!("conn, sql: {}", q.sql.as_str());
-        let mut stmt = match conn.prepare(q.sql.as_str()) {
+        let mut stmt = match conn.prepare_cached(q.sql.as_str()) {
             Ok(v) => v,
             Err(e) => {
                 return Ok(Err(ft_sys_shared::DbError::UnableToSendCommand(
@@ -0,0 +1,196 @@
+pub const DEFAULT_MEMORY: usize = 256 * 1024 * 1024;
+pub const DEFAULT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+/// how often the epoch of `WASM_ENGINE` is incremented, a running handler yields to the executor
+/// on every tick so the timeout can interrupt it
+pub const EPOCH_TICK: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// `Limits` caps the resources a single wasm request can use. They are set per endpoint in
+/// `fastn.url-mappings`:
+///
+/// ```ftd
+/// -- fastn.url-mappings:
+///
+/// /api/* -> wasm+proxy://api.wasm/* fuel=50000000 memory=64MB timeout=5s
+/// ```
+///
+/// `FASTN_WASM_FUEL`, `FASTN_WASM_MEMORY` and `FASTN_WASM_TIMEOUT` set the package wide defaults.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limits {
+    /// roughly the number of wasm instructions a request can execute, `None` is unlimited
+    pub fuel: Option<u64>,
+    /// size the linear memory can grow to, in bytes
+    pub memory: usize,
+    /// wall clock time for the request, time spent in host calls (db, http) included
+    pub timeout: std::time::Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            fuel: None,
+            memory: DEFAULT_MEMORY,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum LimitsError {
+    #[error("unknown wasm limit {0}, expected fuel, memory or timeout")]
+    UnknownLimit(String),
+    #[error("invalid value for wasm limit {0}: {1}")]
+    InvalidValue(String, String),
+}
+
+/// The limit that stopped a request
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Limit {
+    Fuel,
+    Memory,
+    Timeout,
+}
+
+impl std::fmt::Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Limit::Fuel => write!(f, "fuel"),
+            Limit::Memory => write!(f, "memory"),
+            Limit::Timeout => write!(f, "timeout"),
+        }
+    }
+}
+
+impl Limit {
+    /// `504 Gateway Timeout` when the request took too long, `503 Service Unavailable` otherwise
+    pub fn status(&self) -> u16 {
+        match self {
+            Limit::Timeout => 504,
+            Limit::Fuel | Limit::Memory => 503,
+        }
+    }
+}
+
+impl Limits {
+    /// `options` are `fuel`, `memory` (`64MB`, `512KB` or bytes) and `timeout` (`5s`, `500ms`,
+    /// `2m`), anything not given is left as is
+    pub fn with_options<'a>(
+        self,
+        options: impl IntoIterator<Item = (&'a String, &'a String)>,
+    ) -> Result<Limits, LimitsError> {
+        let mut limits = self;
+        for (key, value) in options {
+            let invalid = || LimitsError::InvalidValue(key.to_string(), value.to_string());
+            match key.as_str() {
+                "fuel" => limits.fuel = Some(value.trim().parse().map_err(|_| invalid())?),
+                "memory" => limits.memory = parse_size(value).ok_or_else(invalid)?,
+                "timeout" => {
+                    limits.timeout = fastn_ds::utils::parse_duration(value).ok_or_else(invalid)?
+                }
+                t => return Err(LimitsError::UnknownLimit(t.to_string())),
+            }
+        }
+        Ok(limits)
+    }
+}
+
+pub(crate) fn parse_size(v: &str) -> Option<usize> {
+    let v = v.trim().to_uppercase();
+    let (number, unit) = v.split_at(v.find(|c: char| !c.is_ascii_digit()).unwrap_or(v.len()));
+    let multiplier = match unit.trim() {
+        "" | "B" => 1,
+        "K" | "KB" => 1024,
+        "M" | "MB" => 1024 * 1024,
+        "G" | "GB" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    number.parse::<usize>().ok()?.checked_mul(multiplier)
+}
+
+impl wasmtime::ResourceLimiter for fastn_ds::wasm::Store {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        desired: usize,
+        _maximum: Option<usize>,
+    ) -> wasmtime::Result<bool> {
+        if desired > self.limits.memory {
+            self.limit_exceeded = Some(Limit::Memory);
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    fn table_growing(
+        &mut self,
+        _current: u32,
+        _desired: u32,
+        _maximum: Option<u32>,
+    ) -> wasmtime::Result<bool> {
+        Ok(true)
+    }
+}
+
+static EPOCH_TICKER: std::sync::Once = std::sync::Once::new();
+
+/// starts the thread that drives epoch interruption of `WASM_ENGINE`
+pub(crate) fn start_epoch_ticker() {
+    EPOCH_TICKER.call_once(|| {
+        std::thread::spawn(|| loop {
+            std::thread::sleep(EPOCH_TICK);
+            fastn_ds::WASM_ENGINE.increment_epoch();
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_size() {
+        assert_eq!(super::parse_size("1024"), Some(1024));
+        assert_eq!(super::parse_size("10B"), Some(10));
+        assert_eq!(super::parse_size("512KB"), Some(512 * 1024));
+        assert_eq!(super::parse_size("512k"), Some(512 * 1024));
+        assert_eq!(super::parse_size(" 64 MB "), Some(64 * 1024 * 1024));
+        assert_eq!(super::parse_size("1gb"), Some(1024 * 1024 * 1024));
+        assert_eq!(super::parse_size(""), None);
+        assert_eq!(super::parse_size("MB"), None);
+        assert_eq!(super::parse_size("-1MB"), None);
+        assert_eq!(super::parse_size("1.5MB"), None);
+        assert_eq!(super::parse_size("1TB"), None);
+        assert_eq!(super::parse_size("18446744073709551615G"), None);
+    }
+
+    #[test]
+    fn with_options() {
+        let options = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<std::collections::BTreeMap<_, _>>()
+        };
+
+        assert_eq!(
+            super::Limits::default().with_options(&options(&[
+                ("fuel", " 100 "),
+                ("memory", "64MB"),
+                ("timeout", "500ms"),
+            ])),
+            Ok(super::Limits {
+                fuel: Some(100),
+                memory: 64 * 1024 * 1024,
+                timeout: std::time::Duration::from_millis(500),
+            })
+        );
+        assert_eq!(
+            super::Limits::default().with_options(&options(&[("timeout", "5 parsecs")])),
+            Err(super::LimitsError::InvalidValue(
+                "timeout".to_string(),
+                "5 parsecs".to_string()
+            ))
+        );
+        assert_eq!(
+            super::Limits::default().with_options(&options(&[("cpu", "1")])),
+            Err(super::LimitsError::UnknownLimit("cpu".to_string()))
+        );
+    }
+}
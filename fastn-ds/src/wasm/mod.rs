@@ -1,79 +1,120 @@
+mod cache;
 pub mod exports;
 pub mod helpers;
+mod limits;
 pub mod macros;
+mod module;
 mod store;
 
+pub use cache::{WasmCache, WasmCacheError};
+pub use limits::{Limit, Limits, LimitsError};
+pub(crate) use module::allocation_strategy;
+pub use module::{new_store, AppliedMigration, MigrationError, Module, LINKER};
 pub use store::{Conn, Store};
 
+#[allow(clippy::too_many_arguments)]
 #[tracing::instrument(skip_all)]
 pub async fn process_http_request(
     req: ft_sys_shared::Request,
     ud: Option<ft_sys_shared::UserData>,
-    module: wasmtime::Module,
+    module: fastn_ds::wasm::Module,
     wasm_pg_pools: actix_web::web::Data<scc::HashMap<String, deadpool_postgres::Pool>>,
-    db_url: String,
+    sqlite_pools: fastn_ds::sqlite::Pools,
+    db_url: fastn_ds::db::DbUrl,
+    tejar: fastn_ds::wasm::exports::Tejar,
+    aws: Result<fastn_ds::wasm::exports::AwsConfig, fastn_ds::wasm::exports::AwsError>,
+    limits: fastn_ds::wasm::Limits,
+    http_fixtures: fastn_ds::http_fixtures::HttpFixtures,
 ) -> wasmtime::Result<ft_sys_shared::Request> {
     let path = req.uri.clone();
-    let hostn_store = fastn_ds::wasm::Store::new(req, ud, wasm_pg_pools, db_url);
-    let mut linker = wasmtime::Linker::new(module.engine());
-    hostn_store.register_functions(&mut linker);
+    let start = std::time::Instant::now();
+    let timeout = limits.timeout;
+    let wasm_store = fastn_ds::wasm::new_store(fastn_ds::wasm::Store::new(
+        req,
+        ud,
+        wasm_pg_pools,
+        sqlite_pools,
+        db_url,
+        tejar,
+        aws,
+        limits,
+        http_fixtures,
+    ))?;
+
+    let limit = match tokio::time::timeout(timeout, run(module, wasm_store, path.clone())).await {
+        Ok(Ok(Ok(response))) => return Ok(response),
+        Ok(Ok(Err(limit))) => limit,
+        Ok(Err(e)) => return Err(e),
+        Err(_) => fastn_ds::wasm::Limit::Timeout,
+    };
 
-    let mut wasm_store = wasmtime::Store::new(module.engine(), hostn_store);
+    tracing::warn!(
+        msg = "wasm request stopped",
+        limit = %limit,
+        path = path,
+        elapsed_ms = start.elapsed().as_millis() as u64,
+    );
+
+    Ok(ft_sys_shared::Request {
+        uri: path,
+        method: limit.status().to_string(),
+        headers: vec![(
+            "content-type".to_string(),
+            b"text/plain; charset=utf-8".to_vec(),
+        )],
+        body: format!("request exceeded the {limit} limit").into_bytes(),
+    })
+}
 
-    let instance = match linker.instantiate_async(&mut wasm_store, &module).await {
+/// runs the handler, `Err(limit)` if it was stopped by one of the (non time) limits
+async fn run(
+    module: fastn_ds::wasm::Module,
+    mut wasm_store: wasmtime::Store<fastn_ds::wasm::Store>,
+    path: String,
+) -> wasmtime::Result<Result<ft_sys_shared::Request, fastn_ds::wasm::Limit>> {
+    let instance = match module.instance_pre.instantiate_async(&mut wasm_store).await {
         Ok(i) => i,
         Err(e) => {
-            return Ok(ft_sys_shared::Request::server_error(format!(
+            if let Some(limit) = exceeded_limit(&wasm_store, &e) {
+                return Ok(Err(limit));
+            }
+            return Ok(Ok(ft_sys_shared::Request::server_error(format!(
                 "failed to instantiate wasm module: {e:?}"
-            )));
-        }
-    };
-
-    let wasm_store = match apply_migration(instance, wasm_store).await {
-        Ok(((), store)) => store,
-        Err(e) => {
-            return Ok(ft_sys_shared::Request::server_error(format!(
-                "failed to apply migration: {e:?}"
-            )));
+            ))));
         }
     };
 
-    let (main, mut wasm_store) = get_entrypoint(instance, wasm_store, path)?;
-    main.call_async(&mut wasm_store, ()).await?;
+    let (main, mut wasm_store) = get_entrypoint(instance, wasm_store, path.clone())?;
+    if let Err(e) = main.call_async(&mut wasm_store, ()).await {
+        return match exceeded_limit(&wasm_store, &e) {
+            Some(limit) => Ok(Err(limit)),
+            None => Err(e),
+        };
+    }
 
-    Ok(wasm_store.into_data().response.unwrap_or_else(|| {
-        // actix_web::HttpResponse::InternalServerError().body("no response from wasm")
-        eprintln!("wasm file returned no http response");
-        todo!()
+    Ok(Ok(match wasm_store.into_data().response {
+        Some(response) => response,
+        None => {
+            tracing::error!(msg = "wasm handler returned no http response", path = path);
+            ft_sys_shared::Request::server_error(
+                "wasm handler returned no http response".to_string(),
+            )
+        }
     }))
 }
 
-#[derive(Debug, thiserror::Error)]
-enum ApplyMigrationError {
-    #[error("failed to get migration__entrypoint: {0}")]
-    GetMigrationEntrypoint(#[from] wasmtime::Error),
-    #[error("migration failed: {0}")]
-    MigrationFailed(i32),
-}
-
-async fn apply_migration(
-    instance: wasmtime::Instance,
-    mut store: wasmtime::Store<fastn_ds::wasm::Store>,
-) -> Result<((), wasmtime::Store<fastn_ds::wasm::Store>), ApplyMigrationError> {
-    let ep = match instance.get_typed_func::<(), i32>(&mut store, "migration__entrypoint") {
-        Ok(v) => v,
-        Err(e) => {
-            println!("failed to get migration__entrypoint ({e}), proceeding without migration");
-            return Ok(((), store));
-        }
-    };
-
-    let i = ep.call_async(&mut store, ()).await?;
-    if i != 0 {
-        return Err(ApplyMigrationError::MigrationFailed(i));
+pub(crate) fn exceeded_limit(
+    wasm_store: &wasmtime::Store<fastn_ds::wasm::Store>,
+    e: &wasmtime::Error,
+) -> Option<fastn_ds::wasm::Limit> {
+    if let Some(limit) = wasm_store.data().limit_exceeded {
+        return Some(limit);
     }
 
-    Ok(((), store))
+    match e.downcast_ref::<wasmtime::Trap>() {
+        Some(wasmtime::Trap::OutOfFuel) => Some(fastn_ds::wasm::Limit::Fuel),
+        _ => None,
+    }
 }
 
 pub fn get_entrypoint(
@@ -88,7 +129,7 @@ pub fn get_entrypoint(
         return Ok((f, store));
     }
     let entrypoint = path_to_entrypoint(path)?;
-    println!("main_ft not found, trying {entrypoint}");
+    tracing::info!(msg = "main_ft not found", entrypoint = entrypoint);
     instance
         .get_typed_func(&mut store, entrypoint.as_str())
         .map(|v| (v, store))
@@ -116,7 +157,7 @@ fn path_to_entrypoint(path: String) -> wasmtime::Result<String> {
 }
 
 pub fn to_response(req: ft_sys_shared::Request) -> actix_web::HttpResponse {
-    println!("{req:?}");
+    tracing::debug!(msg = "wasm response", status = req.method, uri = req.uri);
     let mut builder = actix_web::HttpResponse::build(req.method.parse().unwrap());
     let mut resp = builder.status(req.method.parse().unwrap()).body(req.body);
 
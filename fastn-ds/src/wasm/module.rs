@@ -0,0 +1,187 @@
+const WASM_PAGE_SIZE: usize = 64 * 1024;
+
+/// All the host functions, shared by every module.
+pub static LINKER: once_cell::sync::Lazy<wasmtime::Linker<fastn_ds::wasm::Store>> =
+    once_cell::sync::Lazy::new(|| {
+        let mut linker = wasmtime::Linker::new(&fastn_ds::WASM_ENGINE);
+        fastn_ds::wasm::Store::register_functions(&mut linker);
+        linker
+    });
+
+/// `Module` is a compiled wasm module with its imports already resolved against `LINKER`, so
+/// a request only has to instantiate it, see `allocation_strategy`.
+#[derive(Clone)]
+pub struct Module {
+    pub module: wasmtime::Module,
+    pub instance_pre: wasmtime::InstancePre<fastn_ds::wasm::Store>,
+}
+
+impl std::fmt::Debug for Module {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Module")
+            .field("module", &self.module)
+            .finish()
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MigrationError {
+    #[error("failed to run migration__entrypoint: {0}")]
+    Wasm(#[from] wasmtime::Error),
+    #[error("migration failed: {0}")]
+    Failed(i32),
+    #[error("migration exceeded the {0} limit")]
+    Limit(fastn_ds::wasm::Limit),
+}
+
+/// `AppliedMigration` records that `migration__entrypoint` of a module ran against `db_url`
+#[derive(Debug, Clone)]
+pub struct AppliedMigration {
+    pub db_url: fastn_ds::db::DbUrl,
+    /// modification time of the `.wasm` file the migration came from
+    pub modified: Option<std::time::SystemTime>,
+    pub applied_on: chrono::DateTime<chrono::Utc>,
+    /// false if the module has no `migration__entrypoint`
+    pub has_migration: bool,
+}
+
+impl Module {
+    pub fn new(module: wasmtime::Module) -> wasmtime::Result<Module> {
+        Ok(Module {
+            instance_pre: LINKER.instantiate_pre(&module)?,
+            module,
+        })
+    }
+
+    /// runs `migration__entrypoint` with the limits of `hostn_store`, returns false if the
+    /// module does not have one
+    pub async fn migrate(
+        &self,
+        hostn_store: fastn_ds::wasm::Store,
+    ) -> Result<bool, MigrationError> {
+        let timeout = hostn_store.limits.timeout;
+        let mut store = new_store(hostn_store)?;
+
+        let result = tokio::time::timeout(timeout, self.run_migration(&mut store)).await;
+        match result {
+            Ok(Err(MigrationError::Wasm(e))) => match fastn_ds::wasm::exceeded_limit(&store, &e) {
+                Some(limit) => Err(MigrationError::Limit(limit)),
+                None => Err(MigrationError::Wasm(e)),
+            },
+            Ok(result) => result,
+            Err(_) => Err(MigrationError::Limit(fastn_ds::wasm::Limit::Timeout)),
+        }
+    }
+
+    async fn run_migration(
+        &self,
+        store: &mut wasmtime::Store<fastn_ds::wasm::Store>,
+    ) -> Result<bool, MigrationError> {
+        let instance = self.instance_pre.instantiate_async(&mut *store).await?;
+
+        let ep = match instance.get_typed_func::<(), i32>(&mut *store, "migration__entrypoint") {
+            Ok(v) => v,
+            Err(e) => {
+                tracing::info!(msg = "no migration__entrypoint, proceeding without migration", e = %e);
+                return Ok(false);
+            }
+        };
+
+        let i = ep.call_async(&mut *store, ()).await?;
+        if i != 0 {
+            return Err(MigrationError::Failed(i));
+        }
+
+        Ok(true)
+    }
+}
+
+/// store for one call into a module, with the limits of `hostn_store` applied
+pub fn new_store(
+    hostn_store: fastn_ds::wasm::Store,
+) -> wasmtime::Result<wasmtime::Store<fastn_ds::wasm::Store>> {
+    fastn_ds::wasm::limits::start_epoch_ticker();
+
+    let fuel = hostn_store.limits.fuel.unwrap_or(u64::MAX);
+    let mut store = wasmtime::Store::new(&fastn_ds::WASM_ENGINE, hostn_store);
+    store.limiter(|s| s as &mut dyn wasmtime::ResourceLimiter);
+    store.set_fuel(fuel)?;
+    // yield to the executor on every epoch tick, so a timeout can stop a busy handler
+    store.epoch_deadline_async_yield_and_update(1);
+    Ok(store)
+}
+
+/// Instances are allocated on demand. `FASTN_WASM_POOL_INSTANCES=<n>` allocates them from a pool
+/// of `n` slots instead, which is faster but reserves the address space of every slot up front.
+/// `FASTN_WASM_POOL_MEMORY` is the largest memory an instance of the pool can grow to, it
+/// defaults to `fastn_ds::wasm::limits::DEFAULT_MEMORY`.
+pub(crate) fn allocation_strategy() -> wasmtime::InstanceAllocationStrategy {
+    let instances = match std::env::var("FASTN_WASM_POOL_INSTANCES")
+        .ok()
+        .and_then(|v| v.trim().parse::<u32>().ok())
+    {
+        Some(instances) if instances > 0 => instances,
+        _ => return wasmtime::InstanceAllocationStrategy::OnDemand,
+    };
+    let memory = std::env::var("FASTN_WASM_POOL_MEMORY")
+        .ok()
+        .and_then(|v| fastn_ds::wasm::limits::parse_size(v.as_str()))
+        .unwrap_or(fastn_ds::wasm::limits::DEFAULT_MEMORY);
+
+    let mut config = wasmtime::PoolingAllocationConfig::default();
+    config
+        .total_core_instances(instances)
+        .total_memories(instances)
+        .total_tables(instances)
+        .memory_pages(memory.div_ceil(WASM_PAGE_SIZE) as u64);
+    wasmtime::InstanceAllocationStrategy::Pooling(config)
+}
+
+#[cfg(test)]
+mod tests {
+    /// a module without imports that only exports `migration__entrypoint`, returning 0
+    const MIGRATION_WASM: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // magic and version
+        0x01, 0x05, 0x01, 0x60, 0x00, 0x01, 0x7f, // type: () -> i32
+        0x03, 0x02, 0x01, 0x00, // function 0 has type 0
+        0x07, 0x19, 0x01, 0x15, b'm', b'i', b'g', b'r', b'a', b't', b'i', b'o', b'n', b'_', b'_',
+        b'e', b'n', b't', b'r', b'y', b'p', b'o', b'i', b'n', b't', 0x00, 0x00, // export
+        0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x00, 0x0b, // i32.const 0
+    ];
+
+    #[tokio::test]
+    async fn get_wasm_migrates_once_per_path_and_db() {
+        let root =
+            std::env::temp_dir().join(format!("fastn-wasm-module-{:016x}", rand::random::<u64>()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("app.wasm"), MIGRATION_WASM).unwrap();
+
+        let root_dir = camino::Utf8PathBuf::from_path_buf(root.clone()).unwrap();
+        let ds = fastn_ds::DocumentStore::new(&root_dir, Default::default())
+            .with_wasm_cache_dir(root_dir.join("cache"))
+            .with_db_url(fastn_ds::db::DbUrl::Sqlite("a.sqlite".to_string()));
+        let path = root.join("app.wasm").to_str().unwrap().to_string();
+        let applied_on = |ds: &fastn_ds::DocumentStore| {
+            let applied = ds.wasm_migrations.get(&path).unwrap();
+            assert!(applied.get().has_migration);
+            applied.get().applied_on
+        };
+
+        ds.get_wasm(path.as_str()).await.unwrap();
+        let first = applied_on(&ds);
+        ds.get_wasm(path.as_str()).await.unwrap();
+        assert_eq!(ds.wasm_modules.len(), 1);
+        assert_eq!(applied_on(&ds), first);
+
+        // another database runs the migration again
+        let ds = ds.with_db_url(fastn_ds::db::DbUrl::Sqlite("b.sqlite".to_string()));
+        assert_eq!(ds.wasm_modules.len(), 0);
+        ds.get_wasm(path.as_str()).await.unwrap();
+        let second = applied_on(&ds);
+        assert!(second > first);
+        ds.get_wasm(path.as_str()).await.unwrap();
+        assert_eq!(applied_on(&ds), second);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -3,9 +3,17 @@ pub struct Store {
     pub ud: Option<ft_sys_shared::UserData>,
     pub clients: std::sync::Arc<async_lock::Mutex<Vec<Conn>>>,
     pub pg_pools: actix_web::web::Data<scc::HashMap<String, deadpool_postgres::Pool>>,
-    pub sqlite: Option<std::sync::Arc<async_lock::Mutex<rusqlite::Connection>>>,
+    pub sqlite_pools: fastn_ds::sqlite::Pools,
+    pub sqlite: Option<std::sync::Arc<async_lock::Mutex<fastn_ds::sqlite::Connection>>>,
     pub response: Option<ft_sys_shared::Request>,
-    pub db_url: String,
+    pub db_url: fastn_ds::db::DbUrl,
+    pub tejar: fastn_ds::wasm::exports::Tejar,
+    /// credentials of `hostn_aws_pre_signed_request`, or why they are missing
+    pub aws: Result<fastn_ds::wasm::exports::AwsConfig, fastn_ds::wasm::exports::AwsError>,
+    pub limits: fastn_ds::wasm::Limits,
+    /// set when the request is stopped for using more than `limits` allow
+    pub limit_exceeded: Option<fastn_ds::wasm::Limit>,
+    pub http_fixtures: fastn_ds::http_fixtures::HttpFixtures,
 }
 
 pub struct Conn {
@@ -13,11 +21,17 @@ pub struct Conn {
 }
 
 impl Store {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         req: ft_sys_shared::Request,
         ud: Option<ft_sys_shared::UserData>,
         pg_pools: actix_web::web::Data<scc::HashMap<String, deadpool_postgres::Pool>>,
-        db_url: String,
+        sqlite_pools: fastn_ds::sqlite::Pools,
+        db_url: fastn_ds::db::DbUrl,
+        tejar: fastn_ds::wasm::exports::Tejar,
+        aws: Result<fastn_ds::wasm::exports::AwsConfig, fastn_ds::wasm::exports::AwsError>,
+        limits: fastn_ds::wasm::Limits,
+        http_fixtures: fastn_ds::http_fixtures::HttpFixtures,
     ) -> Store {
         Self {
             req,
@@ -25,8 +39,14 @@ impl Store {
             response: None,
             clients: Default::default(),
             pg_pools,
+            sqlite_pools,
             db_url,
             sqlite: None,
+            tejar,
+            aws,
+            limits,
+            limit_exceeded: None,
+            http_fixtures,
         }
     }
 }
@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
 pub enum Ast {
     Component(fastn_js::Component),
     UDF(fastn_js::UDF), // user defined function
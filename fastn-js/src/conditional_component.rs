@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
 pub struct ConditionalComponent {
     pub deps: Vec<String>,
     pub condition: fastn_grammar::evalexpr::ExprNode,
@@ -1,4 +1,4 @@
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
 pub enum DeviceType {
     Desktop,
     Mobile,
@@ -14,7 +14,7 @@ impl From<&str> for DeviceType {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
 pub struct DeviceBlock {
     pub device: fastn_js::DeviceType,
     pub statements: Vec<fastn_js::ComponentStatement>,
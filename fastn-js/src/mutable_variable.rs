@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
 pub struct MutableVariable {
     pub name: String,
     pub value: fastn_js::SetPropertyValue,
@@ -21,7 +21,7 @@ pub fn mutable_string(name: &str, value: &str) -> fastn_js::ComponentStatement {
     })
 }
 
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
 pub struct MutableList {
     pub name: String,
     pub value: fastn_js::SetPropertyValue,
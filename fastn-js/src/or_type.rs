@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
 pub struct OrType {
     pub name: String,
     pub variant: fastn_js::SetPropertyValue,
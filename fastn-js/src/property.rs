@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
 pub struct SetProperty {
     pub kind: PropertyKind,
     pub value: SetPropertyValue,
@@ -6,7 +6,7 @@ pub struct SetProperty {
     pub inherited: String,
 }
 
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
 pub enum SetPropertyValue {
     Reference(String),
     Value(fastn_js::Value),
@@ -65,13 +65,13 @@ impl fastn_js::SetPropertyValue {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
 pub struct Formula {
     pub deps: Vec<String>,
     pub type_: FormulaType,
 }
 
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
 pub enum FormulaType {
     Conditional(Vec<ConditionalValue>),
     FunctionCall(fastn_js::Function),
@@ -121,7 +121,7 @@ impl Formula {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
 pub struct ConditionalValue {
     pub condition: Option<fastn_grammar::evalexpr::ExprNode>,
     pub expression: SetPropertyValue,
@@ -185,7 +185,7 @@ pub(crate) fn conditional_values_to_js(
     )
 }
 
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
 pub enum Value {
     String(String),
     Integer(i64),
@@ -307,7 +307,7 @@ impl Value {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
 pub enum PropertyKind {
     BreakpointWidth,
     Children,
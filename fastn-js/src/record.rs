@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, serde::Serialize)]
 pub struct RecordInstance {
     pub name: String,
     pub fields: fastn_js::SetPropertyValue,
@@ -70,6 +70,10 @@ pub struct EndpointData {
     pub mountpoint: String,
     #[serde(rename = "user-id")]
     pub user_id: Option<bool>,
+    /// `key=value` options after the endpoint in `fastn.url-mappings`, for `wasm+proxy://`
+    /// endpoints these are the limits: `fuel=50000000 memory=64MB timeout=5s`
+    #[serde(default)]
+    pub options: std::collections::BTreeMap<String, String>,
 }
 
 /// PackageTemp is a struct that is used for mapping the `fastn.package` data in FASTN.ftd file. It is
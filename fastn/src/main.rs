@@ -60,7 +60,9 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
         actix_web::web::Data::new(scc::HashMap::new());
 
     let current_dir: camino::Utf8PathBuf = std::env::current_dir()?.canonicalize()?.try_into()?;
-    let ds = fastn_ds::DocumentStore::new(current_dir, pg_pools);
+    let ds = fastn_ds::DocumentStore::new(current_dir, pg_pools)
+        .with_sqlite_config_from_env()
+        .await;
 
     if let Some(update) = matches.subcommand_matches("update") {
         let check = update.get_flag("check");
@@ -94,7 +96,8 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
             .add_external_js(external_js.clone())
             .add_inline_js(inline_js.clone())
             .add_external_css(external_css.clone())
-            .add_inline_css(inline_css.clone());
+            .add_inline_css(inline_css.clone())
+            .set_live_reload(serve.get_flag("watch"));
 
         return fastn_core::listen(std::sync::Arc::new(config), bind.as_str(), port).await;
     }
@@ -105,7 +108,8 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
         let inline_js = test.values_of_("js");
         let external_css = test.values_of_("external-css");
         let inline_css = test.values_of_("css");
-        let offline: bool = test.get_flag("offline");
+        // replaying tests do not touch the network
+        let offline: bool = test.get_flag("offline") || test.get_flag("replay");
 
         if !offline {
             fastn_update::update(&ds, false).await?;
@@ -121,13 +125,35 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
             .add_inline_css(inline_css)
             .set_test_command_running();
 
+        let jobs = match test.value_of_("jobs").map(|j| j.parse::<usize>()) {
+            Some(Ok(v)) if v > 0 => v,
+            None => 1,
+            Some(_) => {
+                eprintln!("--jobs has to be a number greater than 0.");
+                std::process::exit(1);
+            }
+        };
+
         return fastn_core::test(
             &config,
-            test.value_of_("file"), // TODO: handle more than one files
-            test.value_of_("base").unwrap_or("/"),
-            test.get_flag("headless"),
-            test.get_flag("script"),
-            test.get_flag("verbose"),
+            fastn_core::commands::test::TestOptions {
+                files: test.values_of_("file"),
+                filter: test.value_of_("filter").map(ToString::to_string),
+                headless: test.get_flag("headless"),
+                script: test.get_flag("script"),
+                verbose: test.get_flag("verbose"),
+                jobs,
+                http_fixtures: if test.get_flag("record") {
+                    fastn_ds::http_fixtures::Mode::Record
+                } else if test.get_flag("replay") {
+                    fastn_ds::http_fixtures::Mode::Replay
+                } else {
+                    fastn_ds::http_fixtures::Mode::Live
+                },
+                update_snapshots: test.get_flag("update-snapshots"),
+                junit_report: test.value_of_("junit").map(ToString::to_string),
+                json_report: test.value_of_("json-report").map(ToString::to_string),
+            },
         )
         .await;
     }
@@ -144,6 +170,10 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
         let inline_css = build.values_of_("css");
         let zip_url = build.value_of_("zip-url");
         let offline: bool = build.get_flag("offline");
+        let message_format: fastn_core::commands::check::MessageFormat = build
+            .value_of_("message-format")
+            .unwrap_or("human")
+            .parse()?;
 
         if !offline {
             fastn_update::update(&ds, false).await?;
@@ -158,7 +188,15 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
             .add_external_css(external_css)
             .add_inline_css(inline_css);
 
-        return fastn_core::build(
+        if message_format == fastn_core::commands::check::MessageFormat::Json {
+            let diagnostics = fastn_core::check(&config, build.value_of_("file")).await?;
+            if !diagnostics.is_empty() {
+                fastn_core::commands::check::print_diagnostics(&diagnostics, message_format);
+                std::process::exit(1);
+            }
+        }
+
+        let result = fastn_core::build(
             &config,
             build.value_of_("file"), // TODO: handle more than one files
             build.value_of_("base").unwrap_or("/"),
@@ -168,6 +206,20 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
             zip_url,
         )
         .await;
+
+        return match (result, message_format) {
+            (Err(e), fastn_core::commands::check::MessageFormat::Json) => {
+                fastn_core::commands::check::print_diagnostics(
+                    &[fastn_core::commands::check::Diagnostic::from_error(
+                        build.value_of_("file").unwrap_or_default(),
+                        &e,
+                    )],
+                    message_format,
+                );
+                std::process::exit(1);
+            }
+            (result, _) => result,
+        };
     }
 
     let config = fastn_core::Config::read(ds, true).await?;
@@ -177,24 +229,91 @@ async fn fastn_core_commands(matches: &clap::ArgMatches) -> fastn_core::Result<(
     }
 
     if let Some(wasmc) = matches.subcommand_matches("wasmc") {
-        if let Err(e) = fastn_ds::wasmc(wasmc.value_of_("file").unwrap()).await {
-            eprintln!("failed to compile: {e:?}");
-            std::process::exit(1);
-        }
+        return fastn_core::wasmc(&config, &wasmc.values_of_("file"), wasmc.get_flag("all")).await;
+    }
+
+    if let Some(migrate) = matches.subcommand_matches("migrate") {
+        let action = match migrate.subcommand() {
+            Some(("apply", _)) => fastn_core::commands::migrate::MigrateAction::Apply,
+            Some(("rollback-to", rollback)) => {
+                fastn_core::commands::migrate::MigrateAction::RollbackTo(
+                    rollback.value_of_("migration").unwrap().to_string(),
+                )
+            }
+            _ => fastn_core::commands::migrate::MigrateAction::Status,
+        };
+        return fastn_core::migrate(&config, action).await;
     }
 
     if let Some(query) = matches.subcommand_matches("query") {
+        let request = fastn_core::commands::query::mock_request(
+            query.value_of_("url").unwrap_or("/"),
+            &query.values_of_("cookie"),
+            &query.values_of_("header"),
+        )?;
         return fastn_core::query(
             &config,
             query.value_of_("stage").unwrap(),
             query.value_of_("path"),
             query.get_flag("null"),
+            &request,
+        )
+        .await;
+    }
+
+    if matches.subcommand_matches("lsp").is_some() {
+        return fastn_core::lsp(&config).await;
+    }
+
+    if let Some(check) = matches.subcommand_matches("check") {
+        let message_format: fastn_core::commands::check::MessageFormat = check
+            .value_of_("message-format")
+            .unwrap_or("human")
+            .parse()?;
+        let diagnostics = fastn_core::check(&config, check.value_of_("file")).await?;
+        fastn_core::commands::check::print_diagnostics(&diagnostics, message_format);
+        if !diagnostics.is_empty() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(i18n) = matches.subcommand_matches("i18n") {
+        let message_format: fastn_core::commands::check::MessageFormat = i18n
+            .value_of_("message-format")
+            .unwrap_or("human")
+            .parse()?;
+        let reports = fastn_core::commands::i18n::i18n(&config, message_format).await?;
+        if i18n.get_flag("strict") && reports.iter().any(|r| !r.missing.is_empty()) {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(deps) = matches.subcommand_matches("deps") {
+        let format: fastn_core::commands::deps::DepsFormat =
+            deps.value_of_("format").unwrap_or("human").parse()?;
+        return fastn_core::commands::deps::deps(&config, deps.value_of_("why"), format).await;
+    }
+
+    if let Some(translation_status) = matches.subcommand_matches("translation-status") {
+        let message_format: fastn_core::commands::check::MessageFormat = translation_status
+            .value_of_("message-format")
+            .unwrap_or("human")
+            .parse()?;
+        return fastn_core::commands::translation_status::translation_status(
+            &config,
+            message_format,
         )
         .await;
     }
 
-    if matches.subcommand_matches("check").is_some() {
-        return fastn_core::post_build_check(&config).await;
+    if let Some(mark) = matches.subcommand_matches("mark-upto-date") {
+        return fastn_core::commands::translation_status::mark_upto_date(
+            &config,
+            mark.value_of_("file").unwrap(),
+        )
+        .await;
     }
 
     Ok(())
@@ -266,6 +385,7 @@ fn app(version: &'static str) -> clap::Command {
                 .arg(clap::arg!(--"zip-url" <URL> "The zip archive url for this package"))
                 .arg(clap::arg!(--"ignore-failed" "Ignore failed files."))
                 .arg(clap::arg!(--"check-build" "Checks .build for index files validation."))
+                .arg(clap::arg!(--"message-format" <FORMAT> "How to print errors: human or json").default_value("human"))
                 .arg(clap::arg!(--"external-js" <URL> "Script added in ftd files")
                     .action(clap::ArgAction::Append))
                 .arg(clap::arg!(--"js" <URL> "Script text added in ftd files")
@@ -285,15 +405,31 @@ fn app(version: &'static str) -> clap::Command {
         )
         .subcommand(
             clap::Command::new("wasmc")
-                .about("Convert .wasm to .wasmc file")
+                .about("Compile .wasm files into the wasm cache")
                 .arg(clap::arg!(file: [FILE]... "The file to compile").required(false))
+                .arg(clap::arg!(--all "Compile every wasm endpoint in fastn.url-mappings"))
+        )
+        .subcommand(
+            clap::Command::new("migrate")
+                .about("Apply the .sql files in the `migrations` folder to FASTN_DB_URL")
+                .subcommand(clap::Command::new("status").about("List applied and pending migrations"))
+                .subcommand(clap::Command::new("apply").about("Apply the pending migrations"))
+                .subcommand(
+                    clap::Command::new("rollback-to")
+                        .about("Revert the migrations applied after the given one, 0 reverts all")
+                        .arg(clap::arg!(migration: <MIGRATION> "The id or number of the migration").required(true))
+                )
         )
         .subcommand(
             clap::Command::new("test")
                 .about("Run the test files in `_tests` folder")
-                .arg(clap::arg!(file: [FILE]... "The file to build (if specified only these are built, else entire package is built)"))
-                .arg(clap::arg!(-b --base [BASE] "The base path.").default_value("/"))
+                .arg(clap::arg!(file: [FILE]... "The test files to run, paths or globs like `_tests/auth-*` (if specified only these are run, else all tests are run)"))
+                .arg(clap::arg!(-b --base [BASE] "The base path, not used").default_value("/").hide(true))
                 .arg(clap::arg!(--"headless" "Run the test in headless mode"))
+                .arg(clap::arg!(--filter <TEXT> "Runs only the tests whose title or id contains TEXT"))
+                .arg(clap::arg!(-j --jobs <N> "The number of test files to run at the same time [default: 1]"))
+                .arg(clap::arg!(--junit <PATH> "Writes a JUnit XML report of the test results"))
+                .arg(clap::arg!(--"json-report" <PATH> "Writes a JSON report of the test results"))
                 .arg(clap::arg!(--"external-js" <URL> "Script added in ftd files")
                     .action(clap::ArgAction::Append))
                 .arg(clap::arg!(--"js" <URL> "Script text added in ftd files")
@@ -306,19 +442,54 @@ fn app(version: &'static str) -> clap::Command {
                 .arg(clap::arg!(--"script" "Generates a script file (for debugging purposes)"))
                 .arg(clap::arg!(--"verbose" "To provide more better logs (for debugging purposes)"))
                 .arg(clap::arg!(--offline "Disables automatic package update checks to operate in offline mode"))
+                .arg(clap::arg!(--record "Records the outgoing http requests of each test file in `_tests/http-fixtures`"))
+                .arg(clap::arg!(--replay "Serves the outgoing http requests from `_tests/http-fixtures`, failing on requests that were not recorded").conflicts_with("record"))
+                .arg(clap::arg!(--"update-snapshots" "Writes the rendered pages to the `fastn.snapshot` snapshots in `_tests/snapshots`"))
         )
         .subcommand(
             clap::Command::new("query")
                 .about("JSON Dump in various stages")
-                .arg(clap::arg!(--stage <STAGE> "The stage. Currently supported (p1)").required
+                .arg(clap::arg!(--stage <STAGE> "The stage: p1, ast, interpreter, js-ast or js").required
                 (true))
                 .arg(clap::arg!(-p --path [PATH] "The path of the file"))
                 .arg(clap::arg!(-n --null "JSON with null and empty list"))
+                .arg(clap::arg!(--url <URL> "The url of the mock request the processors see, with the query string").default_value("/"))
+                .arg(clap::arg!(--cookie <COOKIE> "A `name=value` cookie of the mock request")
+                    .action(clap::ArgAction::Append))
+                .arg(clap::arg!(--header <HEADER> "A `name: value` header of the mock request")
+                    .action(clap::ArgAction::Append))
         )
         .subcommand(
             clap::Command::new("check")
-                .about("Check if everything is fine with current fastn package")
-                .hide(true) // hidden since the feature is not being released yet.
+                .about("Type check every document of the current fastn package, without writing .build")
+                .arg(clap::arg!(file: [FILE] "The file to check (if specified only this is checked, else entire package is checked)"))
+                .arg(clap::arg!(--"message-format" <FORMAT> "How to print errors: human or json").default_value("human"))
+        )
+        .subcommand(
+            clap::Command::new("i18n")
+                .about("List the messages each language in i18n/ is missing, and the ones no document uses")
+                .arg(clap::arg!(--"message-format" <FORMAT> "How to print the report: human or json").default_value("human"))
+                .arg(clap::arg!(--strict "Exit with an error if a language is missing messages"))
+        )
+        .subcommand(
+            clap::Command::new("deps")
+                .about("Print the dependency tree of the package, or the documents that import a module")
+                .arg(clap::arg!(--why <MODULE> "Print the documents that import this document, module or package"))
+                .arg(clap::arg!(--format <FORMAT> "How to print: human, json or dot").default_value("human"))
+        )
+        .subcommand(
+            clap::Command::new("translation-status")
+                .about("Show which files of a translation package are missing, never marked, out of date or up to date")
+                .arg(clap::arg!(--"message-format" <FORMAT> "How to print the status: human or json").default_value("human"))
+        )
+        .subcommand(
+            clap::Command::new("mark-upto-date")
+                .about("Mark the translation of a file up to date with the latest version of the original")
+                .arg(clap::arg!(file: <FILE> "The file, like `index.ftd`").required(true))
+        )
+        .subcommand(
+            clap::Command::new("lsp")
+                .about("Start the language server for .ftd files (LSP over stdio)")
         )
         .subcommand(
             clap::Command::new("update")
@@ -347,7 +518,8 @@ mod sub_command {
             .arg(clap::arg!(--"css" <URL> "CSS text added in ftd files")
                 .action(clap::ArgAction::Append))
             .arg(clap::arg!(--"download-base-url" <URL> "If running without files locally, download needed files from here"))
-            .arg(clap::arg!(--offline "Disables automatic package update checks to operate in offline mode"));
+            .arg(clap::arg!(--offline "Disables automatic package update checks to operate in offline mode"))
+            .arg(clap::arg!(--watch "Reload the browser when package files change"));
         if cfg!(feature = "remote") {
             serve
         } else {
@@ -10449,6 +10449,14 @@ pub fn default_test_bag() -> indexmap::IndexMap<String, ftd::interpreter::Thing>
             "fastn#redirect".to_string(),
             ftd::interpreter::Thing::Component(fastn_redirect_function()),
         ),
+        (
+            "fastn#snapshot".to_string(),
+            ftd::interpreter::Thing::Component(fastn_snapshot_function()),
+        ),
+        (
+            "fastn#sql".to_string(),
+            ftd::interpreter::Thing::Component(fastn_sql_function()),
+        ),
         (
             "fastn#test".to_string(),
             ftd::interpreter::Thing::Component(fastn_test_function()),
@@ -10584,6 +10592,74 @@ pub fn fastn_redirect_function() -> ftd::interpreter::ComponentDefinition {
     }
 }
 
+pub fn fastn_snapshot_function() -> ftd::interpreter::ComponentDefinition {
+    ftd::interpreter::ComponentDefinition {
+        name: "fastn#snapshot".to_string(),
+        arguments: [vec![
+            ftd::interpreter::Argument::default(
+                "title",
+                ftd::interpreter::Kind::string().into_kind_data().caption(),
+            ),
+            ftd::interpreter::Argument::default(
+                "url",
+                ftd::interpreter::Kind::string().into_kind_data(),
+            ),
+            ftd::interpreter::Argument::default(
+                "format",
+                ftd::interpreter::Kind::string()
+                    .into_kind_data()
+                    .into_optional(),
+            ),
+            ftd::interpreter::Argument::default(
+                "id",
+                ftd::interpreter::Kind::string()
+                    .into_kind_data()
+                    .into_optional(),
+            ),
+        ]]
+        .concat()
+        .into_iter()
+        .collect(),
+        definition: ftd::interpreter::Component::from_name("ftd.kernel"),
+        css: None,
+        line_number: 0,
+    }
+}
+
+pub fn fastn_sql_function() -> ftd::interpreter::ComponentDefinition {
+    ftd::interpreter::ComponentDefinition {
+        name: "fastn#sql".to_string(),
+        arguments: [vec![
+            ftd::interpreter::Argument::default(
+                "title",
+                ftd::interpreter::Kind::string()
+                    .into_optional()
+                    .into_kind_data()
+                    .caption(),
+            ),
+            ftd::interpreter::Argument::default(
+                "file",
+                ftd::interpreter::Kind::string()
+                    .into_kind_data()
+                    .into_optional(),
+            ),
+            ftd::interpreter::Argument::default(
+                "query",
+                ftd::interpreter::Kind::string()
+                    .into_optional()
+                    .into_kind_data()
+                    .body(),
+            ),
+        ]]
+        .concat()
+        .into_iter()
+        .collect(),
+        definition: ftd::interpreter::Component::from_name("ftd.kernel"),
+        css: None,
+        line_number: 0,
+    }
+}
+
 pub fn fastn_test_function() -> ftd::interpreter::ComponentDefinition {
     ftd::interpreter::ComponentDefinition {
         name: "fastn#test".to_string(),
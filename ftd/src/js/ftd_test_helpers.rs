@@ -178,6 +178,7 @@ fn p(
                 js_script =
                     format!("{js_document_script}{}", test_available_code_themes()).as_str(),
                 favicon_html_tag = "",
+                language_links = "",
                 base_url_tag = "",
                 extra_js = "",
                 default_css = (if manual { ftd::ftd_js_css() } else { "" })